@@ -0,0 +1,295 @@
+//! headless tools for build machines, nothing here opens a window or needs a gpu
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use sdf_application::meshing::export::{self, MeshFormat};
+use sdf_application::meshing::mesh::{MeshSettings, Mesher, Progress};
+use sdf_application::packages::compute_render_pipeline::COMPUTE_SOURCE;
+use sdf_application::packages::path_trace_pipeline::PATH_TRACE_SOURCE;
+use sdf_application::packages::scene_render_pipeline::{interpreter_shader, SHADER_TEMPLATE};
+use sdf_application::packages::slice_pipeline::SLICE_SOURCE;
+use sdf_application::render::cpu;
+use sdf_application::render::image::Image;
+use sdf_application::scene::graph::Scene;
+use sdf_application::scene::{bytecode, compiler, file};
+
+const USAGE: &str = "\
+usage: sdf-cli <command> [options]
+
+commands:
+   render <scene.json> <output.png>    render the scene from its camera on the cpu, as the shader draws it
+      --width <pixels>                   default 1280
+      --height <pixels>                  default 720
+
+   mesh <scene.json> <output>          export the surface, the format comes from the extension (stl, obj, ply)
+      --mesher <name>                    marching-cubes (default) or dual-contouring
+      --resolution <cells>               cells along the longest side, default 128
+      --min <x,y,z>                      lower corner of the bounds
+      --max <x,y,z>                      upper corner of the bounds
+      --ascii                            write ascii stl
+      --open                             don't close surfaces cut off by the bounds
+
+   validate <scene.json>...            check files load and every shader builds for them
+
+   upgrade <scene.json>...             rewrite files at the current format version
+      --output <path>                    write here instead of in place, only with one file
+
+   diff <expected.png> <actual.png>    fail if the images differ, for golden image checks
+      --tolerance <0-255>                per channel difference ignored, default 2
+      --pixels <count>                   pixels allowed over the tolerance, default 0
+      --output <diff.png>                write the difference as a grey image
+
+use 'demo' in place of a scene path for the built in demo scene";
+
+
+fn main() -> ExitCode {
+   let args: Vec<String> = std::env::args().skip(1).collect();
+   let Some((command, args)) = args.split_first() else {
+      eprintln!("{USAGE}");
+      return ExitCode::FAILURE;
+   };
+
+   let result = match command.as_str() {
+      "render" => render(args),
+      "mesh" => mesh(args),
+      "validate" => validate(args),
+      "upgrade" => upgrade(args),
+      "diff" => diff(args),
+      "help" | "--help" | "-h" => {
+         println!("{USAGE}");
+         Ok(())
+      }
+      _ => Err(format!("unknown command '{command}'\n\n{USAGE}")),
+   };
+
+   match result {
+      Ok(()) => ExitCode::SUCCESS,
+      Err(error) => {
+         eprintln!("error: {error}");
+         ExitCode::FAILURE
+      }
+   }
+}
+
+
+/// positional arguments, `--name value` options and `--name` flags
+struct Options {
+   positional: Vec<String>,
+   values: HashMap<&'static str, String>,
+   flags: Vec<&'static str>,
+}
+impl Options {
+   fn parse(args: &[String], values: &[&'static str], flags: &[&'static str]) -> Result<Self, String> {
+      let mut options = Options { positional: vec![], values: HashMap::new(), flags: vec![] };
+      let mut args = args.iter();
+      while let Some(arg) = args.next() {
+         let Some(name) = arg.strip_prefix("--") else {
+            options.positional.push(arg.clone());
+            continue;
+         };
+         if let Some(name) = values.iter().find(|value| **value == name) {
+            let value = args.next().ok_or(format!("--{name} needs a value"))?;
+            options.values.insert(name, value.clone());
+         } else if let Some(name) = flags.iter().find(|flag| **flag == name) {
+            options.flags.push(name);
+         } else {
+            return Err(format!("unknown option '{arg}'"));
+         }
+      }
+      Ok(options)
+   }
+
+   fn value<T: FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+      match self.values.get(name) {
+         Some(value) => value.parse().map_err(|_| format!("--{name}: can't read '{value}'")),
+         None => Ok(default),
+      }
+   }
+
+   fn vector(&self, name: &str, default: [f32; 3]) -> Result<[f32; 3], String> {
+      let Some(value) = self.values.get(name) else { return Ok(default) };
+      let parts: Vec<f32> = value.split(',')
+          .map(|part| part.trim().parse())
+          .collect::<Result<_, _>>()
+          .map_err(|_| format!("--{name}: can't read '{value}', expected x,y,z"))?;
+      parts.try_into().map_err(|_| format!("--{name}: expected three numbers, got '{value}'"))
+   }
+
+   fn flag(&self, name: &str) -> bool {
+      self.flags.contains(&name)
+   }
+
+   /// exactly `N` positional arguments
+   fn paths<const N: usize>(&self, usage: &str) -> Result<[&str; N], String> {
+      let paths: Vec<&str> = self.positional.iter().map(String::as_str).collect();
+      paths.try_into().map_err(|_| format!("usage: sdf-cli {usage}"))
+   }
+}
+
+
+fn load(path: &str) -> Result<Scene, String> {
+   if path == "demo" {
+      return Ok(Scene::demo());
+   }
+   file::load(Path::new(path)).map_err(|error| format!("{path}: {error}"))
+}
+
+
+fn render(args: &[String]) -> Result<(), String> {
+   let options = Options::parse(args, &["width", "height"], &[])?;
+   let [scene_path, output] = options.paths("render <scene.json> <output.png>")?;
+   let width: u32 = options.value("width", 1280)?;
+   let height: u32 = options.value("height", 720)?;
+   if width == 0 || height == 0 {
+      return Err("the image needs to be at least one pixel across".to_string());
+   }
+
+   let scene = load(scene_path)?;
+   let image = cpu::render(&scene, width, height, &Progress::default()).ok_or("rendering was cancelled")?;
+   image.save_png(Path::new(output)).map_err(|error| format!("{output}: {error}"))?;
+   println!("wrote {width}x{height} image to {output}");
+   Ok(())
+}
+
+
+/// fails when two images differ by more than the tolerance, for checking renders against golden images
+fn diff(args: &[String]) -> Result<(), String> {
+   let options = Options::parse(args, &["tolerance", "pixels", "output"], &[])?;
+   let [expected_path, actual_path] = options.paths("diff <expected.png> <actual.png>")?;
+   let tolerance: u8 = options.value("tolerance", 2)?;
+   let allowed: usize = options.value("pixels", 0)?;
+
+   let read = |path: &str| Image::load_png(Path::new(path)).map_err(|error| format!("{path}: {error}"));
+   let (expected, actual) = (read(expected_path)?, read(actual_path)?);
+   let difference = expected.difference(&actual).ok_or(format!(
+      "{expected_path} is {}x{} but {actual_path} is {}x{}",
+      expected.width, expected.height, actual.width, actual.height,
+   ))?;
+
+   if let Some(output) = options.values.get("output") {
+      difference.image.save_png(Path::new(output)).map_err(|error| format!("{output}: {error}"))?;
+   }
+
+   let over = difference.count_above(tolerance);
+   println!("max difference {}, mean {:.3}, {over} pixels over {tolerance}", difference.max, difference.mean);
+   if over > allowed {
+      return Err(format!("{over} pixels differ by more than {tolerance}, {allowed} allowed"));
+   }
+   Ok(())
+}
+
+fn mesh(args: &[String]) -> Result<(), String> {
+   let options = Options::parse(args, &["mesher", "resolution", "min", "max"], &["ascii", "open"])?;
+   let [scene_path, output] = options.paths("mesh <scene.json> <output>")?;
+
+   let defaults = MeshSettings::default();
+   let settings = MeshSettings {
+      mesher: match options.value("mesher", "marching-cubes".to_string())?.as_str() {
+         "marching-cubes" | "mc" => Mesher::MarchingCubes,
+         "dual-contouring" | "dc" => Mesher::DualContouring,
+         other => return Err(format!("unknown mesher '{other}', expected marching-cubes or dual-contouring")),
+      },
+      min: options.vector("min", defaults.min)?,
+      max: options.vector("max", defaults.max)?,
+      resolution: options.value("resolution", defaults.resolution)?,
+      close_boundary: !options.flag("open"),
+   };
+   if (0..3).any(|axis| settings.min[axis] >= settings.max[axis]) {
+      return Err("--min has to be below --max on every axis".to_string());
+   }
+
+   let path = PathBuf::from(output);
+   let format = match MeshFormat::from_path(&path) {
+      Some(MeshFormat::StlBinary) if options.flag("ascii") => MeshFormat::StlAscii,
+      Some(format) => format,
+      None => return Err(format!("{output}: can't tell the format, use a .stl, .obj or .ply extension")),
+   };
+
+   let scene = load(scene_path)?;
+   let progress = Progress::default();
+   let mesh = thread::scope(|scope| {
+      let job = scope.spawn(|| settings.mesher.mesh(&scene, &settings, &progress));
+      let mut stage = "";
+      while !job.is_finished() {
+         if progress.stage() != stage {
+            stage = progress.stage();
+            eprintln!("{stage}...");
+         }
+         thread::sleep(Duration::from_millis(50));
+      }
+      job.join()
+   });
+   let mesh = mesh.map_err(|_| "the mesher panicked".to_string())?.ok_or("meshing was cancelled")?;
+
+   export::save(&path, &mesh, format).map_err(|error| format!("{output}: {error}"))?;
+   println!("wrote {} triangles to {output}", mesh.triangle_count());
+   Ok(())
+}
+
+
+fn validate(args: &[String]) -> Result<(), String> {
+   let options = Options::parse(args, &[], &[])?;
+   if options.positional.is_empty() {
+      return Err("usage: sdf-cli validate <scene.json>...".to_string());
+   }
+
+   let mut failed = 0;
+   for path in &options.positional {
+      match validate_file(path) {
+         Ok(note) => println!("{path}: ok{note}"),
+         Err(error) => {
+            println!("{path}: {error}");
+            failed += 1;
+         }
+      }
+   }
+   match failed {
+      0 => Ok(()),
+      _ => Err(format!("{failed} of {} files failed", options.positional.len())),
+   }
+}
+
+/// loads, then builds the compiled, path traced, compute, slice and interpreted versions of the scene the way the renderer would
+fn validate_file(path: &str) -> Result<String, String> {
+   let mut note = String::new();
+   let scene = if path == "demo" {
+      Scene::demo()
+   } else {
+      let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+      let version = file::version(&source).map_err(|error| error.to_string())?;
+      if version < file::FORMAT_VERSION {
+         note = format!(" (version {version}, upgrade to write it as version {})", file::FORMAT_VERSION);
+      }
+      file::from_str(&source).map_err(|error| error.to_string())?
+   };
+   let compiled = compiler::compile(SHADER_TEMPLATE, &scene).map_err(|error| format!("compiled shader: {error}"))?;
+   compiler::validate(&(compiled.clone() + PATH_TRACE_SOURCE)).map_err(|error| format!("path traced shader: {error}"))?;
+   compiler::validate(&(compiled.clone() + COMPUTE_SOURCE)).map_err(|error| format!("compute shader: {error}"))?;
+   compiler::validate(&(compiled + SLICE_SOURCE)).map_err(|error| format!("slice shader: {error}"))?;
+   interpreter_shader().map_err(|error| format!("interpreted shader: {error}"))?;
+   bytecode::flatten(&scene, None).map_err(|error| format!("interpreted program: {error}"))?;
+   Ok(note)
+}
+
+
+fn upgrade(args: &[String]) -> Result<(), String> {
+   let options = Options::parse(args, &["output"], &[])?;
+   let output = options.values.get("output");
+   match (options.positional.len(), output) {
+      (0, _) => return Err("usage: sdf-cli upgrade <scene.json>... [--output <path>]".to_string()),
+      (1, _) | (_, None) => {}
+      _ => return Err("--output only works with a single file".to_string()),
+   }
+
+   for path in &options.positional {
+      let scene = load(path)?;
+      let destination = output.map_or(path.as_str(), String::as_str);
+      file::save(Path::new(destination), &scene).map_err(|error| format!("{destination}: {error}"))?;
+      println!("wrote {destination} as version {}", file::FORMAT_VERSION);
+   }
+   Ok(())
+}
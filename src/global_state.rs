@@ -2,11 +2,15 @@ use wgpu::Extent3d;
 use log::error;
 use std::iter;
 use winit::dpi::{PhysicalSize};
-use winit::event::WindowEvent;
+use winit::event::{ElementState, KeyEvent, Modifiers, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 use crate::inbuilt::gui_state::EguiRenderer;
 use crate::inbuilt::setup::Setup;
-use crate::packages::test_render_pipeline::TestRenderPipeline;
+use crate::packages::post_process_pipeline::PostProcessPipeline;
+use crate::packages::scene_render_pipeline::SceneRenderPipeline;
+use crate::scene::document::Document;
+use crate::scene::graph::Scene;
 use crate::ui::ui_state::UiState;
 use crate::utility::structs::EguiTexturePackage;
 
@@ -17,10 +21,15 @@ pub struct GlobalState<'a> {
    ui_state: UiState,
 
    egui_texture_package: EguiTexturePackage,
+   slice_texture_package: EguiTexturePackage,
 
    // time_package: TimePackage,
 
-   test_render_pipeline: TestRenderPipeline,
+   document: Document,
+   scene_render_pipeline: SceneRenderPipeline,
+   post_process_pipeline: PostProcessPipeline,
+
+   modifiers: Modifiers,
 }
 impl<'a> GlobalState<'a> {
    pub fn new(window: &'a Window) -> GlobalState<'a> {
@@ -28,7 +37,8 @@ impl<'a> GlobalState<'a> {
 
       let mut egui_renderer = EguiRenderer::new(&setup.device, setup.config.format, None, 1, setup.window);
 
-      let test_render_pipeline = TestRenderPipeline::new(&setup);
+      let scene = Scene::demo();
+      let scene_render_pipeline = SceneRenderPipeline::new(&setup, &scene);
 
       let egui_texture_package = EguiTexturePackage::new(&setup, &mut egui_renderer, Extent3d {
          width: 250,
@@ -36,6 +46,15 @@ impl<'a> GlobalState<'a> {
          depth_or_array_layers: 1,
       });
 
+      let slice_texture_package = EguiTexturePackage::new(&setup, &mut egui_renderer, Extent3d {
+         width: 250,
+         height: 250,
+         depth_or_array_layers: 1,
+      });
+
+      let size = egui_texture_package.size;
+      let post_process_pipeline = PostProcessPipeline::new(&setup, egui_texture_package.texture.format(), (size.width, size.height));
+
       let ui_state = UiState::new();
 
       // let time_package = TimePackage::new();
@@ -43,8 +62,12 @@ impl<'a> GlobalState<'a> {
       Self {
          setup,
          egui_renderer,
-         test_render_pipeline,
+         document: Document::new(scene),
+         scene_render_pipeline,
+         post_process_pipeline,
+         modifiers: Modifiers::default(),
          egui_texture_package,
+         slice_texture_package,
          ui_state,
          // time_package,
       }
@@ -62,10 +85,76 @@ impl<'a> GlobalState<'a> {
       }
    }
 
-   pub fn update_input(&mut self, _event: &WindowEvent) -> bool { false }
+   /// returns true if the event was used up and shouldn't reach egui or quit the app
+   pub fn update_input(&mut self, event: &WindowEvent) -> bool {
+      match event {
+         // escape cancels renames and drags, egui still needs to see it but it mustn't quit
+         WindowEvent::KeyboardInput {
+            event: KeyEvent { state: ElementState::Pressed, physical_key: PhysicalKey::Code(KeyCode::Escape), .. },
+            ..
+         } if self.escape_in_use() => {
+            self.egui_renderer.handle_input(self.setup.window, event);
+            true
+         }
+
+         WindowEvent::ModifiersChanged(modifiers) => {
+            self.modifiers = *modifiers;
+            false
+         }
+
+         // text fields keep their own ctrl+z
+         WindowEvent::KeyboardInput {
+            event: KeyEvent { state: ElementState::Pressed, physical_key: PhysicalKey::Code(key), .. },
+            ..
+         } if self.command_key() && !self.egui_renderer.context.wants_keyboard_input() => {
+            let shift = self.modifiers.state().shift_key();
+            match key {
+               KeyCode::KeyZ if shift => self.document.redo(),
+               KeyCode::KeyZ => self.document.undo(),
+               KeyCode::KeyY => self.document.redo(),
+               _ => return false,
+            };
+            true
+         }
+
+         _ => false,
+      }
+   }
+
+   /// a text field has focus, or something's being dragged
+   fn escape_in_use(&self) -> bool {
+      let context = &self.egui_renderer.context;
+      context.wants_keyboard_input() || context.dragged_id().is_some() || self.ui_state.escape_in_use()
+   }
+
+   /// ctrl, or cmd on mac
+   fn command_key(&self) -> bool {
+      let state = self.modifiers.state();
+      state.control_key() || state.super_key()
+   }
 
    pub fn update(&mut self) {
       self.egui_texture_package.update(&self.setup, &mut self.egui_renderer);
+
+      self.scene_render_pipeline.mode = self.ui_state.render_mode;
+      self.scene_render_pipeline.debug_view = self.ui_state.debug_view();
+      self.scene_render_pipeline.solo = self.ui_state.solo();
+      let size = self.egui_texture_package.texture.size();
+      let scene = &self.document.scene;
+      if let Some(path_tracer) = self.scene_render_pipeline.path_tracer() {
+         path_tracer.settings = self.ui_state.path_trace.settings;
+         if std::mem::take(&mut self.ui_state.path_trace.restart) {
+            path_tracer.restart();
+         }
+      }
+      self.scene_render_pipeline.update(&self.setup, scene, (size.width, size.height));
+      if self.ui_state.slice.open {
+         self.slice_texture_package.update(&self.setup, &mut self.egui_renderer);
+         let size = self.slice_texture_package.texture.size();
+         self.scene_render_pipeline.update_slice(&self.setup, scene, self.ui_state.slice.uniform((size.width, size.height)));
+      }
+      self.post_process_pipeline.settings = self.ui_state.post_process.clone();
+      self.post_process_pipeline.update(&self.setup, (size.width, size.height));
       // self.time_package.update();
    }
 
@@ -77,11 +166,18 @@ impl<'a> GlobalState<'a> {
       });
 
       {
-         self.test_render_pipeline.render_pass(&mut encoder, &self.egui_texture_package.view);
+         self.scene_render_pipeline.render_pass(&mut encoder, self.post_process_pipeline.input());
+         self.post_process_pipeline.render_pass(&mut encoder, &self.egui_texture_package.view);
+         if self.ui_state.slice.open {
+            self.scene_render_pipeline.render_slice_pass(&mut encoder, &self.slice_texture_package.view);
+         }
+         if let Some(path_tracer) = self.scene_render_pipeline.path_tracer() {
+            self.ui_state.path_trace.samples = path_tracer.samples;
+         }
       }
 
 
-      self.ui_state.render_and_update(&self.setup, &mut self.egui_renderer, &mut self.egui_texture_package, &view, &mut encoder);
+      self.ui_state.render_and_update(&self.setup, &mut self.egui_renderer, &mut self.egui_texture_package, &mut self.slice_texture_package, &view, &mut encoder, &mut self.document);
 
       self.setup.queue.submit(iter::once(encoder.finish()));
       output.present();
@@ -72,84 +72,83 @@ pub fn run() {
          Event::WindowEvent {
             ref event,
             window_id,
-         } if window_id == state.setup.window.id() => {
-            if !state.update_input(event) {
-               // UPDATED!
-               match event {
-                  WindowEvent::CloseRequested
-                  | WindowEvent::KeyboardInput {
-                     event:
-                     KeyEvent {
-                        state: ElementState::Pressed,
-                        physical_key: PhysicalKey::Code(KeyCode::Escape),
-                        ..
-                     },
+         } if window_id == state.setup.window.id()
+            && !state.update_input(event) => {
+            // UPDATED!
+            match event {
+               WindowEvent::CloseRequested
+               | WindowEvent::KeyboardInput {
+                  event:
+                  KeyEvent {
+                     state: ElementState::Pressed,
+                     physical_key: PhysicalKey::Code(KeyCode::Escape),
                      ..
-                  } => control_flow.exit(),
+                  },
+                  ..
+               } => control_flow.exit(),
 
-                  WindowEvent::Resized(physical_size) => {
+               WindowEvent::Resized(physical_size) => {
 
-                     #[cfg(not(target_arch="wasm32"))] {
-                        log::info!("physical_size: {physical_size:?}");
-                        surface_configured = true;
-                        state.resize(*physical_size);
-                     }
+                  #[cfg(not(target_arch="wasm32"))] {
+                     log::info!("physical_size: {physical_size:?}");
+                     surface_configured = true;
+                     state.resize(*physical_size);
+                  }
 
 
-                     #[cfg(target_arch = "wasm32")]
-                     {
-                        let window = web_sys::window().unwrap();
-                        // Get the device pixel ratio
-                        let dpr = window.device_pixel_ratio();
+                  #[cfg(target_arch = "wasm32")]
+                  {
+                     let window = web_sys::window().unwrap();
+                     // Get the device pixel ratio
+                     let dpr = window.device_pixel_ratio();
 
-                        let nps = &mut physical_size.clone();
-                        nps.width = (nps.width as f64 / dpr) as u32;
-                        nps.height = (nps.height as f64 / dpr) as u32;
+                     let nps = &mut physical_size.clone();
+                     nps.width = (nps.width as f64 / dpr) as u32;
+                     nps.height = (nps.height as f64 / dpr) as u32;
 
-                        log::info!("physical_size: {nps:?}");
-                        surface_configured = true;
-                        state.resize(*nps);
+                     log::info!("physical_size: {nps:?}");
+                     surface_configured = true;
+                     state.resize(*nps);
 
-                        // log::info!("physical_size: {physical_size:?}");
-                        // surface_configured = true;
-                        // state.resize(*physical_size);
+                     // log::info!("physical_size: {physical_size:?}");
+                     // surface_configured = true;
+                     // state.resize(*physical_size);
 
 
-                        resize_to_canvas();
-                     }
+                     resize_to_canvas();
                   }
+               }
+
+               WindowEvent::RedrawRequested => {
+                  // This tells winit that we want another frame after this one
+                  state.setup.window.request_redraw();
 
-                  WindowEvent::RedrawRequested => {
-                     // This tells winit that we want another frame after this one
-                     state.setup.window.request_redraw();
+                  if !surface_configured {
+                     return;
+                  }
 
-                     if !surface_configured {
-                        return;
+                  state.update();
+                  match state.render() {
+                     Ok(_) => {}
+                     // Reconfigure the surface if it's lost or outdated
+                     Err(
+                        wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
+                     ) => state.resize(state.setup.size),
+                     // The system is out of memory, we should probably quit
+                     Err(wgpu::SurfaceError::OutOfMemory) => {
+                        log::error!("OutOfMemory");
+                        control_flow.exit();
                      }
 
-                     state.update();
-                     match state.render() {
-                        Ok(_) => {}
-                        // Reconfigure the surface if it's lost or outdated
-                        Err(
-                           wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
-                        ) => state.resize(state.setup.size),
-                        // The system is out of memory, we should probably quit
-                        Err(wgpu::SurfaceError::OutOfMemory) => {
-                           log::error!("OutOfMemory");
-                           control_flow.exit();
-                        }
-
-                        // This happens when the frame takes too long to present
-                        Err(wgpu::SurfaceError::Timeout) => {
-                           log::warn!("Surface timeout")
-                        }
+                     // This happens when the frame takes too long to present
+                     Err(wgpu::SurfaceError::Timeout) => {
+                        log::warn!("Surface timeout")
                      }
                   }
-                  _ => {}
                }
-               state.egui_renderer.handle_input(&mut state.setup.window, &event);
+               _ => {}
             }
+            state.egui_renderer.handle_input(state.setup.window, event);
          }
          _ => {}
       }
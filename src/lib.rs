@@ -8,16 +8,61 @@ pub mod inbuilt {
 }
 pub mod packages {
    pub mod test_render_pipeline;
+   pub mod compute_render_pipeline;
+   pub mod scene_render_pipeline;
+   pub mod path_trace_pipeline;
+   pub mod post_process_pipeline;
+   pub mod slice_pipeline;
    pub mod test_gui;
    // pub mod time_package;
 }
 
+pub mod scene {
+   pub mod graph;
+   pub mod node;
+   pub mod primitives;
+   pub mod operators;
+   pub mod domain;
+   pub mod file;
+   pub mod history;
+   pub mod document;
+   pub mod material;
+   pub mod camera;
+   pub mod light;
+   pub mod settings;
+   pub mod compiler;
+   pub mod sdf;
+   pub mod bytecode;
+   pub mod eval;
+   pub mod picking;
+}
+
+pub mod meshing {
+   pub mod mesh;
+   pub mod marching_cubes;
+   pub mod dual_contouring;
+   pub mod export;
+}
+
+pub mod render {
+   pub mod image;
+   pub mod cpu;
+}
+
 pub mod utility {
    pub mod functions;
    pub mod macros;
    pub mod structs;
+   pub mod widgets;
 }
 
 pub mod ui {
    pub mod ui_state;
+   pub mod outliner;
+   pub mod inspector;
+   pub mod viewport;
+   pub mod resolution;
+   pub mod slice_view;
+   pub mod gizmo;
+   pub mod mesh_export;
 }
\ No newline at end of file
@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use cgmath::{InnerSpace, Vector3};
+use crate::meshing::marching_cubes::{case_polygons, cell_case, edge_between, EDGES};
+use crate::meshing::mesh::{parallel_map, Grid, Mesh, MeshSettings, Progress};
+use crate::scene::eval;
+use crate::scene::graph::Scene;
+
+/// false position steps taken past the linear guess of where an edge crosses the surface
+const REFINE_STEPS: usize = 4;
+
+/// directions the qef barely constrains are left at the mass point, relative to the strongest one
+const EIGEN_THRESHOLD: f64 = 0.02;
+
+
+/// meshes the scene's surface inside the settings' bounds, none if cancelled
+pub fn mesh(scene: &Scene, settings: &MeshSettings, progress: &Progress) -> Option<Mesh> {
+   let grid = Grid::sample(scene, settings, progress)?;
+   let mut mesh = contour(scene, settings, &grid, progress)?;
+   mesh.shade(scene, progress);
+   (!progress.is_cancelled()).then_some(mesh)
+}
+
+/// dual contouring over already sampled values
+///
+/// every separate piece of surface in a cell gets one vertex, placed where the tangent planes at its edge
+/// crossings meet so sharp edges and corners survive, then every crossed edge joins the four cells around it
+/// with a quad. splitting pieces the same way marching cubes does keeps the mesh manifold
+pub fn contour(scene: &Scene, settings: &MeshSettings, grid: &Grid, progress: &Progress) -> Option<Mesh> {
+   let polygons = case_polygons();
+   let [cx, cy, cz] = grid.cells;
+
+   let mut cells = vec![];
+   for z in 0..cz {
+      for y in 0..cy {
+         for x in 0..cx {
+            let case = cell_case(grid, [x, y, z]);
+            if case != 0 && case != 255 {
+               cells.push(([x, y, z], case));
+            }
+         }
+      }
+   }
+
+   progress.start("Placing vertices", cells.len());
+   let placed = parallel_map(cells.len(), progress, |i| {
+      let (cell, case) = cells[i];
+      polygons[case as usize].iter()
+          .map(|polygon| place_vertex(scene, settings, grid, cell, polygon))
+          .collect::<Vec<_>>()
+   });
+   if progress.is_cancelled() {
+      return None;
+   }
+
+   let mut mesh = Mesh::default();
+   // sample index of the cell's lower corner -> (its first vertex, case)
+   let mut first_vertex: HashMap<usize, (u32, u8)> = HashMap::with_capacity(cells.len());
+   for ((cell, case), vertices) in cells.iter().zip(placed) {
+      first_vertex.insert(grid.index(*cell), (mesh.positions.len() as u32, *case));
+      mesh.positions.extend(vertices.iter().map(|p| [p.x, p.y, p.z]));
+   }
+   let vertex = |cell: [usize; 3], edge: u8| {
+      let (first, case) = first_vertex[&grid.index(cell)];
+      let piece = polygons[case as usize].iter()
+          .position(|polygon| polygon.contains(&edge))
+          .expect("a crossed edge belongs to a piece of every cell around it");
+      first + piece as u32
+   };
+
+   progress.start("Connecting", cz + 1);
+   let mut quads = vec![];
+   // (inside corner, the face corner across from it) -> vertex splitting an ambiguous face's segment
+   let mut segment_vertices: HashMap<(usize, usize), u32> = HashMap::new();
+   for z in 0..=cz {
+      if progress.is_cancelled() {
+         return None;
+      }
+      for y in 0..=cy {
+         for x in 0..=cx {
+            let a = [x, y, z];
+            for axis in 0..3 {
+               let (u, w) = ((axis + 1) % 3, (axis + 2) % 3);
+               // edges on the grid's sides don't have four cells around them
+               if a[axis] == grid.cells[axis] || !(1..grid.cells[u]).contains(&a[u]) || !(1..grid.cells[w]).contains(&a[w]) {
+                  continue;
+               }
+               let mut b = a;
+               b[axis] += 1;
+               let inside = grid.value(a) < 0.0;
+               if inside == (grid.value(b) < 0.0) {
+                  continue;
+               }
+
+               // counter clockwise seen from +axis, which is outside when the lower end is inside
+               let around = [(1, 1), (0, 1), (0, 0), (1, 0)];
+               let mut polygon = vec![];
+               for (k, (du, dw)) in around.into_iter().enumerate() {
+                  let mut cell = a;
+                  cell[u] -= du;
+                  cell[w] -= dw;
+                  let low = du << u | dw << w;
+                  polygon.push(vertex(cell, edge_between(low, low | 1 << axis)));
+
+                  // on an ambiguous face both segments can join the same two pieces, which would give their
+                  // vertices an edge shared by four quads, so each segment gets a vertex of its own
+                  let (next_du, _) = around[(k + 1) % 4];
+                  let (side, across) = if du != next_du { (w, dw) } else { (u, du) };
+                  let [mut a_side, mut b_side] = [a, b];
+                  a_side[side] = if across == 1 { a[side] - 1 } else { a[side] + 1 };
+                  b_side[side] = a_side[side];
+                  let in_a_side = grid.value(a_side) < 0.0;
+                  if in_a_side == inside || (grid.value(b_side) < 0.0) != inside {
+                     continue;
+                  }
+                  let (corner, corner_side, across_corner) = if inside { (a, a_side, b_side) } else { (b, b_side, a_side) };
+                  let key = (grid.index(corner), grid.index(across_corner));
+                  let segment_vertex = *segment_vertices.entry(key).or_insert_with(|| {
+                     let distance = |p: Vector3<f32>| settings.distance(scene, p);
+                     let along = crossing(distance, grid.position(a), grid.position(b), grid.value(a), grid.value(b));
+                     let beside = crossing(
+                        distance,
+                        grid.position(corner), grid.position(corner_side),
+                        grid.value(corner), grid.value(corner_side),
+                     );
+                     let p = (along + beside) * 0.5;
+                     mesh.positions.push([p.x, p.y, p.z]);
+                     mesh.positions.len() as u32 - 1
+                  });
+                  polygon.push(segment_vertex);
+               }
+               if !inside {
+                  polygon.reverse();
+               }
+
+               if let [a, b, c, d] = polygon[..] {
+                  quads.push([a, b, c, d]);
+               } else {
+                  // fanned from its middle, any diagonal could also be an edge of a neighbouring polygon
+                  let middle = polygon.iter()
+                      .map(|i| Vector3::from(mesh.positions[*i as usize]))
+                      .sum::<Vector3<f32>>() / polygon.len() as f32;
+                  mesh.positions.push([middle.x, middle.y, middle.z]);
+                  let middle = mesh.positions.len() as u32 - 1;
+                  for (i, corner) in polygon.iter().enumerate() {
+                     mesh.triangles.push([middle, *corner, polygon[(i + 1) % polygon.len()]]);
+                  }
+               }
+            }
+         }
+      }
+      progress.advance(1);
+   }
+
+   progress.start("Splitting quads", quads.len());
+   let splits = parallel_map(quads.len(), progress, |i| split_along_surface(scene, settings, &mesh, quads[i]));
+   if progress.is_cancelled() {
+      return None;
+   }
+   mesh.triangles.extend(quads.into_iter().zip(splits).flat_map(|([a, b, c, d], first_diagonal)| {
+      if first_diagonal { [[a, b, c], [a, c, d]] } else { [[b, c, d], [b, d, a]] }
+   }));
+
+   Some(mesh)
+}
+
+
+/// the point best fitting the tangent planes where a piece of surface crosses the cell's edges
+///
+/// creases passing near a cell's side often fit just outside it, so it's kept within a cell of the cell
+/// rather than inside it, which is still close enough to not fold the mesh over its neighbours
+fn place_vertex(scene: &Scene, settings: &MeshSettings, grid: &Grid, cell: [usize; 3], polygon: &[u8]) -> Vector3<f32> {
+   let distance = |p: Vector3<f32>| settings.distance(scene, p);
+   let mut qef = Qef::default();
+   for edge in polygon {
+      let (low, axis) = EDGES[*edge as usize];
+      let a = [cell[0] + (low & 1), cell[1] + (low >> 1 & 1), cell[2] + (low >> 2 & 1)];
+      let mut b = a;
+      b[axis] += 1;
+
+      let p = crossing(distance, grid.position(a), grid.position(b), grid.value(a), grid.value(b));
+      let normal = eval::gradient_of(distance, p);
+      if normal.magnitude2() > 1e-12 {
+         qef.add(p, normal.normalize());
+      }
+   }
+
+   let min = grid.position(cell.map(|i| i.saturating_sub(1)));
+   let max = grid.position(cell.map(|i| i + 2));
+   let p = qef.solve();
+   Vector3::new(p.x.clamp(min.x, max.x), p.y.clamp(min.y, max.y), p.z.clamp(min.z, max.z))
+}
+
+/// where the field crosses zero between two samples of opposite sign
+fn crossing(distance: impl Fn(Vector3<f32>) -> f32, a: Vector3<f32>, b: Vector3<f32>, va: f32, vb: f32) -> Vector3<f32> {
+   let (mut low, mut high, mut v_low, mut v_high) = (0.0, 1.0, va, vb);
+   let mut t = 0.5;
+   for _ in 0..REFINE_STEPS {
+      if v_low == v_high {
+         break;
+      }
+      t = low + (high - low) * (v_low / (v_low - v_high)).clamp(0.0, 1.0);
+      let v = distance(a + (b - a) * t);
+      if v == 0.0 {
+         break;
+      }
+      // halving the kept end's value stops false position creeping up on one side
+      if (v < 0.0) == (v_low < 0.0) {
+         (low, v_low) = (t, v);
+         v_high *= 0.5;
+      } else {
+         (high, v_high) = (t, v);
+         v_low *= 0.5;
+      }
+   }
+   a + (b - a) * t
+}
+
+/// picks the diagonal whose two triangles face the same way as the surface under them,
+/// which folds quads along sharp edges instead of across them
+fn split_along_surface(scene: &Scene, settings: &MeshSettings, mesh: &Mesh, [a, b, c, d]: [u32; 4]) -> bool {
+   let fit = |triangle: [u32; 3]| {
+      let [p, q, r] = triangle.map(|i| Vector3::from(mesh.positions[i as usize]));
+      let normal = eval::gradient_of(|p| settings.distance(scene, p), (p + q + r) / 3.0);
+      let area_normal = (q - p).cross(r - p);
+      if normal.magnitude2() > 1e-12 { area_normal.dot(normal.normalize()) } else { 0.0 }
+   };
+   fit([a, b, c]) + fit([a, c, d]) >= fit([b, c, d]) + fit([b, d, a])
+}
+
+
+/// sum of squared distances to a set of planes, kept as its normal equations
+#[derive(Default)]
+struct Qef {
+   ata: [[f64; 3]; 3],
+   atb: [f64; 3],
+   mass: [f64; 3],
+   count: usize,
+}
+impl Qef {
+   fn add(&mut self, point: Vector3<f32>, normal: Vector3<f32>) {
+      let n = [normal.x as f64, normal.y as f64, normal.z as f64];
+      let p = [point.x as f64, point.y as f64, point.z as f64];
+      let d = n[0] * p[0] + n[1] * p[1] + n[2] * p[2];
+      for i in 0..3 {
+         for j in 0..3 {
+            self.ata[i][j] += n[i] * n[j];
+         }
+         self.atb[i] += n[i] * d;
+         self.mass[i] += p[i];
+      }
+      self.count += 1;
+   }
+
+   /// the minimiser nearest the mass point, solved with a truncated pseudo inverse so flat and
+   /// creased pieces don't send the point off along the directions they leave free
+   fn solve(&self) -> Vector3<f32> {
+      if self.count == 0 {
+         return Vector3::new(0.0, 0.0, 0.0);
+      }
+      let mass = self.mass.map(|m| m / self.count as f64);
+      let mut rhs = self.atb;
+      for (i, value) in rhs.iter_mut().enumerate() {
+         *value -= (0..3).map(|j| self.ata[i][j] * mass[j]).sum::<f64>();
+      }
+
+      let (values, vectors) = symmetric_eigen(self.ata);
+      let largest = values.iter().fold(0.0f64, |a, b| a.max(b.abs()));
+      let mut point = mass;
+      for k in 0..3 {
+         if values[k].abs() <= largest * EIGEN_THRESHOLD || values[k] == 0.0 {
+            continue;
+         }
+         let along = (0..3).map(|i| vectors[i][k] * rhs[i]).sum::<f64>() / values[k];
+         for (i, value) in point.iter_mut().enumerate() {
+            *value += vectors[i][k] * along;
+         }
+      }
+      Vector3::new(point[0] as f32, point[1] as f32, point[2] as f32)
+   }
+}
+
+/// eigenvalues and eigenvectors (as columns) of a symmetric 3x3 matrix, by jacobi rotations
+fn symmetric_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+   let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+   for _ in 0..16 {
+      for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+         if a[p][q].abs() < 1e-12 {
+            continue;
+         }
+         let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+         let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+         let c = 1.0 / (t * t + 1.0).sqrt();
+         let s = t * c;
+         for row in a.iter_mut().chain(v.iter_mut()) {
+            let (kp, kq) = (row[p], row[q]);
+            row[p] = c * kp - s * kq;
+            row[q] = s * kp + c * kq;
+         }
+         let (rp, rq) = (a[p], a[q]);
+         for k in 0..3 {
+            a[p][k] = c * rp[k] - s * rq[k];
+            a[q][k] = s * rp[k] + c * rq[k];
+         }
+      }
+   }
+   ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::node::Node;
+   use crate::scene::operators::Operation;
+   use crate::scene::primitives::{BoxParams, Primitive, SphereParams};
+
+   /// off the grid's samples so no face lies exactly on them
+   const HALF: f32 = 0.63;
+   const RADIUS: f32 = 0.8;
+   const TOLERANCE: f32 = 0.01;
+
+   fn cube() -> Node {
+      Node::primitive("Box", Primitive::Box(BoxParams { half_x: HALF, half_y: HALF, half_z: HALF }))
+   }
+
+   fn contoured(scene: &Scene) -> (Mesh, MeshSettings) {
+      let settings = MeshSettings { min: [-1.0; 3], max: [1.0; 3], resolution: 20, ..Default::default() };
+      let grid = Grid::sample(scene, &settings, &Progress::default()).unwrap();
+      (contour(scene, &settings, &grid, &Progress::default()).unwrap(), settings)
+   }
+
+   /// the mesh has a vertex within `TOLERANCE` of `feature` near each of `samples`
+   fn assert_follows(mesh: &Mesh, cell: f32, samples: &[Vector3<f32>], feature: impl Fn(Vector3<f32>) -> f32) {
+      for sample in samples {
+         let nearest = mesh.positions.iter()
+             .map(|p| Vector3::from(*p))
+             .filter(|p| (p - sample).magnitude() < cell)
+             .map(&feature)
+             .fold(f32::INFINITY, f32::min);
+         assert!(nearest < TOLERANCE, "nothing within {TOLERANCE} near {sample:?}, closest {nearest}");
+      }
+   }
+
+   #[test]
+   fn box_keeps_its_corners_and_edges() {
+      let mut scene = Scene::new();
+      let root = scene.root();
+      scene.insert(root, cube()).unwrap();
+      let (mesh, settings) = contoured(&scene);
+      assert!(mesh.is_closed());
+
+      for p in &mesh.positions {
+         assert!(eval::eval(&scene, Vector3::from(*p)).abs() < TOLERANCE);
+      }
+
+      for i in 0..8 {
+         let sign = |bit: usize| if i >> bit & 1 == 1 { HALF } else { -HALF };
+         let corner = Vector3::new(sign(0), sign(1), sign(2));
+         assert_follows(&mesh, settings.cell_size(), &[corner], |p| (p - corner).magnitude());
+      }
+
+      // the four edges along each axis, checked part way along
+      for axis in 0..3 {
+         for (a, b) in [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)] {
+            let mut edge = Vector3::new(0.0, 0.0, 0.0);
+            edge[(axis + 1) % 3] = a * HALF;
+            edge[(axis + 2) % 3] = b * HALF;
+            let samples: Vec<_> = [-0.4, 0.0, 0.3].iter().map(|t| {
+               let mut sample = edge;
+               sample[axis] = *t;
+               sample
+            }).collect();
+            assert_follows(&mesh, settings.cell_size(), &samples, |p| {
+               let mut off = p - edge;
+               off[axis] = 0.0;
+               off.magnitude()
+            });
+         }
+      }
+   }
+
+   #[test]
+   fn box_and_sphere_keep_the_edges_where_they_meet() {
+      let mut scene = Scene::new();
+      let root = scene.root();
+      let both = scene.insert(root, Node::operation("Intersection", Operation::intersection())).unwrap();
+      scene.insert(both, cube()).unwrap();
+      scene.insert(both, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: RADIUS }))).unwrap();
+      let (mesh, settings) = contoured(&scene);
+      assert!(mesh.is_closed());
+
+      for p in &mesh.positions {
+         assert!(eval::eval(&scene, Vector3::from(*p)).abs() < TOLERANCE);
+      }
+
+      // the sphere cuts each face of the box in a circle
+      let circle = (RADIUS * RADIUS - HALF * HALF).sqrt();
+      for axis in 0..3 {
+         for side in [-HALF, HALF] {
+            let samples: Vec<_> = (0..8).map(|i| {
+               let angle = i as f32 / 8.0 * std::f32::consts::TAU;
+               let mut sample = Vector3::new(0.0, 0.0, 0.0);
+               sample[axis] = side;
+               sample[(axis + 1) % 3] = circle * angle.cos();
+               sample[(axis + 2) % 3] = circle * angle.sin();
+               sample
+            }).collect();
+            assert_follows(&mesh, settings.cell_size(), &samples, |p| {
+               let across = (p[(axis + 1) % 3].powi(2) + p[(axis + 2) % 3].powi(2)).sqrt();
+               ((p[axis] - side).powi(2) + (across - circle).powi(2)).sqrt()
+            });
+         }
+      }
+   }
+}
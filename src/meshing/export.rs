@@ -0,0 +1,265 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::meshing::mesh::Mesh;
+use crate::utility::functions::linear_to_srgb8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshFormat {
+   StlBinary,
+   StlAscii,
+   /// with vertex normals
+   Obj,
+   /// binary, with vertex normals and colours
+   Ply,
+}
+impl MeshFormat {
+   pub const ALL: [MeshFormat; 4] = [MeshFormat::StlBinary, MeshFormat::StlAscii, MeshFormat::Obj, MeshFormat::Ply];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         MeshFormat::StlBinary => "STL (binary)",
+         MeshFormat::StlAscii => "STL (ascii)",
+         MeshFormat::Obj => "OBJ",
+         MeshFormat::Ply => "PLY",
+      }
+   }
+
+   pub fn extension(&self) -> &'static str {
+      match self {
+         MeshFormat::StlBinary | MeshFormat::StlAscii => "stl",
+         MeshFormat::Obj => "obj",
+         MeshFormat::Ply => "ply",
+      }
+   }
+
+   /// picks from the file extension, stl files are written binary
+   pub fn from_path(path: &Path) -> Option<MeshFormat> {
+      let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+      match extension.as_str() {
+         "stl" => Some(MeshFormat::StlBinary),
+         "obj" => Some(MeshFormat::Obj),
+         "ply" => Some(MeshFormat::Ply),
+         _ => None,
+      }
+   }
+
+   pub fn write(&self, writer: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+      match self {
+         MeshFormat::StlBinary => write_stl_binary(writer, mesh),
+         MeshFormat::StlAscii => write_stl_ascii(writer, mesh),
+         MeshFormat::Obj => write_obj(writer, mesh),
+         MeshFormat::Ply => write_ply(writer, mesh),
+      }
+   }
+}
+
+pub fn save(path: &Path, mesh: &Mesh, format: MeshFormat) -> std::io::Result<()> {
+   let mut writer = BufWriter::new(File::create(path)?);
+   format.write(&mut writer, mesh)?;
+   writer.flush()
+}
+
+
+/// 80 byte header, triangle count, then a face normal, three corners and an unused u16 per triangle
+pub fn write_stl_binary(writer: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+   let mut header = [0u8; 80];
+   let name = b"sdf_application";
+   header[..name.len()].copy_from_slice(name);
+   writer.write_all(&header)?;
+   writer.write_all(&(mesh.triangle_count() as u32).to_le_bytes())?;
+
+   for triangle in &mesh.triangles {
+      let normal = mesh.face_normal(*triangle);
+      let corners = triangle.map(|i| mesh.positions[i as usize]);
+      for value in [normal.x, normal.y, normal.z].iter().chain(corners.iter().flatten()) {
+         writer.write_all(&value.to_le_bytes())?;
+      }
+      writer.write_all(&0u16.to_le_bytes())?;
+   }
+   Ok(())
+}
+
+pub fn write_stl_ascii(writer: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+   writeln!(writer, "solid sdf_application")?;
+   for triangle in &mesh.triangles {
+      let normal = mesh.face_normal(*triangle);
+      writeln!(writer, "facet normal {:e} {:e} {:e}", normal.x, normal.y, normal.z)?;
+      writeln!(writer, "  outer loop")?;
+      for i in triangle {
+         let [x, y, z] = mesh.positions[*i as usize];
+         writeln!(writer, "    vertex {x:e} {y:e} {z:e}")?;
+      }
+      writeln!(writer, "  endloop")?;
+      writeln!(writer, "endfacet")?;
+   }
+   writeln!(writer, "endsolid sdf_application")
+}
+
+/// normals share the vertex indices, obj counts from 1
+pub fn write_obj(writer: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+   writeln!(writer, "# sdf_application")?;
+   for [x, y, z] in &mesh.positions {
+      writeln!(writer, "v {x} {y} {z}")?;
+   }
+   for [x, y, z] in &mesh.normals {
+      writeln!(writer, "vn {x} {y} {z}")?;
+   }
+   let has_normals = mesh.normals.len() == mesh.positions.len();
+   for triangle in &mesh.triangles {
+      let [a, b, c] = triangle.map(|i| i + 1);
+      if has_normals {
+         writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+      } else {
+         writeln!(writer, "f {a} {b} {c}")?;
+      }
+   }
+   Ok(())
+}
+
+/// binary little endian, colours are converted from linear to 8 bit srgb
+pub fn write_ply(writer: &mut impl Write, mesh: &Mesh) -> std::io::Result<()> {
+   let has_normals = mesh.normals.len() == mesh.positions.len();
+   let has_colors = mesh.colors.len() == mesh.positions.len();
+
+   writeln!(writer, "ply")?;
+   writeln!(writer, "format binary_little_endian 1.0")?;
+   writeln!(writer, "comment sdf_application")?;
+   writeln!(writer, "element vertex {}", mesh.vertex_count())?;
+   for axis in ["x", "y", "z"] {
+      writeln!(writer, "property float {axis}")?;
+   }
+   if has_normals {
+      for axis in ["nx", "ny", "nz"] {
+         writeln!(writer, "property float {axis}")?;
+      }
+   }
+   if has_colors {
+      for channel in ["red", "green", "blue"] {
+         writeln!(writer, "property uchar {channel}")?;
+      }
+   }
+   writeln!(writer, "element face {}", mesh.triangle_count())?;
+   writeln!(writer, "property list uchar uint vertex_indices")?;
+   writeln!(writer, "end_header")?;
+
+   for i in 0..mesh.vertex_count() {
+      for value in mesh.positions[i] {
+         writer.write_all(&value.to_le_bytes())?;
+      }
+      if has_normals {
+         for value in mesh.normals[i] {
+            writer.write_all(&value.to_le_bytes())?;
+         }
+      }
+      if has_colors {
+         writer.write_all(&mesh.colors[i].map(linear_to_srgb8))?;
+      }
+   }
+   for triangle in &mesh.triangles {
+      writer.write_all(&[3])?;
+      for i in triangle {
+         writer.write_all(&i.to_le_bytes())?;
+      }
+   }
+   Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   /// a tetrahedron with normals pointing out from the middle and one colour per corner
+   fn tetrahedron() -> Mesh {
+      let positions = vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+      let normals = positions.iter().map(|[x, y, z]| [x - 0.25, y - 0.25, z - 0.25]).collect();
+      Mesh {
+         positions,
+         normals,
+         colors: vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.5, 0.5, 0.5]],
+         triangles: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+      }
+   }
+
+   fn written(format: MeshFormat, mesh: &Mesh) -> Vec<u8> {
+      let mut bytes = Vec::new();
+      format.write(&mut bytes, mesh).unwrap();
+      bytes
+   }
+
+   #[test]
+   fn binary_stl_has_a_record_per_triangle() {
+      let mesh = tetrahedron();
+      let bytes = written(MeshFormat::StlBinary, &mesh);
+      assert_eq!(bytes.len(), 84 + 50 * mesh.triangle_count());
+      assert_eq!(u32::from_le_bytes(bytes[80..84].try_into().unwrap()), 4);
+
+      // first corner of the last triangle sits after its normal
+      let record = &bytes[84 + 50 * 3..];
+      let corner: Vec<f32> = record[12..24].chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+      assert_eq!(corner, [1.0, 0.0, 0.0]);
+      assert_eq!(&record[48..50], &[0, 0]);
+   }
+
+   #[test]
+   fn ascii_stl_has_a_facet_per_triangle() {
+      let text = String::from_utf8(written(MeshFormat::StlAscii, &tetrahedron())).unwrap();
+      assert!(text.starts_with("solid sdf_application\n"));
+      assert!(text.ends_with("endsolid sdf_application\n"));
+      assert_eq!(text.lines().filter(|l| l.starts_with("facet normal ")).count(), 4);
+      assert_eq!(text.lines().filter(|l| l == &"endfacet").count(), 4);
+      assert_eq!(text.lines().filter(|l| l.trim_start().starts_with("vertex ")).count(), 12);
+   }
+
+   #[test]
+   fn obj_faces_count_from_one_and_share_normal_indices() {
+      let text = String::from_utf8(written(MeshFormat::Obj, &tetrahedron())).unwrap();
+      assert_eq!(text.lines().filter(|l| l.starts_with("v ")).count(), 4);
+      assert_eq!(text.lines().filter(|l| l.starts_with("vn ")).count(), 4);
+      let faces: Vec<&str> = text.lines().filter(|l| l.starts_with("f ")).collect();
+      assert_eq!(faces, ["f 1//1 3//3 2//2", "f 1//1 2//2 4//4", "f 1//1 4//4 3//3", "f 2//2 3//3 4//4"]);
+   }
+
+   #[test]
+   fn obj_without_normals_writes_plain_faces() {
+      let mesh = Mesh { normals: Vec::new(), ..tetrahedron() };
+      let text = String::from_utf8(written(MeshFormat::Obj, &mesh)).unwrap();
+      assert!(!text.contains("vn "));
+      assert!(text.contains("\nf 2 3 4\n"));
+   }
+
+   #[test]
+   fn ply_header_matches_the_body() {
+      let mesh = tetrahedron();
+      let bytes = written(MeshFormat::Ply, &mesh);
+      let end = b"end_header\n";
+      let header_len = bytes.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+      let header = std::str::from_utf8(&bytes[..header_len]).unwrap();
+
+      assert!(header.contains("format binary_little_endian 1.0\n"));
+      assert!(header.contains("element vertex 4\n"));
+      assert!(header.contains("element face 4\n"));
+      for property in ["float nx", "float ny", "float nz", "uchar red", "uchar green", "uchar blue"] {
+         assert!(header.contains(&format!("property {property}\n")), "missing {property}");
+      }
+
+      // position and normal floats plus three colour bytes, then a count byte and three indices
+      let vertex = 6 * 4 + 3;
+      let face = 1 + 3 * 4;
+      assert_eq!(bytes.len(), header_len + 4 * vertex + 4 * face);
+
+      assert_eq!(&bytes[header_len + vertex - 3..header_len + vertex], &[255, 0, 0]);
+      let last_face = &bytes[bytes.len() - face..];
+      assert_eq!(last_face[0], 3);
+      assert_eq!(u32::from_le_bytes(last_face[9..13].try_into().unwrap()), 3);
+   }
+
+   #[test]
+   fn ply_without_colours_leaves_the_properties_out() {
+      let mesh = Mesh { colors: Vec::new(), ..tetrahedron() };
+      let bytes = written(MeshFormat::Ply, &mesh);
+      let text = String::from_utf8_lossy(&bytes);
+      assert!(!text.contains("property uchar red"));
+      assert!(text.contains("element vertex 4\n"));
+   }
+}
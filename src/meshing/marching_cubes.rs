@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::meshing::mesh::{Grid, Mesh, MeshSettings, Progress};
+use crate::scene::graph::Scene;
+
+/// corner `i` of a cell sits at (i & 1, i >> 1 & 1, i >> 2 & 1)
+const CORNERS: usize = 8;
+
+/// (lower corner, axis) of the twelve cell edges
+pub const EDGES: [(usize, usize); 12] = [
+   (0, 0), (2, 0), (4, 0), (6, 0),
+   (0, 1), (1, 1), (4, 1), (5, 1),
+   (0, 2), (1, 2), (2, 2), (3, 2),
+];
+
+
+/// triangles for each of the 256 inside/outside corner cases, as edge indices
+fn case_table() -> &'static [Vec<[u8; 3]>; 256] {
+   static TABLE: OnceLock<[Vec<[u8; 3]>; 256]> = OnceLock::new();
+   TABLE.get_or_init(|| std::array::from_fn(|case| triangulate_case(&case_polygons()[case])))
+}
+
+/// the separate pieces of surface in each of the 256 cases, as loops of edge indices
+///
+/// built once by walking each face of the cube, every face splits its inside corners off on their own,
+/// so neighbouring cells agree on ambiguous faces and the mesh has no holes
+pub fn case_polygons() -> &'static [Vec<Vec<u8>>; 256] {
+   static TABLE: OnceLock<[Vec<Vec<u8>>; 256]> = OnceLock::new();
+   TABLE.get_or_init(|| std::array::from_fn(|case| walk_case(case as u8)))
+}
+
+/// the edge joining two corners of a cell
+pub fn edge_between(a: usize, b: usize) -> u8 {
+   let (low, axis) = (a.min(b), (a ^ b).trailing_zeros() as usize);
+   EDGES.iter().position(|edge| *edge == (low, axis)).expect("corners aren't neighbours") as u8
+}
+
+fn walk_case(case: u8) -> Vec<Vec<u8>> {
+   let inside = |corner: usize| case & (1 << corner) != 0;
+
+   // each face gives segments from where its boundary enters the inside to where it next leaves,
+   // walked counter clockwise seen from outside so the segments chain into loops around the surface
+   let mut next_edge = HashMap::new();
+   for axis in 0..3 {
+      let (u, w) = ((axis + 1) % 3, (axis + 2) % 3);
+      for side in 0..2 {
+         let mut cycle: Vec<usize> = [(0, 0), (1, 0), (1, 1), (0, 1)].iter()
+             .map(|(a, b)| side << axis | a << u | b << w)
+             .collect();
+         if side == 0 {
+            cycle.reverse();
+         }
+
+         let crossings: Vec<(u8, bool)> = (0..4)
+             .map(|i| (cycle[i], cycle[(i + 1) % 4]))
+             .filter(|(a, b)| inside(*a) != inside(*b))
+             .map(|(a, b)| (edge_between(a, b), inside(b)))
+             .collect();
+
+         for (i, (edge, entering)) in crossings.iter().enumerate() {
+            if *entering {
+               let (leaving, _) = crossings[(i + 1) % crossings.len()];
+               next_edge.insert(*edge, leaving);
+            }
+         }
+      }
+   }
+
+   let mut polygons = vec![];
+   while let Some(&start) = next_edge.keys().min() {
+      let mut polygon = vec![start];
+      let mut edge = next_edge.remove(&start).expect("start was a key");
+      while edge != start {
+         polygon.push(edge);
+         edge = next_edge.remove(&edge).expect("face segments always close into loops");
+      }
+      polygons.push(polygon);
+   }
+   polygons
+}
+
+fn triangulate_case(polygons: &[Vec<u8>]) -> Vec<[u8; 3]> {
+   let mut triangles = vec![];
+   for polygon in polygons {
+      // a fan diagonal lying in a face could be made again by the neighbouring cell, so start the fan
+      // somewhere none of them do
+      let n = polygon.len();
+      let apex = (0..n)
+          .find(|apex| (2..n - 1).all(|i| !shares_face(polygon[*apex], polygon[(apex + i) % n])))
+          .unwrap_or(0);
+      for i in 1..n - 1 {
+         triangles.push([polygon[apex], polygon[(apex + i) % n], polygon[(apex + i + 1) % n]]);
+      }
+   }
+   triangles
+}
+
+/// faces as (axis, side), every edge lies on the two faces across the other axes
+fn shares_face(a: u8, b: u8) -> bool {
+   let faces = |edge: u8| {
+      let (low, axis) = EDGES[edge as usize];
+      (0..3).filter(move |face_axis| *face_axis != axis).map(move |face_axis| (face_axis, low >> face_axis & 1))
+   };
+   faces(a).any(|face| faces(b).any(|other| other == face))
+}
+
+
+/// which corners of a cell are inside, bit `i` for corner `i`
+pub fn cell_case(grid: &Grid, [x, y, z]: [usize; 3]) -> u8 {
+   let mut case = 0u8;
+   for i in 0..CORNERS {
+      if grid.value([x + (i & 1), y + (i >> 1 & 1), z + (i >> 2 & 1)]) < 0.0 {
+         case |= 1 << i;
+      }
+   }
+   case
+}
+
+
+/// meshes the scene's surface inside the settings' bounds, none if cancelled
+pub fn mesh(scene: &Scene, settings: &MeshSettings, progress: &Progress) -> Option<Mesh> {
+   let grid = Grid::sample(scene, settings, progress)?;
+   let mut mesh = polygonise(&grid, progress)?;
+   mesh.shade(scene, progress);
+   (!progress.is_cancelled()).then_some(mesh)
+}
+
+/// marching cubes over already sampled values, vertices on shared edges are welded
+pub fn polygonise(grid: &Grid, progress: &Progress) -> Option<Mesh> {
+   let table = case_table();
+   let [cx, cy, cz] = grid.cells;
+   let mut mesh = Mesh::default();
+   // (sample index of the edge's lower end, axis) -> vertex
+   let mut vertices: HashMap<(usize, usize), u32> = HashMap::new();
+
+   progress.start("Polygonising", cz);
+   for z in 0..cz {
+      if progress.is_cancelled() {
+         return None;
+      }
+      for y in 0..cy {
+         for x in 0..cx {
+            let corner = |i: usize| [x + (i & 1), y + (i >> 1 & 1), z + (i >> 2 & 1)];
+            for triangle in &table[cell_case(grid, [x, y, z]) as usize] {
+               let indices = triangle.map(|edge| {
+                  let (low, axis) = EDGES[edge as usize];
+                  let a = corner(low);
+                  let mut b = a;
+                  b[axis] += 1;
+
+                  *vertices.entry((grid.index(a), axis)).or_insert_with(|| {
+                     let (va, vb) = (grid.value(a), grid.value(b));
+                     let t = if va != vb { (va / (va - vb)).clamp(0.0, 1.0) } else { 0.5 };
+                     let p = grid.position(a) + (grid.position(b) - grid.position(a)) * t;
+                     mesh.positions.push([p.x, p.y, p.z]);
+                     mesh.positions.len() as u32 - 1
+                  })
+               });
+               mesh.triangles.push(indices);
+            }
+         }
+      }
+      progress.advance(1);
+   }
+
+   Some(mesh)
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use cgmath::{InnerSpace, Vector3};
+   use crate::scene::node::Node;
+   use crate::scene::primitives::{Primitive, SphereParams};
+
+   fn settings(min: f32, max: f32, resolution: u32) -> MeshSettings {
+      MeshSettings { min: [min; 3], max: [max; 3], resolution, ..Default::default() }
+   }
+
+   #[test]
+   fn sphere_mesh_is_closed_and_on_the_surface() {
+      let mut scene = Scene::new();
+      let root = scene.root();
+      scene.insert(root, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 1.0 }))).unwrap();
+
+      let settings = settings(-1.5, 1.5, 24);
+      let mesh = mesh(&scene, &settings, &Progress::default()).unwrap();
+      assert!(mesh.triangle_count() > 0);
+      assert!(mesh.is_closed());
+      for position in &mesh.positions {
+         assert!((Vector3::from(*position).magnitude() - 1.0).abs() < settings.cell_size() * 0.1);
+      }
+   }
+
+   #[test]
+   fn demo_mesh_is_closed() {
+      let settings = MeshSettings { resolution: 40, ..Default::default() };
+      let grid = Grid::sample(&Scene::demo(), &settings, &Progress::default()).unwrap();
+      let mesh = polygonise(&grid, &Progress::default()).unwrap();
+      assert!(mesh.is_closed());
+   }
+}
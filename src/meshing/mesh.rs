@@ -0,0 +1,267 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use cgmath::{InnerSpace, Vector3};
+use parking_lot::Mutex;
+use crate::meshing::{dual_contouring, marching_cubes};
+use crate::scene::eval;
+use crate::scene::graph::Scene;
+use crate::scene::sdf;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mesher {
+   MarchingCubes,
+   DualContouring,
+}
+impl Mesher {
+   pub const ALL: [Mesher; 2] = [Mesher::MarchingCubes, Mesher::DualContouring];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         Mesher::MarchingCubes => "Marching cubes",
+         Mesher::DualContouring => "Dual contouring",
+      }
+   }
+
+   pub fn description(&self) -> &'static str {
+      match self {
+         Mesher::MarchingCubes => "smooth, rounds off sharp edges",
+         Mesher::DualContouring => "keeps sharp edges and corners, slower",
+      }
+   }
+
+   /// meshes the scene's surface inside the settings' bounds, none if cancelled
+   pub fn mesh(&self, scene: &Scene, settings: &MeshSettings, progress: &Progress) -> Option<Mesh> {
+      match self {
+         Mesher::MarchingCubes => marching_cubes::mesh(scene, settings, progress),
+         Mesher::DualContouring => dual_contouring::mesh(scene, settings, progress),
+      }
+   }
+}
+
+
+/// region and detail the meshers sample the distance field at
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshSettings {
+   pub mesher: Mesher,
+   pub min: [f32; 3],
+   pub max: [f32; 3],
+   /// cells along the longest side of the bounds
+   pub resolution: u32,
+   /// caps surfaces cut off by the bounds, so infinite shapes like planes still give a watertight mesh
+   pub close_boundary: bool,
+}
+impl Default for MeshSettings {
+   fn default() -> Self {
+      Self {
+         mesher: Mesher::MarchingCubes,
+         min: [-4.0, -1.5, -4.0],
+         max: [4.0, 2.0, 4.0],
+         resolution: 128,
+         close_boundary: true,
+      }
+   }
+}
+impl MeshSettings {
+   pub fn size(&self) -> Vector3<f32> {
+      Vector3::from(self.max) - Vector3::from(self.min)
+   }
+
+   pub fn cell_size(&self) -> f32 {
+      let size = self.size();
+      size.x.max(size.y).max(size.z).max(1e-6) / self.resolution.max(1) as f32
+   }
+
+   /// cells along each axis, at least one
+   pub fn cells(&self) -> [usize; 3] {
+      let cell = self.cell_size();
+      let size = self.size();
+      [size.x, size.y, size.z].map(|length| ((length / cell).ceil() as usize).max(1))
+   }
+
+   /// the scene's distance, clipped to the bounds when `close_boundary` is set
+   pub fn distance(&self, scene: &Scene, p: Vector3<f32>) -> f32 {
+      let distance = eval::eval(scene, p);
+      if !self.close_boundary {
+         return distance;
+      }
+      // shrunk by half a cell so the outermost samples are always outside
+      let center = (Vector3::from(self.min) + Vector3::from(self.max)) * 0.5;
+      let half = self.size() * 0.5 - Vector3::new(1.0, 1.0, 1.0) * (self.cell_size() * 0.5);
+      distance.max(sdf::sd_box(p - center, half.x, half.y, half.z))
+   }
+}
+
+
+/// indexed triangles with per vertex normals and linear rgb colours, wound counter clockwise seen from outside
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+   pub positions: Vec<[f32; 3]>,
+   pub normals: Vec<[f32; 3]>,
+   pub colors: Vec<[f32; 3]>,
+   pub triangles: Vec<[u32; 3]>,
+}
+impl Mesh {
+   pub fn vertex_count(&self) -> usize {
+      self.positions.len()
+   }
+
+   pub fn triangle_count(&self) -> usize {
+      self.triangles.len()
+   }
+
+   /// normal of a triangle from its winding, zero for degenerate ones
+   pub fn face_normal(&self, triangle: [u32; 3]) -> Vector3<f32> {
+      let [a, b, c] = triangle.map(|i| Vector3::from(self.positions[i as usize]));
+      let normal = (b - a).cross(c - a);
+      if normal.magnitude2() > 0.0 { normal.normalize() } else { normal }
+   }
+
+   /// fills normals and colours from the scene at every vertex
+   pub fn shade(&mut self, scene: &Scene, progress: &Progress) {
+      progress.start("Shading", self.positions.len());
+      let shaded = parallel_map(self.positions.len(), progress, |i| {
+         let p = Vector3::from(self.positions[i]);
+         let normal = eval::normal(scene, p);
+         ([normal.x, normal.y, normal.z], eval::eval_surface(scene, p).color)
+      });
+      (self.normals, self.colors) = shaded.into_iter().unzip();
+   }
+}
+
+#[cfg(test)]
+impl Mesh {
+   /// every edge is shared by exactly two triangles, wound opposite ways, so the mesh has no holes
+   pub fn is_closed(&self) -> bool {
+      let mut edges = std::collections::HashMap::new();
+      for triangle in &self.triangles {
+         for i in 0..3 {
+            *edges.entry((triangle[i], triangle[(i + 1) % 3])).or_insert(0) += 1;
+         }
+      }
+      edges.iter().all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+   }
+}
+
+
+/// shared between a mesher on a background thread and the ui showing its progress
+#[derive(Debug, Default)]
+pub struct Progress {
+   stage: Mutex<&'static str>,
+   done: AtomicUsize,
+   total: AtomicUsize,
+   cancelled: AtomicBool,
+}
+impl Progress {
+   pub fn start(&self, stage: &'static str, total: usize) {
+      *self.stage.lock() = stage;
+      self.done.store(0, Ordering::Relaxed);
+      self.total.store(total, Ordering::Relaxed);
+   }
+
+   pub fn advance(&self, amount: usize) {
+      self.done.fetch_add(amount, Ordering::Relaxed);
+   }
+
+   pub fn stage(&self) -> &'static str {
+      *self.stage.lock()
+   }
+
+   /// of the current stage, 0 to 1
+   pub fn fraction(&self) -> f32 {
+      let total = self.total.load(Ordering::Relaxed);
+      if total == 0 {
+         return 0.0;
+      }
+      (self.done.load(Ordering::Relaxed) as f32 / total as f32).min(1.0)
+   }
+
+   pub fn cancel(&self) {
+      self.cancelled.store(true, Ordering::Relaxed);
+   }
+
+   pub fn is_cancelled(&self) -> bool {
+      self.cancelled.load(Ordering::Relaxed)
+   }
+}
+
+
+/// distance samples on the corners of the settings' cells
+#[derive(Clone, Debug)]
+pub struct Grid {
+   /// cells along each axis, there is one more sample than cells
+   pub cells: [usize; 3],
+   pub min: Vector3<f32>,
+   pub cell_size: f32,
+   pub values: Vec<f32>,
+}
+impl Grid {
+   /// samples every corner across all cores, none if cancelled part way
+   pub fn sample(scene: &Scene, settings: &MeshSettings, progress: &Progress) -> Option<Grid> {
+      let cells = settings.cells();
+      let min = Vector3::from(settings.min);
+      let cell_size = settings.cell_size();
+      let [nx, ny, nz] = cells.map(|n| n + 1);
+
+      progress.start("Sampling", nx * ny * nz);
+      let mut grid = Grid { cells, min, cell_size, values: vec![] };
+      let values = parallel_map(nx * ny * nz, progress, |index| {
+         settings.distance(scene, grid.position(grid.coordinates(index)))
+      });
+      grid.values = values;
+
+      (!progress.is_cancelled()).then_some(grid)
+   }
+
+   pub fn samples(&self) -> [usize; 3] {
+      self.cells.map(|n| n + 1)
+   }
+
+   pub fn index(&self, [x, y, z]: [usize; 3]) -> usize {
+      let [nx, ny, _] = self.samples();
+      x + nx * (y + ny * z)
+   }
+
+   pub fn coordinates(&self, index: usize) -> [usize; 3] {
+      let [nx, ny, _] = self.samples();
+      [index % nx, (index / nx) % ny, index / (nx * ny)]
+   }
+
+   pub fn position(&self, [x, y, z]: [usize; 3]) -> Vector3<f32> {
+      self.min + Vector3::new(x as f32, y as f32, z as f32) * self.cell_size
+   }
+
+   pub fn value(&self, coordinates: [usize; 3]) -> f32 {
+      self.values[self.index(coordinates)]
+   }
+}
+
+
+/// `f` of every index below `count` across all cores, stops early once the progress is cancelled
+pub fn parallel_map<R: Send + Default + Clone>(count: usize, progress: &Progress, f: impl Fn(usize) -> R + Sync) -> Vec<R> {
+   const CHUNK: usize = 1024;
+   let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+   let mut results = vec![R::default(); count];
+   let next = AtomicUsize::new(0);
+
+   // chunks are handed out as threads free up, so uneven work still balances
+   let chunks: Vec<Mutex<&mut [R]>> = results.chunks_mut(CHUNK).map(Mutex::new).collect();
+   thread::scope(|scope| {
+      for _ in 0..threads {
+         scope.spawn(|| loop {
+            let chunk = next.fetch_add(1, Ordering::Relaxed);
+            if chunk >= chunks.len() || progress.is_cancelled() {
+               break;
+            }
+            let mut output = chunks[chunk].lock();
+            let start = chunk * CHUNK;
+            for (offset, result) in output.iter_mut().enumerate() {
+               *result = f(start + offset);
+            }
+            progress.advance(output.len());
+         });
+      }
+   });
+   drop(chunks);
+
+   results
+}
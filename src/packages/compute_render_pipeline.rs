@@ -0,0 +1,168 @@
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, ComputePipeline, IndexFormat, RenderPipeline, PipelineLayout, TextureView};
+use crate::inbuilt::setup::Setup;
+use crate::inbuilt::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
+use crate::inbuilt::vertex_package::VertexPackage;
+use crate::packages::path_trace_pipeline::DISPLAY_SOURCE;
+use crate::packages::scene_render_pipeline::{self, SHADER_TEMPLATE};
+use crate::scene::compiler;
+use crate::scene::compiler::CompileError;
+use crate::utility::structs::StorageTexturePackage;
+
+/// appended to the spliced scene shader, it uses the scene's `map` and the raymarcher's lighting
+pub const COMPUTE_SOURCE: &str = include_str!("compute_render_pipeline.wgsl");
+
+/// pixels along each side of a workgroup's tile, matches `cs_render`
+const TILE_SIZE: u32 = 8;
+
+
+/// the compiled raymarcher as a compute shader, a workgroup per tile of pixels writing into a float
+/// storage texture that's then drawn into the target
+///
+/// one thread per tile marches a cone around all the tile's rays first, so every ray in it starts
+/// from where the nearest surface could begin rather than from the camera
+pub struct ComputeRenderPipeline {
+   compute_layout: PipelineLayout,
+   /// none until a scene's shader builds
+   compute_pipeline: Option<ComputePipeline>,
+   map_source: String,
+   pub error: Option<CompileError>,
+
+   display_pipeline: RenderPipeline,
+   vertex_package: VertexPackage,
+   output: StorageTexturePackage,
+}
+impl ComputeRenderPipeline {
+   pub fn new(setup: &Setup, view_layout: &BindGroupLayout, params_layout: &BindGroupLayout) -> Self {
+      let output = StorageTexturePackage::new(setup, (1.0, 1.0));
+
+      let compute_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Compute Render Pipeline Layout"),
+         bind_group_layouts: &[
+            view_layout,
+            params_layout,
+            &output.bind_group_layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      let display_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Compute Render Display Pipeline Layout"),
+         bind_group_layouts: &[
+            &output.bind_group_layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      Self {
+         compute_layout,
+         compute_pipeline: None,
+         map_source: String::new(),
+         error: None,
+         display_pipeline: scene_render_pipeline::create_pipeline(setup, &display_layout, DISPLAY_SOURCE.to_string()),
+         vertex_package: VertexPackage::new(&setup.device, SQUARE_VERTICES, SQUARE_INDICES),
+         output,
+      }
+   }
+
+   /// rebuilds for a new map and resizes the output to the image
+   pub fn prepare(&mut self, setup: &Setup, map_source: &str, resolution: (u32, u32)) {
+      if map_source != self.map_source {
+         self.rebuild(setup, map_source);
+      }
+
+      let (width, height) = (resolution.0.max(1), resolution.1.max(1));
+      if (self.output.size.width, self.output.size.height) != (width, height) {
+         self.output.remake(setup, (width as f32, height as f32));
+      }
+   }
+
+   /// on failure the old pipeline keeps drawing, though its params no longer line up
+   fn rebuild(&mut self, setup: &Setup, map_source: &str) {
+      self.map_source = map_source.to_string();
+      let source = compiler::splice(SHADER_TEMPLATE, map_source).and_then(|source| {
+         let source = source + COMPUTE_SOURCE;
+         compiler::validate(&source)?;
+         Ok(source)
+      });
+
+      match source {
+         Ok(source) => {
+            let shader = setup.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+               label: Some("Compute Render Shader"),
+               source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            self.compute_pipeline = Some(setup.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+               label: Some("Compute Render Pipeline"),
+               layout: Some(&self.compute_layout),
+               module: &shader,
+               entry_point: "cs_render",
+               compilation_options: Default::default(),
+            }));
+            self.error = None;
+         }
+         Err(error) => {
+            log::error!("{error}");
+            self.error = Some(error);
+         }
+      }
+   }
+
+   /// renders every tile into the storage texture, then draws it into `target`
+   pub fn render_pass(
+      &self, encoder: &mut CommandEncoder,
+      target: &TextureView,
+      view_bind_group: &BindGroup,
+      params_bind_group: &BindGroup,
+   ) {
+      if let Some(compute_pipeline) = &self.compute_pipeline {
+         let size = self.output.size;
+         let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Compute Render Pass"),
+            timestamp_writes: None,
+         });
+         compute_pass.set_pipeline(compute_pipeline);
+         compute_pass.set_bind_group(0, view_bind_group, &[]);
+         compute_pass.set_bind_group(1, params_bind_group, &[]);
+         compute_pass.set_bind_group(2, &self.output.bind_group, &[]);
+         compute_pass.dispatch_workgroups(size.width.div_ceil(TILE_SIZE), size.height.div_ceil(TILE_SIZE), 1);
+      }
+
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+         label: Some("Compute Render Display Pass"),
+         color_attachments: &[
+            Some(wgpu::RenderPassColorAttachment {
+               view: target,
+               resolve_target: None,
+               ops: wgpu::Operations {
+                  load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                  store: wgpu::StoreOp::Store,
+               }
+            })
+         ],
+         depth_stencil_attachment: None,
+         occlusion_query_set: None,
+         timestamp_writes: None,
+      });
+
+      render_pass.set_pipeline(&self.display_pipeline);
+      render_pass.set_bind_group(0, &self.output.bind_group, &[]);
+      render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
+      render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
+      render_pass.draw_indexed(0..self.vertex_package.num_indices, 0, 0..1);
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::graph::Scene;
+
+   #[test]
+   fn shader_validates_with_the_demo_map() {
+      let source = compiler::compile(SHADER_TEMPLATE, &Scene::demo()).unwrap() + COMPUTE_SOURCE;
+      if let Err(error) = compiler::validate(&source) {
+         panic!("{error}");
+      }
+   }
+}
@@ -0,0 +1,63 @@
+// appended to the spliced scene shader by `ComputeRenderPipeline`, so `map`, `march_from` and `shade_hit` come from there
+
+// rgb is the shaded colour, a is always 1 so the path tracer's display shader can draw it
+@group(2) @binding(0)
+var output: texture_storage_2d<rgba32float, read_write>;
+
+const TILE_SIZE: u32 = 8u;
+
+// how far every ray in the tile can go before any of them could reach a surface
+var<workgroup> tile_start: f32;
+
+// marches the tile's centre ray, only stepping as far as is safe for every ray within `spread` of it.
+// at `t` the other rays are at most `t * spread` away, so the field there is at least `d - t * spread`
+fn cone_march(ro: vec3<f32>, rd: vec3<f32>, spread: f32) -> f32 {
+    var t = 0.0;
+    for (var i = 0u; i < view.max_steps; i++) {
+        let safe = map(ro + rd * t).dist * view.step_scale - t * spread;
+        if safe < view.surface_epsilon {
+            break;
+        }
+        t += safe;
+        if t > view.max_distance {
+            break;
+        }
+    }
+    return t;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_render(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+    @builtin(workgroup_id) tile: vec3<u32>,
+) {
+    let ro = view.camera_position.xyz;
+
+    // one thread finds where the whole tile can start, the rest skip straight there
+    if index == 0u {
+        let corner = vec2<f32>(tile.xy * TILE_SIZE);
+        let center = camera_ray(corner + f32(TILE_SIZE) * 0.5);
+        var spread = 0.0;
+        for (var i = 0u; i < 4u; i++) {
+            let offset = vec2<f32>(f32(i & 1u), f32(i >> 1u)) * f32(TILE_SIZE - 1u) + 0.5;
+            spread = max(spread, length(camera_ray(corner + offset) - center));
+        }
+        tile_start = cone_march(ro, center, spread);
+    }
+    let start = workgroupUniformLoad(&tile_start);
+
+    let size = textureDimensions(output);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    let rd = camera_ray(vec2<f32>(id.xy) + 0.5);
+    var color = view.background.rgb;
+    if view.debug_view != DEBUG_SHADED {
+        color = debug_shade(ro, rd);
+    } else if start <= view.max_distance {
+        color = shade_hit(ro, rd, march_from(ro, rd, start));
+    }
+    textureStore(output, id.xy, vec4<f32>(color, 1.0));
+}
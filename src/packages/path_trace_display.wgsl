@@ -0,0 +1,27 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+// rgb is summed radiance and a the number of samples in it
+@group(0) @binding(0)
+var accumulation: texture_storage_2d<rgba32float, read_write>;
+
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sum = textureLoad(accumulation, vec2<u32>(in.clip_position.xy));
+    return vec4<f32>(sum.rgb / max(sum.a, 1.0), 1.0);
+}
@@ -0,0 +1,238 @@
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, ComputePipeline, IndexFormat, PipelineLayout, RenderPipeline, TextureView};
+use crate::inbuilt::setup::Setup;
+use crate::inbuilt::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
+use crate::inbuilt::vertex_package::VertexPackage;
+use crate::packages::scene_render_pipeline::{self, ViewUniform, SHADER_TEMPLATE};
+use crate::scene::compiler;
+use crate::scene::compiler::{CompileError, SceneParams};
+use crate::utility::structs::{PingPongData, StorageTexturePackage};
+
+/// appended to the spliced scene shader, it uses the scene's `map` and the raymarcher's lighting
+pub const PATH_TRACE_SOURCE: &str = include_str!("path_trace_pipeline.wgsl");
+
+/// draws a float storage texture with its rgb divided by its alpha, also used by `ComputeRenderPipeline`
+pub(crate) const DISPLAY_SOURCE: &str = include_str!("path_trace_display.wgsl");
+
+/// pixels along each side of a workgroup, matches `cs_trace`
+const WORKGROUP_SIZE: u32 = 8;
+
+
+/// set from the ui every frame
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PathTraceSettings {
+   /// samples per pixel after which tracing stops
+   pub budget: u32,
+   /// bounces after the first hit, more brightens shadows and adds reflections of reflections
+   pub max_bounces: u32,
+}
+impl Default for PathTraceSettings {
+   fn default() -> Self {
+      Self {
+         budget: 256,
+         max_bounces: 4,
+      }
+   }
+}
+
+
+/// progressive path tracing, each frame adds one sample per pixel until the budget is spent
+///
+/// the running sums ping-pong between two float textures, last frame's is read while this frame's is written.
+/// anything that changes the picture, the camera, the scene or the image size, starts the sums again
+pub struct PathTracePipeline {
+   compute_layout: PipelineLayout,
+   /// none until a scene's shader builds
+   compute_pipeline: Option<ComputePipeline>,
+   map_source: String,
+   pub error: Option<CompileError>,
+
+   display_pipeline: RenderPipeline,
+   vertex_package: VertexPackage,
+   accumulation: PingPongData<StorageTexturePackage>,
+
+   pub settings: PathTraceSettings,
+   /// samples in the current sums
+   pub samples: u32,
+   /// the view and params the current sums were traced with
+   traced: Vec<u8>,
+   /// set by `prepare` when this frame adds a sample
+   pending: bool,
+}
+impl PathTracePipeline {
+   pub fn new(setup: &Setup, view_layout: &BindGroupLayout, params_layout: &BindGroupLayout) -> Self {
+      let accumulation = PingPongData::new(
+         StorageTexturePackage::new(setup, (1.0, 1.0)),
+         StorageTexturePackage::new(setup, (1.0, 1.0)),
+      );
+      let texture_layout = &accumulation.pull_current().bind_group_layout;
+
+      let compute_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Path Trace Pipeline Layout"),
+         bind_group_layouts: &[
+            view_layout,
+            params_layout,
+            texture_layout,
+            texture_layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      let display_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Path Trace Display Pipeline Layout"),
+         bind_group_layouts: &[
+            texture_layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      Self {
+         compute_layout,
+         compute_pipeline: None,
+         map_source: String::new(),
+         error: None,
+         display_pipeline: scene_render_pipeline::create_pipeline(setup, &display_layout, DISPLAY_SOURCE.to_string()),
+         vertex_package: VertexPackage::new(&setup.device, SQUARE_VERTICES, SQUARE_INDICES),
+         accumulation,
+         settings: PathTraceSettings::default(),
+         samples: 0,
+         traced: vec![],
+         pending: false,
+      }
+   }
+
+   /// starts the sums again from nothing
+   pub fn restart(&mut self) {
+      self.samples = 0;
+   }
+
+   /// rebuilds for a new map, restarts if anything the image depends on changed,
+   /// then fills in the sample number and bounces the shader reads from `view`
+   pub fn prepare(&mut self, setup: &Setup, map_source: &str, params: &SceneParams, view: &mut ViewUniform) {
+      if map_source != self.map_source {
+         self.rebuild(setup, map_source);
+         self.restart();
+      }
+
+      let [width, height] = view.resolution.map(|size| size as u32);
+      let size = self.accumulation.pull_current().size;
+      if (size.width, size.height) != (width, height) {
+         let size = (width as f32, height as f32);
+         self.accumulation = PingPongData::new(StorageTexturePackage::new(setup, size), StorageTexturePackage::new(setup, size));
+         self.restart();
+      }
+
+      view.sample_index = 0;
+      view.max_bounces = self.settings.max_bounces;
+      let traced = [bytemuck::bytes_of(view), bytemuck::bytes_of(params)].concat();
+      if traced != self.traced {
+         self.traced = traced;
+         self.restart();
+      }
+
+      view.sample_index = self.samples;
+      self.pending = self.compute_pipeline.is_some() && self.samples < self.settings.budget.max(1);
+   }
+
+   /// on failure the old pipeline keeps tracing, though its params no longer line up
+   fn rebuild(&mut self, setup: &Setup, map_source: &str) {
+      self.map_source = map_source.to_string();
+      let source = compiler::splice(SHADER_TEMPLATE, map_source).and_then(|source| {
+         let source = source + PATH_TRACE_SOURCE;
+         compiler::validate(&source)?;
+         Ok(source)
+      });
+
+      match source {
+         Ok(source) => {
+            let shader = setup.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+               label: Some("Path Trace Shader"),
+               source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            self.compute_pipeline = Some(setup.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+               label: Some("Path Trace Pipeline"),
+               layout: Some(&self.compute_layout),
+               module: &shader,
+               entry_point: "cs_trace",
+               compilation_options: Default::default(),
+            }));
+            self.error = None;
+         }
+         Err(error) => {
+            log::error!("{error}");
+            self.error = Some(error);
+         }
+      }
+   }
+
+   /// adds this frame's sample if `prepare` asked for one, then draws the average into `target`
+   pub fn render_pass(
+      &mut self, encoder: &mut CommandEncoder,
+      target: &TextureView,
+      view_bind_group: &BindGroup,
+      params_bind_group: &BindGroup,
+   ) {
+      if let (true, Some(compute_pipeline)) = (self.pending, &self.compute_pipeline) {
+         let size = self.accumulation.pull_current().size;
+         let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Path Trace Pass"),
+            timestamp_writes: None,
+         });
+         compute_pass.set_pipeline(compute_pipeline);
+         compute_pass.set_bind_group(0, view_bind_group, &[]);
+         compute_pass.set_bind_group(1, params_bind_group, &[]);
+         compute_pass.set_bind_group(2, &self.accumulation.pull_current().bind_group, &[]);
+         compute_pass.set_bind_group(3, &self.accumulation.pull_other().bind_group, &[]);
+         compute_pass.dispatch_workgroups(size.width.div_ceil(WORKGROUP_SIZE), size.height.div_ceil(WORKGROUP_SIZE), 1);
+         drop(compute_pass);
+
+         self.accumulation.ping_pong();
+         self.samples += 1;
+         self.pending = false;
+      }
+
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+         label: Some("Path Trace Display Pass"),
+         color_attachments: &[
+            Some(wgpu::RenderPassColorAttachment {
+               view: target,
+               resolve_target: None,
+               ops: wgpu::Operations {
+                  load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                  store: wgpu::StoreOp::Store,
+               }
+            })
+         ],
+         depth_stencil_attachment: None,
+         occlusion_query_set: None,
+         timestamp_writes: None,
+      });
+
+      render_pass.set_pipeline(&self.display_pipeline);
+      render_pass.set_bind_group(0, &self.accumulation.pull_current().bind_group, &[]);
+      render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
+      render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
+      render_pass.draw_indexed(0..self.vertex_package.num_indices, 0, 0..1);
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::graph::Scene;
+
+   #[test]
+   fn shader_validates_with_the_demo_map() {
+      let source = compiler::compile(SHADER_TEMPLATE, &Scene::demo()).unwrap() + PATH_TRACE_SOURCE;
+      if let Err(error) = compiler::validate(&source) {
+         panic!("{error}");
+      }
+   }
+
+   #[test]
+   fn display_shader_validates() {
+      if let Err(error) = compiler::validate(DISPLAY_SOURCE) {
+         panic!("{error}");
+      }
+   }
+}
@@ -0,0 +1,144 @@
+// appended to the spliced scene shader by `PathTracePipeline`, so `map`, `march`, `brdf` and the rest come from there
+
+// running sums, rgb is radiance and a the number of samples
+@group(2) @binding(0)
+var previous: texture_storage_2d<rgba32float, read_write>;
+@group(3) @binding(0)
+var current: texture_storage_2d<rgba32float, read_write>;
+
+// pcg, good enough spread for one hash per random number
+fn hash(x: u32) -> u32 {
+    let state = x * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// in [0, 1)
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed >> 8u) / 16777216.0;
+}
+
+fn random_unit_vector(seed: ptr<function, u32>) -> vec3<f32> {
+    let z = random(seed) * 2.0 - 1.0;
+    let angle = random(seed) * 2.0 * PI;
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3<f32>(r * cos(angle), r * sin(angle), z);
+}
+
+// cosine weighted about `n`, so lambert surfaces need no other weighting
+fn random_cosine_direction(n: vec3<f32>, seed: ptr<function, u32>) -> vec3<f32> {
+    let d = n + random_unit_vector(seed);
+    if dot(d, d) < 1e-8 {
+        return n;
+    }
+    return normalize(d);
+}
+
+fn occluded(ro: vec3<f32>, rd: vec3<f32>, max_t: f32) -> bool {
+    var t = view.surface_epsilon * 2.0;
+    for (var i = 0u; i < view.max_steps && t < max_t; i++) {
+        let h = map(ro + rd * t).dist;
+        if h < view.surface_epsilon {
+            return true;
+        }
+        t += h * view.step_scale;
+    }
+    return false;
+}
+
+// every light once, each shadow ray aimed a little off by the penumbra so edges soften over many samples
+fn direct_light(p: vec3<f32>, n: vec3<f32>, v: vec3<f32>, surface: Surface, seed: ptr<function, u32>) -> vec3<f32> {
+    let metallic = clamp(surface.material.x, 0.0, 1.0);
+    let roughness = clamp(surface.material.y, 0.02, 1.0);
+    let origin = p + n * (view.surface_epsilon * 4.0);
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < view.light_count; i++) {
+        let light = view.lights[i];
+        let sample = sample_light(light, p);
+        let l = normalize(sample.direction + random_unit_vector(seed) * view.penumbra);
+        let n_dot_l = dot(n, l);
+        if n_dot_l <= 0.0 || max(sample.radiance.r, max(sample.radiance.g, sample.radiance.b)) <= 0.0 {
+            continue;
+        }
+        if light.shadows != 0u && occluded(origin, l, sample.distance) {
+            continue;
+        }
+        color += brdf(n, v, l, surface.color, metallic, roughness) * sample.radiance * n_dot_l;
+    }
+    return color;
+}
+
+// one path, lights are sampled at every hit and the background lights whatever escapes
+fn trace(ro_in: vec3<f32>, rd_in: vec3<f32>, seed: ptr<function, u32>) -> vec3<f32> {
+    var ro = ro_in;
+    var rd = rd_in;
+    var throughput = vec3<f32>(1.0);
+    var radiance = vec3<f32>(0.0);
+
+    for (var bounce = 0u; bounce <= view.max_bounces; bounce++) {
+        let hit = march(ro, rd);
+        if !hit.hit {
+            radiance += throughput * view.background.rgb;
+            break;
+        }
+
+        let p = ro + rd * hit.t;
+        let n = calc_normal(p);
+        let surface = hit.surface;
+        radiance += throughput * direct_light(p, n, -rd, surface, seed);
+
+        let metallic = clamp(surface.material.x, 0.0, 1.0);
+        let roughness = clamp(surface.material.y, 0.02, 1.0);
+        let f0 = mix(vec3<f32>(0.04), surface.color, metallic);
+        var specular_chance = clamp(max(f0.r, max(f0.g, f0.b)), 0.04, 0.96);
+        if view.shading_model == SHADING_LAMBERT {
+            specular_chance = 0.0;
+        }
+
+        if random(seed) < specular_chance {
+            rd = normalize(reflect(rd, n) + random_unit_vector(seed) * roughness * roughness);
+            throughput *= f0 / specular_chance;
+            if dot(rd, n) <= 0.0 {
+                break;
+            }
+        } else {
+            rd = random_cosine_direction(n, seed);
+            throughput *= surface.color * (1.0 - metallic) / (1.0 - specular_chance);
+        }
+        ro = p + n * (view.surface_epsilon * 4.0);
+
+        // russian roulette, dim paths stop early and the survivors make up for them
+        if bounce >= 2u {
+            let survive = clamp(max(throughput.r, max(throughput.g, throughput.b)), 0.05, 1.0);
+            if random(seed) > survive {
+                break;
+            }
+            throughput /= survive;
+        }
+    }
+    return radiance;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_trace(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(current);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    var seed = hash(id.x + hash(id.y + hash(view.sample_index)));
+    let pixel = vec2<f32>(id.xy) + vec2<f32>(random(&seed), random(&seed));
+    var radiance = trace(view.camera_position.xyz, camera_ray(pixel), &seed);
+    // one bad sample would stay in the sum for good
+    if any(radiance != radiance) {
+        radiance = vec3<f32>(0.0);
+    }
+
+    var sum = vec4<f32>(0.0);
+    if view.sample_index > 0u {
+        sum = textureLoad(previous, id.xy);
+    }
+    textureStore(current, id.xy, sum + vec4<f32>(radiance, 1.0));
+}
@@ -0,0 +1,507 @@
+use bytemuck::{Pod, Zeroable};
+use egui::{ComboBox, Context, Ui, Window};
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, Extent3d, IndexFormat, PipelineLayout, RenderPipeline, Sampler, ShaderModule, ShaderStages, TextureFormat, TextureView};
+use crate::inbuilt::setup::Setup;
+use crate::inbuilt::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
+use crate::inbuilt::vertex_package::{Vertex, VertexPackage};
+use crate::utility::structs::{PingPongData, UniformPackageSingles};
+use crate::utility::widgets::{FieldOptions, ParamField};
+
+/// what the scene is drawn into and the passes work in, linear and free to go above 1
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// every pass's fragment shader, each one an entry point
+const POST_SOURCE: &str = include_str!("post_process_pipeline.wgsl");
+
+
+/// values match the `TONEMAP_*` constants in `post_process_pipeline.wgsl`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Tonemapper {
+   /// filmic contrast, saturated brights skew towards yellow
+   Aces = 0,
+   /// brights desaturate towards white the way film does
+   Agx = 1,
+   /// gentle, never quite reaches white
+   Reinhard = 2,
+}
+impl Tonemapper {
+   pub const ALL: [Tonemapper; 3] = [Tonemapper::Aces, Tonemapper::Agx, Tonemapper::Reinhard];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         Tonemapper::Aces => "ACES",
+         Tonemapper::Agx => "AgX",
+         Tonemapper::Reinhard => "Reinhard",
+      }
+   }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PostEffect {
+   Exposure,
+   Bloom,
+   Tonemap,
+   Fxaa,
+   Vignette,
+}
+impl PostEffect {
+   pub fn name(&self) -> &'static str {
+      match self {
+         PostEffect::Exposure => "Exposure",
+         PostEffect::Bloom => "Bloom",
+         PostEffect::Tonemap => "Tonemap",
+         PostEffect::Fxaa => "FXAA",
+         PostEffect::Vignette => "Vignette",
+      }
+   }
+
+   pub fn description(&self) -> &'static str {
+      match self {
+         PostEffect::Exposure => "Scales the brightness by powers of two",
+         PostEffect::Bloom => "Bright areas glow into their surroundings, best before tonemapping",
+         PostEffect::Tonemap => "Squeezes unbounded brightness into what the screen can show",
+         PostEffect::Fxaa => "Smooths jagged edges, best after tonemapping",
+         PostEffect::Vignette => "Darkens towards the corners",
+      }
+   }
+}
+
+
+/// the post process chain, passes run top to bottom and the disabled ones are skipped
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostSettings {
+   pub passes: Vec<(PostEffect, bool)>,
+   /// in stops, each one doubles the brightness
+   pub exposure: f32,
+   pub tonemapper: Tonemapper,
+   /// brightness above which things glow
+   pub bloom_threshold: f32,
+   pub bloom_intensity: f32,
+   /// spread of the blur
+   pub bloom_radius: f32,
+   pub vignette_strength: f32,
+   /// distance from the centre, 1 being a corner, where the darkening starts
+   pub vignette_radius: f32,
+   /// longest distance in pixels fxaa blurs along an edge
+   pub fxaa_span: f32,
+}
+impl Default for PostSettings {
+   /// every pass off, so the viewport shows the scene shader's output untouched
+   fn default() -> Self {
+      Self {
+         passes: vec![
+            (PostEffect::Exposure, false),
+            (PostEffect::Bloom, false),
+            (PostEffect::Tonemap, false),
+            (PostEffect::Fxaa, false),
+            (PostEffect::Vignette, false),
+         ],
+         exposure: 0.0,
+         tonemapper: Tonemapper::Aces,
+         bloom_threshold: 1.0,
+         bloom_intensity: 0.5,
+         bloom_radius: 1.5,
+         vignette_strength: 0.4,
+         vignette_radius: 0.5,
+         fxaa_span: 8.0,
+      }
+   }
+}
+impl PostSettings {
+   /// returns true if anything changed
+   pub fn ui(&mut self, ui: &mut Ui) -> bool {
+      let before = self.clone();
+      if ui.button("Reset").clicked() {
+         *self = Self::default();
+      }
+
+      let mut swap = None;
+      let count = self.passes.len();
+      for index in 0..count {
+         ui.push_id(("post_pass", index), |ui| {
+            let (effect, enabled) = &mut self.passes[index];
+            let effect = *effect;
+            ui.horizontal(|ui| {
+               ui.checkbox(enabled, effect.name()).on_hover_text(effect.description());
+               ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                  if ui.add_enabled(index + 1 < count, egui::Button::new("⏷").small()).on_hover_text("Run later").clicked() {
+                     swap = Some(index);
+                  }
+                  if ui.add_enabled(index > 0, egui::Button::new("⏶").small()).on_hover_text("Run earlier").clicked() {
+                     swap = Some(index - 1);
+                  }
+               });
+            });
+
+            if *enabled {
+               ui.indent("params", |ui| self.effect_ui(ui, effect));
+            }
+         });
+      }
+      if let Some(index) = swap {
+         self.passes.swap(index, index + 1);
+      }
+
+      *self != before
+   }
+
+   fn effect_ui(&mut self, ui: &mut Ui, effect: PostEffect) {
+      match effect {
+         PostEffect::Exposure => {
+            self.exposure.field_ui(ui, &FieldOptions::new("stops").with_range(-8.0..=8.0));
+         }
+         PostEffect::Bloom => {
+            self.bloom_threshold.field_ui(ui, &FieldOptions::new("threshold").with_range(0.0..=4.0));
+            self.bloom_intensity.field_ui(ui, &FieldOptions::new("intensity").with_range(0.0..=2.0));
+            self.bloom_radius.field_ui(ui, &FieldOptions::new("radius").with_range(0.5..=4.0));
+         }
+         PostEffect::Tonemap => {
+            ComboBox::from_id_source("tonemapper")
+                .selected_text(self.tonemapper.name())
+                .show_ui(ui, |ui| {
+                   for tonemapper in Tonemapper::ALL {
+                      ui.selectable_value(&mut self.tonemapper, tonemapper, tonemapper.name());
+                   }
+                });
+         }
+         PostEffect::Fxaa => {
+            self.fxaa_span.field_ui(ui, &FieldOptions::new("span").with_range(1.0..=16.0));
+         }
+         PostEffect::Vignette => {
+            self.vignette_strength.field_ui(ui, &FieldOptions::new("strength").with_range(0.0..=1.0));
+            self.vignette_radius.field_ui(ui, &FieldOptions::new("radius").with_range(0.0..=1.0));
+         }
+      }
+   }
+
+   /// the chain in its own window, for the View menu
+   pub fn window(&mut self, context: &Context, open: &mut bool) {
+      Window::new("Post Processing")
+          .open(open)
+          .resizable(false)
+          .show(context, |ui| {
+             self.ui(ui);
+          });
+   }
+}
+
+
+/// laid out to match `Post` in `post_process_pipeline.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, PartialEq)]
+pub struct PostUniform {
+   /// linear multiplier
+   pub exposure: f32,
+   pub tonemapper: u32,
+   pub bloom_threshold: f32,
+   pub bloom_intensity: f32,
+   pub bloom_radius: f32,
+   pub vignette_strength: f32,
+   pub vignette_radius: f32,
+   pub fxaa_span: f32,
+}
+impl PostUniform {
+   pub fn new(settings: &PostSettings) -> Self {
+      Self {
+         exposure: settings.exposure.exp2(),
+         tonemapper: settings.tonemapper as u32,
+         bloom_threshold: settings.bloom_threshold,
+         bloom_intensity: settings.bloom_intensity,
+         bloom_radius: settings.bloom_radius,
+         vignette_strength: settings.vignette_strength,
+         vignette_radius: settings.vignette_radius,
+         fxaa_span: settings.fxaa_span,
+      }
+   }
+}
+
+
+/// a float texture passes draw into, bound with a linear sampler for the next pass to read
+struct Target {
+   view: TextureView,
+   bind_group: BindGroup,
+   size: (u32, u32),
+}
+impl Target {
+   fn new(setup: &Setup, layout: &BindGroupLayout, sampler: &Sampler, size: (u32, u32)) -> Self {
+      let size = (size.0.max(1), size.1.max(1));
+      let texture = setup.device.create_texture(&wgpu::TextureDescriptor {
+         label: Some("Post Process Target"),
+         size: Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+         mip_level_count: 1,
+         sample_count: 1,
+         dimension: wgpu::TextureDimension::D2,
+         format: HDR_FORMAT,
+         usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+         view_formats: &[],
+      });
+      let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+      let bind_group = setup.device.create_bind_group(&wgpu::BindGroupDescriptor {
+         label: Some("Post Process Target"),
+         layout,
+         entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&view) },
+            wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+         ],
+      });
+
+      Self { view, bind_group, size }
+   }
+}
+
+
+struct Pipelines {
+   copy: RenderPipeline,
+   exposure: RenderPipeline,
+   tonemap: RenderPipeline,
+   fxaa: RenderPipeline,
+   bloom_threshold: RenderPipeline,
+   blur_horizontal: RenderPipeline,
+   blur_vertical: RenderPipeline,
+   bloom_composite: RenderPipeline,
+   vignette: RenderPipeline,
+}
+
+
+/// the scene is drawn into a float target, then each enabled pass reads the last result and writes
+/// the other of a pair, and a final copy writes the egui texture
+pub struct PostProcessPipeline {
+   vertex_package: VertexPackage,
+   texture_layout: BindGroupLayout,
+   sampler: Sampler,
+   uniform_package: UniformPackageSingles<PostUniform>,
+   pipelines: Pipelines,
+
+   targets: PingPongData<Target>,
+   /// half resolution, blurred back and forth between the two
+   bloom: [Target; 2],
+   pub settings: PostSettings,
+}
+impl PostProcessPipeline {
+   pub fn new(setup: &Setup, output_format: TextureFormat, size: (u32, u32)) -> Self {
+      let texture_layout = setup.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+         label: Some("Post Process Texture Layout"),
+         entries: &[
+            wgpu::BindGroupLayoutEntry {
+               binding: 0,
+               visibility: ShaderStages::FRAGMENT,
+               ty: wgpu::BindingType::Texture {
+                  sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                  view_dimension: wgpu::TextureViewDimension::D2,
+                  multisampled: false,
+               },
+               count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+               binding: 1,
+               visibility: ShaderStages::FRAGMENT,
+               ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+               count: None,
+            },
+         ],
+      });
+
+      let sampler = setup.device.create_sampler(&wgpu::SamplerDescriptor {
+         label: Some("Post Process Sampler"),
+         address_mode_u: wgpu::AddressMode::ClampToEdge,
+         address_mode_v: wgpu::AddressMode::ClampToEdge,
+         mag_filter: wgpu::FilterMode::Linear,
+         min_filter: wgpu::FilterMode::Linear,
+         ..Default::default()
+      });
+
+      let settings = PostSettings::default();
+      let uniform_package = UniformPackageSingles::create(setup, ShaderStages::FRAGMENT, PostUniform::new(&settings));
+
+      let layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Post Process Pipeline Layout"),
+         bind_group_layouts: &[&texture_layout, &uniform_package.layout],
+         push_constant_ranges: &[],
+      });
+      // the composite also reads the blurred bloom
+      let composite_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Post Process Composite Pipeline Layout"),
+         bind_group_layouts: &[&texture_layout, &uniform_package.layout, &texture_layout],
+         push_constant_ranges: &[],
+      });
+
+      let shader = setup.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+         label: Some("Post Process Shader"),
+         source: wgpu::ShaderSource::Wgsl(POST_SOURCE.into()),
+      });
+      let create = |entry_point, layout, format| create_pipeline(setup, &shader, entry_point, layout, format);
+      let pipelines = Pipelines {
+         copy: create("fs_copy", &layout, output_format),
+         exposure: create("fs_exposure", &layout, HDR_FORMAT),
+         tonemap: create("fs_tonemap", &layout, HDR_FORMAT),
+         fxaa: create("fs_fxaa", &layout, HDR_FORMAT),
+         bloom_threshold: create("fs_bloom_threshold", &layout, HDR_FORMAT),
+         blur_horizontal: create("fs_blur_horizontal", &layout, HDR_FORMAT),
+         blur_vertical: create("fs_blur_vertical", &layout, HDR_FORMAT),
+         bloom_composite: create("fs_bloom_composite", &composite_layout, HDR_FORMAT),
+         vignette: create("fs_vignette", &layout, HDR_FORMAT),
+      };
+
+      let targets = PingPongData::new(
+         Target::new(setup, &texture_layout, &sampler, size),
+         Target::new(setup, &texture_layout, &sampler, size),
+      );
+      let half = (size.0.div_ceil(2), size.1.div_ceil(2));
+      let bloom = [
+         Target::new(setup, &texture_layout, &sampler, half),
+         Target::new(setup, &texture_layout, &sampler, half),
+      ];
+
+      Self {
+         vertex_package: VertexPackage::new(&setup.device, SQUARE_VERTICES, SQUARE_INDICES),
+         texture_layout,
+         sampler,
+         uniform_package,
+         pipelines,
+         targets,
+         bloom,
+         settings,
+      }
+   }
+
+   /// where the scene should be drawn this frame
+   pub fn input(&self) -> &TextureView {
+      &self.targets.pull_current().view
+   }
+
+   /// matches the targets to the output size and uploads the settings
+   pub fn update(&mut self, setup: &Setup, size: (u32, u32)) {
+      let size = (size.0.max(1), size.1.max(1));
+      if self.targets.pull_current().size != size {
+         let target = || Target::new(setup, &self.texture_layout, &self.sampler, size);
+         self.targets = PingPongData::new(target(), target());
+         let half = (size.0.div_ceil(2), size.1.div_ceil(2));
+         let target = || Target::new(setup, &self.texture_layout, &self.sampler, half);
+         self.bloom = [target(), target()];
+      }
+
+      let uniform = PostUniform::new(&self.settings);
+      if uniform != self.uniform_package.data {
+         self.uniform_package.data = uniform;
+         self.uniform_package.update_with_data(&setup.queue);
+      }
+   }
+
+   /// runs the enabled passes over what was drawn into `input` and writes the result to `output`
+   pub fn render_pass(&mut self, encoder: &mut CommandEncoder, output: &TextureView) {
+      let uniform = &self.uniform_package.bind_group;
+      for (effect, _) in self.settings.passes.iter().filter(|(_, enabled)| *enabled) {
+         let pipeline = match effect {
+            PostEffect::Exposure => &self.pipelines.exposure,
+            PostEffect::Tonemap => &self.pipelines.tonemap,
+            PostEffect::Fxaa => &self.pipelines.fxaa,
+            PostEffect::Vignette => &self.pipelines.vignette,
+            PostEffect::Bloom => {
+               let [glow, scratch] = &self.bloom;
+               let source = &self.targets.pull_current().bind_group;
+               self.draw(encoder, &self.pipelines.bloom_threshold, &glow.view, &[source, uniform]);
+               self.draw(encoder, &self.pipelines.blur_horizontal, &scratch.view, &[&glow.bind_group, uniform]);
+               self.draw(encoder, &self.pipelines.blur_vertical, &glow.view, &[&scratch.bind_group, uniform]);
+               &self.pipelines.bloom_composite
+            }
+         };
+
+         let bind_groups = [&self.targets.pull_current().bind_group, uniform, &self.bloom[0].bind_group];
+         let used = if *effect == PostEffect::Bloom { 3 } else { 2 };
+         self.draw(encoder, pipeline, &self.targets.pull_other().view, &bind_groups[..used]);
+         self.targets.ping_pong();
+      }
+
+      self.draw(encoder, &self.pipelines.copy, output, &[&self.targets.pull_current().bind_group, uniform]);
+   }
+
+   fn draw(&self, encoder: &mut CommandEncoder, pipeline: &RenderPipeline, target: &TextureView, bind_groups: &[&BindGroup]) {
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+         label: Some("Post Process Pass"),
+         color_attachments: &[
+            Some(wgpu::RenderPassColorAttachment {
+               view: target,
+               resolve_target: None,
+               ops: wgpu::Operations {
+                  load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                  store: wgpu::StoreOp::Store,
+               }
+            })
+         ],
+         depth_stencil_attachment: None,
+         occlusion_query_set: None,
+         timestamp_writes: None,
+      });
+
+      render_pass.set_pipeline(pipeline);
+      for (index, bind_group) in bind_groups.iter().enumerate() {
+         render_pass.set_bind_group(index as u32, bind_group, &[]);
+      }
+      render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
+      render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
+      render_pass.draw_indexed(0..self.vertex_package.num_indices, 0, 0..1);
+   }
+}
+
+
+fn create_pipeline(setup: &Setup, shader: &ShaderModule, entry_point: &str, layout: &PipelineLayout, format: TextureFormat) -> RenderPipeline {
+   setup.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some(entry_point),
+      layout: Some(layout),
+
+      vertex: wgpu::VertexState {
+         module: shader,
+         entry_point: "vs_main",
+         compilation_options: Default::default(),
+         buffers: &[
+            Vertex::desc(),
+         ],
+      },
+
+      fragment: Some(wgpu::FragmentState {
+         module: shader,
+         entry_point,
+         compilation_options: Default::default(),
+         targets: &[Some(wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+         })],
+      }),
+
+      primitive: wgpu::PrimitiveState {
+         topology: wgpu::PrimitiveTopology::TriangleList,
+         strip_index_format: None,
+         front_face: wgpu::FrontFace::Ccw,
+         cull_mode: Some(wgpu::Face::Back),
+         polygon_mode: wgpu::PolygonMode::Fill,
+         unclipped_depth: false,
+         conservative: false,
+      },
+
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState {
+         count: 1,
+         mask: !0,
+         alpha_to_coverage_enabled: false,
+      },
+
+      multiview: None,
+   })
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::compiler;
+
+   #[test]
+   fn shader_validates() {
+      if let Err(error) = compiler::validate(POST_SOURCE) {
+         panic!("{error}");
+      }
+   }
+}
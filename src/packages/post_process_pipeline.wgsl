@@ -0,0 +1,195 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>, // 0, 0 at the top left
+};
+
+const TONEMAP_ACES: u32 = 0u;
+const TONEMAP_AGX: u32 = 1u;
+const TONEMAP_REINHARD: u32 = 2u;
+
+struct Post {
+    exposure: f32, // linear multiplier
+    tonemapper: u32,
+    bloom_threshold: f32,
+    bloom_intensity: f32,
+    bloom_radius: f32, // in half resolution texels
+    vignette_strength: f32,
+    vignette_radius: f32,
+    fxaa_span: f32, // longest blur in texels
+};
+
+@group(0) @binding(0)
+var input: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+@group(1) @binding(0)
+var<uniform> post: Post;
+
+// the blurred bloom, only read by `fs_bloom_composite`
+@group(2) @binding(0)
+var bloom: texture_2d<f32>;
+@group(2) @binding(1)
+var bloom_sampler: sampler;
+
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.uv = model.position.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    return out;
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input, input_sampler, uv, 0.0).rgb;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(sample(in.uv), 1.0);
+}
+
+@fragment
+fn fs_exposure(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(sample(in.uv) * post.exposure, 1.0);
+}
+
+
+// narkowicz's fit of the aces reference curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// benjamin wrensch's minimal agx, the base look
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3<f32>(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var x = inset * max(color, vec3<f32>(1e-10));
+    x = (clamp(log2(x), vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+    x = outset * agx_contrast(x);
+    // agx ends in display encoding, the srgb target wants linear
+    return pow(max(x, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = max(sample(in.uv), vec3<f32>(0.0));
+    switch post.tonemapper {
+        case TONEMAP_AGX: { return vec4<f32>(agx(color), 1.0); }
+        case TONEMAP_REINHARD: { return vec4<f32>(color / (1.0 + color), 1.0); }
+        default: { return vec4<f32>(aces(color), 1.0); }
+    }
+}
+
+
+// lottes' original fxaa, blurs along the edge direction where the local contrast says there's an edge
+@fragment
+fn fs_fxaa(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input));
+    let uv = in.uv;
+
+    let rgb_m = sample(uv);
+    // on compressed values, so bright hdr edges don't swamp everything else
+    let luma_nw = luminance(reinhard_luma(sample(uv + vec2<f32>(-1.0, -1.0) * texel)));
+    let luma_ne = luminance(reinhard_luma(sample(uv + vec2<f32>(1.0, -1.0) * texel)));
+    let luma_sw = luminance(reinhard_luma(sample(uv + vec2<f32>(-1.0, 1.0) * texel)));
+    let luma_se = luminance(reinhard_luma(sample(uv + vec2<f32>(1.0, 1.0) * texel)));
+    let luma_m = luminance(reinhard_luma(rgb_m));
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * (1.0 / 8.0), 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-post.fxaa_span), vec2<f32>(post.fxaa_span)) * texel;
+
+    let rgb_a = 0.5 * (sample(uv + direction * (1.0 / 3.0 - 0.5)) + sample(uv + direction * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample(uv - direction * 0.5) + sample(uv + direction * 0.5));
+    let luma_b = luminance(reinhard_luma(rgb_b));
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(rgb_a, 1.0);
+    }
+    return vec4<f32>(rgb_b, 1.0);
+}
+
+fn reinhard_luma(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + luminance(max(color, vec3<f32>(0.0))));
+}
+
+
+// into a half resolution target, each pixel lands between four source texels so the linear sample averages them
+@fragment
+fn fs_bloom_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = max(sample(in.uv), vec3<f32>(0.0));
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - post.bloom_threshold, 0.0) / max(brightness, 1e-4);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+fn blur(uv: vec2<f32>, texel: vec2<f32>) -> vec4<f32> {
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = sample(uv) * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = texel * f32(i) * post.bloom_radius;
+        color += (sample(uv + offset) + sample(uv - offset)) * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0 / f32(textureDimensions(input).x), 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(in: VertexOutput) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0 / f32(textureDimensions(input).y)));
+}
+
+@fragment
+fn fs_bloom_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let glow = textureSampleLevel(bloom, bloom_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(sample(in.uv) + glow * post.bloom_intensity, 1.0);
+}
+
+
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let aspect = f32(textureDimensions(input).x) / f32(textureDimensions(input).y);
+    // 1 at the corners whatever the aspect ratio
+    let offset = (in.uv - 0.5) * vec2<f32>(aspect, 1.0) / length(vec2<f32>(aspect, 1.0) * 0.5);
+    let fade = smoothstep(post.vignette_radius, post.vignette_radius + 0.5, length(offset));
+    return vec4<f32>(sample(in.uv) * (1.0 - post.vignette_strength * fade), 1.0);
+}
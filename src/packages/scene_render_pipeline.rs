@@ -0,0 +1,526 @@
+use bytemuck::{Pod, Zeroable};
+use egui::{ComboBox, Ui};
+use serde::{Deserialize, Serialize};
+use cgmath::{InnerSpace, Vector3};
+use wgpu::{Color, CommandEncoder, IndexFormat, PipelineLayout, RenderPipeline, ShaderStages, TextureFormat, TextureView};
+use crate::inbuilt::setup::Setup;
+use crate::inbuilt::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
+use crate::inbuilt::vertex_package::{Vertex, VertexPackage};
+use crate::packages::compute_render_pipeline::ComputeRenderPipeline;
+use crate::packages::path_trace_pipeline::PathTracePipeline;
+use crate::packages::post_process_pipeline::HDR_FORMAT;
+use crate::packages::slice_pipeline::{SlicePipeline, SliceUniform};
+use crate::scene::graph::Scene;
+use crate::scene::light::{Light, Lighting, MAX_LIGHTS};
+use crate::scene::node::NodeId;
+use crate::scene::bytecode;
+use crate::scene::bytecode::{BytecodeError, Instruction};
+use crate::scene::compiler;
+use crate::scene::compiler::{CompileError, SceneParams};
+use crate::enum_egui_dropdown;
+use crate::utility::structs::{StorageBufferPackage, UniformPackageSingles};
+
+/// the full shader with the map left out, see `compiler::splice`
+pub const SHADER_TEMPLATE: &str = include_str!("scene_render_pipeline.wgsl");
+
+/// laid out to match `Light` in the shader
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct LightUniform {
+   pub position: [f32; 3],
+   pub kind: u32,
+   /// normalised
+   pub direction: [f32; 3],
+   pub range: f32,
+   pub color: [f32; 3],
+   pub intensity: f32,
+   /// cosines of the spot cone's edge and of where its fade starts
+   pub cos_outer: f32,
+   pub cos_inner: f32,
+   /// 0 when this light or the scene has shadows off
+   pub shadows: u32,
+   pub _padding: u32,
+}
+impl LightUniform {
+   pub fn new(light: &Light, lighting: &Lighting) -> Self {
+      let direction = Vector3::from(light.direction);
+      let direction = if direction.magnitude2() > 0.0 { direction.normalize() } else { -Vector3::unit_y() };
+      let outer = light.spot_angle.to_radians();
+      let cos_outer = outer.cos();
+
+      Self {
+         position: light.position,
+         kind: light.kind as u32,
+         direction: direction.into(),
+         range: light.range.max(1e-3),
+         color: light.color,
+         intensity: light.intensity,
+         cos_outer,
+         // kept apart so the shader's smoothstep never divides by zero
+         cos_inner: (outer * (1.0 - light.spot_softness)).cos().max(cos_outer + 1e-4),
+         shadows: (light.cast_shadows && lighting.shadows) as u32,
+         _padding: 0,
+      }
+   }
+}
+
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ViewUniform {
+   pub camera_position: [f32; 4],
+   pub camera_forward: [f32; 4],
+   pub camera_right: [f32; 4],
+   pub camera_up: [f32; 4],
+   pub background: [f32; 4],
+   pub resolution: [f32; 2],
+   pub max_distance: f32,
+   pub surface_epsilon: f32,
+   pub max_steps: u32,
+   /// below 1 when a warp in the scene overestimates distances
+   pub step_scale: f32,
+   pub light_count: u32,
+   pub shading_model: u32,
+   pub normal_epsilon: f32,
+   pub penumbra: f32,
+   pub shadow_steps: u32,
+   /// 0 when ambient occlusion is off
+   pub occlusion_steps: u32,
+   pub occlusion_strength: f32,
+   pub occlusion_distance: f32,
+   /// samples the path tracer has already accumulated, seeds its random numbers
+   pub sample_index: u32,
+   /// bounces each path tracer ray takes after the first hit
+   pub max_bounces: u32,
+   /// a `DebugView` as a number, 0 for the lit scene
+   pub debug_view: u32,
+   pub _padding: [u32; 3],
+   pub lights: [LightUniform; MAX_LIGHTS],
+}
+impl ViewUniform {
+   pub fn new(scene: &Scene, solo: Option<NodeId>, resolution: (u32, u32)) -> Self {
+      let camera = &scene.camera;
+      let (forward, right, up) = camera.basis();
+      let settings = &scene.settings;
+      let lighting = &scene.lighting;
+
+      let mut lights = [LightUniform::zeroed(); MAX_LIGHTS];
+      for (uniform, light) in lights.iter_mut().zip(&scene.lights) {
+         *uniform = LightUniform::new(light, lighting);
+      }
+
+      Self {
+         camera_position: [camera.position.x, camera.position.y, camera.position.z, camera.focal_scale()],
+         camera_forward: [forward.x, forward.y, forward.z, 0.0],
+         camera_right: [right.x, right.y, right.z, 0.0],
+         camera_up: [up.x, up.y, up.z, 0.0],
+         background: [settings.background[0], settings.background[1], settings.background[2], settings.ambient],
+         resolution: [resolution.0.max(1) as f32, resolution.1.max(1) as f32],
+         max_distance: settings.max_distance,
+         surface_epsilon: settings.surface_epsilon,
+         max_steps: settings.max_steps,
+         step_scale: scene.step_scale(solo),
+         light_count: scene.lights.len().min(MAX_LIGHTS) as u32,
+         shading_model: lighting.model as u32,
+         normal_epsilon: lighting.normals.normal_epsilon,
+         penumbra: lighting.soft_shadows.penumbra.max(1e-4),
+         shadow_steps: lighting.soft_shadows.shadow_steps,
+         occlusion_steps: if lighting.occlusion { lighting.ambient_occlusion.occlusion_steps } else { 0 },
+         occlusion_strength: lighting.ambient_occlusion.occlusion_strength,
+         occlusion_distance: lighting.ambient_occlusion.occlusion_distance.max(1e-4),
+         sample_index: 0,
+         max_bounces: 0,
+         debug_view: DebugView::Shaded as u32,
+         _padding: [0; 3],
+         lights,
+      }
+   }
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+   /// the scene is compiled into the shader, fastest to draw but every structural edit rebuilds the pipeline,
+   /// values live in a uniform so editing them doesn't
+   Compiled,
+   /// a fixed shader walks the flattened scene in a storage buffer, edits are just a buffer upload
+   Interpreted,
+   /// the compiled map under a progressive path tracer, see `PathTracePipeline`
+   PathTraced,
+   /// the compiled map as a compute shader working in tiles, see `ComputeRenderPipeline`
+   Compute,
+}
+
+
+// what the viewport shows instead of the lit scene, for finding out why a field is slow or wrong.
+// values match the `DEBUG_*` constants in `scene_render_pipeline.wgsl`
+enum_egui_dropdown!(DebugView, Shaded, Normals, Depth, Iterations, MissDistance, ObjectId, ClosestPrimitive);
+
+impl DebugView {
+   pub fn description(&self) -> &'static str {
+      match self {
+         DebugView::Shaded => "The lit scene",
+         DebugView::Normals => "Surface normals as colours",
+         DebugView::Depth => "Distance from the camera, brighter is closer",
+         DebugView::Iterations => "Steps the march took, red pixels are the slow ones",
+         DebugView::MissDistance => "How near missed rays ended to a surface, hot ones ran out of steps grazing something",
+         DebugView::ObjectId => "A colour per primitive that was hit",
+         DebugView::ClosestPrimitive => "A colour per primitive, misses take the one they passed nearest",
+      }
+   }
+}
+
+
+struct Interpreter {
+   render_pipeline: RenderPipeline,
+   program_package: StorageBufferPackage<Instruction>,
+   error: Option<BytecodeError>,
+}
+
+
+pub struct SceneRenderPipeline {
+   vertex_package: VertexPackage,
+   view_package: UniformPackageSingles<ViewUniform>,
+
+   render_pipeline: RenderPipeline,
+   params_package: UniformPackageSingles<SceneParams>,
+   pipeline_layout: PipelineLayout,
+   map_source: String,
+   pub last_error: Option<CompileError>,
+
+   /// built the first time interpreted mode is used
+   interpreter: Option<Interpreter>,
+   /// set if the interpreter's shader failed validation, it never changes so it isn't retried
+   interpreter_shader_error: Option<CompileError>,
+   /// built the first time path traced mode is used
+   path_tracer: Option<PathTracePipeline>,
+   /// built the first time compute mode is used
+   compute: Option<ComputeRenderPipeline>,
+   /// built the first time the slice viewer is opened
+   slice: Option<SlicePipeline>,
+   pub mode: RenderMode,
+   /// ignored when path traced
+   pub debug_view: DebugView,
+   /// the only node drawn, its ancestors still place it
+   pub solo: Option<NodeId>,
+}
+impl SceneRenderPipeline {
+   pub fn new(setup: &Setup, scene: &Scene) -> Self {
+      let vertex_package = VertexPackage::new(&setup.device, SQUARE_VERTICES, SQUARE_INDICES);
+
+      // the path tracer reads these from a compute shader, which webgl doesn't have
+      let stages = if cfg!(target_arch = "wasm32") { ShaderStages::FRAGMENT } else { ShaderStages::FRAGMENT | ShaderStages::COMPUTE };
+      let view_package = UniformPackageSingles::create(setup, stages, ViewUniform::new(scene, None, (1, 1)));
+      let params_package = UniformPackageSingles::create(setup, stages, SceneParams::zeroed());
+
+      let pipeline_layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Scene Render Pipeline Layout"),
+         bind_group_layouts: &[
+            &view_package.layout,
+            &params_package.layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      // an empty scene always compiles, so there is a pipeline to fall back to
+      let source = compiler::compile(SHADER_TEMPLATE, &Scene::new()).expect("empty scene failed to compile");
+      let render_pipeline = create_pipeline(setup, &pipeline_layout, source);
+
+      let mut pipeline = Self {
+         vertex_package,
+         view_package,
+         render_pipeline,
+         params_package,
+         pipeline_layout,
+         map_source: String::new(),
+         last_error: None,
+         interpreter: None,
+         interpreter_shader_error: None,
+         path_tracer: None,
+         compute: None,
+         slice: None,
+         mode: RenderMode::Compiled,
+         debug_view: DebugView::Shaded,
+         solo: None,
+      };
+      pipeline.rebuild_if_changed(setup, scene);
+      pipeline
+   }
+
+   /// recompiles the shader if the scene tree produces a different `map`, on failure the old pipeline is kept
+   ///
+   /// value edits only change the params, which are uploaded without touching the pipeline
+   pub fn rebuild_if_changed(&mut self, setup: &Setup, scene: &Scene) {
+      let map = match compiler::compile_map(scene, self.solo) {
+         Ok(map) => map,
+         Err(error) => {
+            if self.last_error.as_ref() != Some(&error) {
+               log::error!("{error}");
+            }
+            self.last_error = Some(error);
+            return;
+         }
+      };
+
+      if map.source != self.map_source {
+         match compiler::splice(SHADER_TEMPLATE, &map.source).and_then(|source| {
+            compiler::validate(&source)?;
+            Ok(source)
+         }) {
+            Ok(source) => {
+               self.render_pipeline = create_pipeline(setup, &self.pipeline_layout, source);
+               self.last_error = None;
+            }
+            Err(error) => {
+               log::error!("{error}");
+               self.last_error = Some(error);
+            }
+         }
+         self.map_source = map.source;
+      }
+
+      // params laid out for a map that failed to build would scramble the one still drawing
+      if self.last_error.is_none() && map.params != self.params_package.data {
+         self.params_package.data = map.params;
+         self.params_package.update_with_data(&setup.queue);
+      }
+   }
+
+   /// reflattens the scene and uploads the program if it changed, on failure the old program is kept
+   pub fn upload_program(&mut self, setup: &Setup, scene: &Scene) {
+      if self.interpreter.is_none() && self.interpreter_shader_error.is_none() {
+         match interpreter_shader() {
+            Ok(source) => self.interpreter = Some(self.create_interpreter(setup, source)),
+            Err(error) => {
+               log::error!("{error}");
+               self.interpreter_shader_error = Some(error);
+            }
+         }
+      }
+      let Some(interpreter) = &mut self.interpreter else { return };
+
+      match bytecode::flatten(scene, self.solo) {
+         Ok(program) => {
+            interpreter.error = None;
+            if program != interpreter.program_package.data {
+               interpreter.program_package.data = program;
+               interpreter.program_package.update_with_data(setup);
+            }
+         }
+         Err(error) => {
+            if interpreter.error.as_ref() != Some(&error) {
+               log::error!("{error}");
+            }
+            interpreter.error = Some(error);
+         }
+      }
+   }
+
+   fn create_interpreter(&self, setup: &Setup, source: String) -> Interpreter {
+      let program_package = StorageBufferPackage::create(setup, ShaderStages::FRAGMENT, vec![]);
+
+      let layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Scene Interpreter Pipeline Layout"),
+         bind_group_layouts: &[
+            &self.view_package.layout,
+            &program_package.layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      Interpreter {
+         render_pipeline: create_pipeline(setup, &layout, source),
+         program_package,
+         error: None,
+      }
+   }
+
+   /// error from whichever mode is active
+   pub fn error(&self) -> Option<String> {
+      match self.mode {
+         RenderMode::Compiled => self.last_error.as_ref().map(|error| error.to_string()),
+         RenderMode::PathTraced => self.last_error.as_ref()
+             .or(self.path_tracer.as_ref().and_then(|path_tracer| path_tracer.error.as_ref()))
+             .map(|error| error.to_string()),
+         RenderMode::Compute => self.last_error.as_ref()
+             .or(self.compute.as_ref().and_then(|compute| compute.error.as_ref()))
+             .map(|error| error.to_string()),
+         RenderMode::Interpreted => self.interpreter_shader_error.as_ref().map(|error| error.to_string())
+             .or(self.interpreter.as_ref().and_then(|interpreter| interpreter.error.as_ref()).map(|error| error.to_string())),
+      }
+   }
+
+   /// brings the active mode's shader or program up to date and uploads the camera, light and settings
+   pub fn update(&mut self, setup: &Setup, scene: &Scene, resolution: (u32, u32)) {
+      match self.mode {
+         RenderMode::Compiled | RenderMode::PathTraced | RenderMode::Compute => self.rebuild_if_changed(setup, scene),
+         RenderMode::Interpreted => self.upload_program(setup, scene),
+      }
+
+      self.view_package.data = ViewUniform::new(scene, self.solo, resolution);
+      if self.mode == RenderMode::PathTraced {
+         let path_tracer = self.path_tracer.get_or_insert_with(|| {
+            PathTracePipeline::new(setup, &self.view_package.layout, &self.params_package.layout)
+         });
+         path_tracer.prepare(setup, &self.map_source, &self.params_package.data, &mut self.view_package.data);
+      } else {
+         self.view_package.data.debug_view = self.debug_view as u32;
+      }
+      if self.mode == RenderMode::Compute {
+         let compute = self.compute.get_or_insert_with(|| {
+            ComputeRenderPipeline::new(setup, &self.view_package.layout, &self.params_package.layout)
+         });
+         compute.prepare(setup, &self.map_source, resolution);
+      }
+      self.view_package.update_with_data(&setup.queue);
+   }
+
+   /// brings the slice viewer's shader up to date, it always draws the compiled map
+   pub fn update_slice(&mut self, setup: &Setup, scene: &Scene, slice: SliceUniform) {
+      if self.mode == RenderMode::Interpreted {
+         self.rebuild_if_changed(setup, scene);
+      }
+      let pipeline = self.slice.get_or_insert_with(|| {
+         SlicePipeline::new(setup, &self.view_package.layout, &self.params_package.layout)
+      });
+      pipeline.prepare(setup, &self.map_source, slice);
+   }
+
+   /// draws the slice into `target`, once `update_slice` has run
+   pub fn render_slice_pass(&self, encoder: &mut CommandEncoder, target: &TextureView) {
+      if let Some(slice) = &self.slice {
+         slice.render_pass(encoder, target, &self.view_package.bind_group, &self.params_package.bind_group);
+      }
+   }
+
+   /// the path tracer, once path traced mode has been used
+   pub fn path_tracer(&mut self) -> Option<&mut PathTracePipeline> {
+      self.path_tracer.as_mut()
+   }
+
+   pub fn render_pass(
+      &mut self, encoder: &mut CommandEncoder,
+      view: &TextureView,
+   ) {
+      if let (RenderMode::PathTraced, Some(path_tracer)) = (self.mode, &mut self.path_tracer) {
+         path_tracer.render_pass(encoder, view, &self.view_package.bind_group, &self.params_package.bind_group);
+         return;
+      }
+      if let (RenderMode::Compute, Some(compute)) = (self.mode, &self.compute) {
+         compute.render_pass(encoder, view, &self.view_package.bind_group, &self.params_package.bind_group);
+         return;
+      }
+
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+         label: Some("Scene Render Pass"),
+         color_attachments: &[
+            Some(wgpu::RenderPassColorAttachment {
+               view,
+               resolve_target: None,
+               ops: wgpu::Operations {
+                  load: wgpu::LoadOp::Clear(Color::BLACK),
+                  store: wgpu::StoreOp::Store,
+               }
+            })
+         ],
+         depth_stencil_attachment: None,
+         occlusion_query_set: None,
+         timestamp_writes: None,
+      });
+
+      match (self.mode, &self.interpreter) {
+         (RenderMode::Interpreted, Some(interpreter)) => {
+            render_pass.set_pipeline(&interpreter.render_pipeline);
+            render_pass.set_bind_group(1, &interpreter.program_package.bind_group, &[]);
+         }
+         _ => {
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(1, &self.params_package.bind_group, &[]);
+         }
+      }
+      render_pass.set_bind_group(0, &self.view_package.bind_group, &[]);
+
+      render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
+      render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
+
+      render_pass.draw_indexed(0..self.vertex_package.num_indices, 0, 0..1);
+   }
+}
+
+
+/// full screen quad pipeline drawing into the post process input, `source` needs a `vs_main` and `fs_main`
+/// the scene template running the bytecode interpreter instead of a compiled `map`
+pub fn interpreter_shader() -> Result<String, CompileError> {
+   let source = compiler::splice(SHADER_TEMPLATE, &bytecode::interpreter_source())?;
+   compiler::validate(&source)?;
+   Ok(source)
+}
+
+pub(crate) fn create_pipeline(setup: &Setup, layout: &PipelineLayout, source: String) -> RenderPipeline {
+   create_pipeline_for(setup, layout, source, "fs_main", HDR_FORMAT)
+}
+
+/// full screen quad pipeline running `entry_point` into a target of `format`
+pub(crate) fn create_pipeline_for(setup: &Setup, layout: &PipelineLayout, source: String, entry_point: &str, format: TextureFormat) -> RenderPipeline {
+   let shader = setup.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("Scene Shader"),
+      source: wgpu::ShaderSource::Wgsl(source.into()),
+   });
+
+   setup.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("Scene Render Pipeline"),
+      layout: Some(layout),
+
+      vertex: wgpu::VertexState {
+         module: &shader,
+         entry_point: "vs_main",
+         compilation_options: Default::default(),
+         buffers: &[
+            Vertex::desc(),
+         ],
+      },
+
+      fragment: Some(wgpu::FragmentState {
+         module: &shader,
+         entry_point,
+         compilation_options: Default::default(),
+         targets: &[Some(wgpu::ColorTargetState {
+            format,
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+         })],
+      }),
+
+      primitive: wgpu::PrimitiveState {
+         topology: wgpu::PrimitiveTopology::TriangleList,
+         strip_index_format: None,
+         front_face: wgpu::FrontFace::Ccw,
+         cull_mode: Some(wgpu::Face::Back),
+         polygon_mode: wgpu::PolygonMode::Fill,
+         unclipped_depth: false,
+         conservative: false,
+      },
+
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState {
+         count: 1,
+         mask: !0,
+         alpha_to_coverage_enabled: false,
+      },
+
+      multiview: None,
+   })
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn interpreter_shader_validates() {
+      if let Err(error) = interpreter_shader() {
+         panic!("{error}");
+      }
+   }
+}
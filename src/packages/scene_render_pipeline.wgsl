@@ -0,0 +1,353 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+const LIGHT_DIRECTIONAL: u32 = 0u;
+const LIGHT_POINT: u32 = 1u;
+const LIGHT_SPOT: u32 = 2u;
+
+const SHADING_LAMBERT: u32 = 0u;
+const SHADING_BLINN_PHONG: u32 = 1u;
+const SHADING_PBR: u32 = 2u;
+
+const DEBUG_SHADED: u32 = 0u;
+const DEBUG_NORMALS: u32 = 1u;
+const DEBUG_DEPTH: u32 = 2u;
+const DEBUG_ITERATIONS: u32 = 3u;
+const DEBUG_MISS_DISTANCE: u32 = 4u;
+const DEBUG_OBJECT_ID: u32 = 5u;
+const DEBUG_CLOSEST_PRIMITIVE: u32 = 6u;
+
+const MAX_LIGHTS: u32 = 8u;
+const PI: f32 = 3.14159265;
+
+struct Light {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>, // normalised
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    cos_outer: f32,
+    cos_inner: f32,
+    shadows: u32,
+    _padding: u32,
+};
+
+struct View {
+    camera_position: vec4<f32>, // w = tan(fov / 2)
+    camera_forward: vec4<f32>,
+    camera_right: vec4<f32>,
+    camera_up: vec4<f32>,
+    background: vec4<f32>, // w = ambient
+    resolution: vec2<f32>,
+    max_distance: f32,
+    surface_epsilon: f32,
+    max_steps: u32,
+    step_scale: f32,
+    light_count: u32,
+    shading_model: u32,
+    normal_epsilon: f32,
+    penumbra: f32,
+    shadow_steps: u32,
+    occlusion_steps: u32, // 0 when ambient occlusion is off
+    occlusion_strength: f32,
+    occlusion_distance: f32,
+    sample_index: u32,
+    max_bounces: u32,
+    debug_view: u32,
+    lights: array<Light, MAX_LIGHTS>,
+};
+
+@group(0) @binding(0)
+var<uniform> view: View;
+
+
+//#MAP
+
+fn calc_normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(1.0, -1.0) * view.normal_epsilon;
+    return normalize(
+        e.xyy * map(p + e.xyy).dist +
+        e.yyx * map(p + e.yyx).dist +
+        e.yxy * map(p + e.yxy).dist +
+        e.xxx * map(p + e.xxx).dist
+    );
+}
+
+struct Hit {
+    surface: Surface,
+    t: f32,
+    hit: bool,
+};
+
+fn march(ro: vec3<f32>, rd: vec3<f32>) -> Hit {
+    return march_from(ro, rd, 0.0);
+}
+
+// starting `start` along the ray, for callers that already know nothing is nearer
+fn march_from(ro: vec3<f32>, rd: vec3<f32>, start: f32) -> Hit {
+    var t = start;
+    for (var i = 0u; i < view.max_steps; i++) {
+        let s = map(ro + rd * t);
+        if abs(s.dist) < view.surface_epsilon {
+            return Hit(s, t, true);
+        }
+        t += s.dist * view.step_scale;
+        if t > view.max_distance {
+            break;
+        }
+    }
+    return Hit(empty_surface(), t, false);
+}
+
+// how much light gets from `ro` along `rd` past the scene, softened by how closely the ray grazes things
+fn soft_shadow(ro: vec3<f32>, rd: vec3<f32>, max_t: f32) -> f32 {
+    var light = 1.0;
+    var t = view.surface_epsilon * 2.0;
+    for (var i = 0u; i < view.shadow_steps && t < max_t; i++) {
+        let h = map(ro + rd * t).dist;
+        if h < view.surface_epsilon {
+            return 0.0;
+        }
+        light = min(light, h / (view.penumbra * t));
+        t += max(h * view.step_scale, view.surface_epsilon);
+    }
+    let x = clamp(light, 0.0, 1.0);
+    return x * x * (3.0 - 2.0 * x);
+}
+
+// 1 in the open, darker where the field says there's geometry close above the surface
+fn ambient_occlusion(p: vec3<f32>, n: vec3<f32>) -> f32 {
+    var occluded = 0.0;
+    var weight = 1.0;
+    var total = 0.0;
+    for (var i = 1u; i <= view.occlusion_steps; i++) {
+        let h = view.occlusion_distance * f32(i) / f32(view.occlusion_steps);
+        occluded += weight * max(h - map(p + n * h).dist, 0.0) / h;
+        total += weight;
+        weight *= 0.75;
+    }
+    if total == 0.0 {
+        return 1.0;
+    }
+    return clamp(1.0 - view.occlusion_strength * occluded / total, 0.0, 1.0);
+}
+
+// falls off with the square of distance and reaches exactly zero at the light's range
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let x = distance / range;
+    let window = clamp(1.0 - x * x * x * x, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+// light reflected towards `v` per unit of incoming light, with cosine weighting left to the caller.
+// scaled by pi so a white lambert surface lit head on returns exactly the light's colour
+fn brdf(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, albedo: vec3<f32>, metallic: f32, roughness: f32) -> vec3<f32> {
+    let h = normalize(l + v);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+    switch view.shading_model {
+        case SHADING_BLINN_PHONG: {
+            let shininess = min(2.0 / max(pow(roughness, 4.0), 1e-6) - 2.0, 8192.0);
+            let highlight = pow(max(dot(n, h), 0.0), shininess) * (shininess + 8.0) / 8.0;
+            return albedo * (1.0 - metallic) + f0 * highlight;
+        }
+        case SHADING_PBR: {
+            let n_dot_v = max(dot(n, v), 1e-4);
+            let n_dot_l = max(dot(n, l), 1e-4);
+            let n_dot_h = max(dot(n, h), 0.0);
+            let v_dot_h = max(dot(v, h), 0.0);
+
+            let a = roughness * roughness;
+            let a2 = a * a;
+            let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            let distribution = a2 / (PI * d * d);
+
+            let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+            let geometry = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+            let fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+            let specular = fresnel * distribution * geometry / (4.0 * n_dot_v * n_dot_l);
+            return (1.0 - fresnel) * (1.0 - metallic) * albedo + specular * PI;
+        }
+        default: {
+            return albedo;
+        }
+    }
+}
+
+struct LightSample {
+    direction: vec3<f32>, // towards the light
+    radiance: vec3<f32>,
+    distance: f32, // how far shadow rays need to go
+};
+
+fn sample_light(light: Light, p: vec3<f32>) -> LightSample {
+    if light.kind == LIGHT_DIRECTIONAL {
+        return LightSample(-light.direction, light.color * light.intensity, view.max_distance);
+    }
+
+    let to_light = light.position - p;
+    let distance = length(to_light);
+    let l = to_light / distance;
+    var radiance = light.color * light.intensity * attenuation(distance, light.range);
+    if light.kind == LIGHT_SPOT {
+        radiance *= smoothstep(light.cos_outer, light.cos_inner, dot(-l, light.direction));
+    }
+    return LightSample(l, radiance, distance);
+}
+
+fn shade(ro: vec3<f32>, rd: vec3<f32>) -> vec3<f32> {
+    return shade_hit(ro, rd, march(ro, rd));
+}
+
+fn shade_hit(ro: vec3<f32>, rd: vec3<f32>, hit: Hit) -> vec3<f32> {
+    if !hit.hit {
+        return view.background.rgb;
+    }
+
+    let p = ro + rd * hit.t;
+    let n = calc_normal(p);
+    let v = -rd;
+    let albedo = hit.surface.color;
+    let metallic = clamp(hit.surface.material.x, 0.0, 1.0);
+    let roughness = clamp(hit.surface.material.y, 0.02, 1.0);
+    let shadow_origin = p + n * (view.surface_epsilon * 4.0);
+
+    var color = albedo * view.background.w * ambient_occlusion(p, n);
+    for (var i = 0u; i < view.light_count; i++) {
+        let light = view.lights[i];
+        let sample = sample_light(light, p);
+        let n_dot_l = dot(n, sample.direction);
+        if n_dot_l <= 0.0 || max(sample.radiance.r, max(sample.radiance.g, sample.radiance.b)) <= 0.0 {
+            continue;
+        }
+        var shadow = 1.0;
+        if light.shadows != 0u {
+            shadow = soft_shadow(shadow_origin, sample.direction, sample.distance);
+        }
+        color += brdf(n, v, sample.direction, albedo, metallic, roughness) * sample.radiance * (n_dot_l * shadow);
+    }
+    return color;
+}
+
+// blue through green to red as `x` goes from 0 to 1
+fn heatmap(x: f32) -> vec3<f32> {
+    let t = clamp(x, 0.0, 1.0);
+    return clamp(vec3<f32>(1.5 - abs(4.0 * t - 3.0), 1.5 - abs(4.0 * t - 2.0), 1.5 - abs(4.0 * t - 1.0)), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// a stable, well spread colour per node, black for nothing
+fn id_color(id: u32) -> vec3<f32> {
+    if id == 0xffffffffu {
+        return vec3<f32>(0.0);
+    }
+    var h = id * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    h = (h >> 22u) ^ h;
+    let hue = f32(h & 0xffffu) / 65535.0;
+    return 0.25 + 0.75 * clamp(abs(fract(hue + vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// the march again, keeping what the debug views need instead of lighting the result
+fn debug_shade(ro: vec3<f32>, rd: vec3<f32>) -> vec3<f32> {
+    var t = 0.0;
+    var steps = 0u;
+    var hit = false;
+    var s = empty_surface();
+    // where the ray came nearest to a surface
+    var closest = view.max_distance;
+    var closest_surface = empty_surface();
+    for (; steps < view.max_steps; steps++) {
+        s = map(ro + rd * t);
+        if abs(s.dist) < closest {
+            closest = abs(s.dist);
+            closest_surface = s;
+        }
+        if abs(s.dist) < view.surface_epsilon {
+            hit = true;
+            break;
+        }
+        t += s.dist * view.step_scale;
+        if t > view.max_distance {
+            break;
+        }
+    }
+
+    var n = -rd;
+    if hit {
+        n = calc_normal(ro + rd * t);
+    }
+    // simple facing ratio shading so shapes read in the flat colour views
+    let facing = 0.35 + 0.65 * max(dot(n, -rd), 0.0);
+    switch view.debug_view {
+        case DEBUG_NORMALS: {
+            return select(vec3<f32>(0.0), n * 0.5 + 0.5, hit);
+        }
+        case DEBUG_DEPTH: {
+            return select(vec3<f32>(0.0), vec3<f32>(1.0 - sqrt(t / view.max_distance)), hit);
+        }
+        case DEBUG_ITERATIONS: {
+            return heatmap(f32(steps) / f32(max(view.max_steps, 1u)));
+        }
+        case DEBUG_MISS_DISTANCE: {
+            // misses that stopped near something show hot, usually rays that ran out of steps grazing a surface
+            if hit {
+                return vec3<f32>(0.1) * facing;
+            }
+            let near = log2(max(abs(s.dist), view.surface_epsilon) / view.surface_epsilon) / log2(1.0 / view.surface_epsilon);
+            return heatmap(1.0 - near);
+        }
+        case DEBUG_OBJECT_ID: {
+            return select(vec3<f32>(0.0), id_color(s.id) * facing, hit);
+        }
+        case DEBUG_CLOSEST_PRIMITIVE: {
+            // misses take the node they passed nearest, faded by how far they stayed from it
+            let fade = select(exp(-closest * 4.0) * 0.6, facing, hit);
+            return id_color(select(closest_surface.id, s.id, hit)) * fade;
+        }
+        default: {
+            return shade(ro, rd);
+        }
+    }
+}
+
+// through `pixel`, measured in pixels from the top left corner
+fn camera_ray(pixel: vec2<f32>) -> vec3<f32> {
+    let ndc = vec2<f32>(
+        pixel.x / view.resolution.x * 2.0 - 1.0,
+        1.0 - pixel.y / view.resolution.y * 2.0,
+    );
+    let aspect = view.resolution.x / view.resolution.y;
+    let s = view.camera_position.w;
+
+    return normalize(
+        view.camera_forward.xyz
+        + view.camera_right.xyz * (ndc.x * s * aspect)
+        + view.camera_up.xyz * (ndc.y * s)
+    );
+}
+
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let rd = camera_ray(in.clip_position.xy);
+    if view.debug_view != DEBUG_SHADED {
+        return vec4<f32>(debug_shade(view.camera_position.xyz, rd), 1.0);
+    }
+    return vec4<f32>(shade(view.camera_position.xyz, rd), 1.0);
+}
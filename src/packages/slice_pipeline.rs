@@ -0,0 +1,149 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, IndexFormat, PipelineLayout, RenderPipeline, ShaderStages, TextureView};
+use crate::inbuilt::setup::Setup;
+use crate::inbuilt::vertex_library::{SQUARE_INDICES, SQUARE_VERTICES};
+use crate::inbuilt::vertex_package::VertexPackage;
+use crate::packages::scene_render_pipeline::{self, SHADER_TEMPLATE};
+use crate::scene::compiler;
+use crate::scene::compiler::CompileError;
+use crate::utility::structs::{EguiTexturePackage, UniformPackageSingles};
+
+/// appended to the spliced scene shader, it only needs the scene's `map`
+pub const SLICE_SOURCE: &str = include_str!("slice_pipeline.wgsl");
+
+
+/// laid out to match `Slice` in `slice_pipeline.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, PartialEq)]
+pub struct SliceUniform {
+   /// the point at the centre of the image, w = world units per pixel
+   pub origin: [f32; 4],
+   /// world direction of the image's right, w = distance between contour lines, 0 for none
+   pub right: [f32; 4],
+   /// world direction of the image's up, w = 1 to highlight where the distance is zero
+   pub up: [f32; 4],
+   pub resolution: [f32; 2],
+   /// distance at which the colours reach full strength
+   pub range: f32,
+   pub _padding: f32,
+}
+
+
+/// a planar cut through the distance field, drawn with the compiled map into its own egui texture
+pub struct SlicePipeline {
+   layout: PipelineLayout,
+   /// none until a scene's shader builds
+   render_pipeline: Option<RenderPipeline>,
+   map_source: String,
+   pub error: Option<CompileError>,
+
+   vertex_package: VertexPackage,
+   slice_package: UniformPackageSingles<SliceUniform>,
+}
+impl SlicePipeline {
+   pub fn new(setup: &Setup, view_layout: &BindGroupLayout, params_layout: &BindGroupLayout) -> Self {
+      let slice_package = UniformPackageSingles::create(setup, ShaderStages::FRAGMENT, SliceUniform::zeroed());
+
+      let layout = setup.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+         label: Some("Slice Pipeline Layout"),
+         bind_group_layouts: &[
+            view_layout,
+            params_layout,
+            &slice_package.layout,
+         ],
+         push_constant_ranges: &[],
+      });
+
+      Self {
+         layout,
+         render_pipeline: None,
+         map_source: String::new(),
+         error: None,
+         vertex_package: VertexPackage::new(&setup.device, SQUARE_VERTICES, SQUARE_INDICES),
+         slice_package,
+      }
+   }
+
+   /// rebuilds for a new map and uploads where the slice is
+   pub fn prepare(&mut self, setup: &Setup, map_source: &str, slice: SliceUniform) {
+      if map_source != self.map_source {
+         self.rebuild(setup, map_source);
+      }
+
+      if slice != self.slice_package.data {
+         self.slice_package.data = slice;
+         self.slice_package.update_with_data(&setup.queue);
+      }
+   }
+
+   /// on failure the old pipeline keeps drawing, though its params no longer line up
+   fn rebuild(&mut self, setup: &Setup, map_source: &str) {
+      self.map_source = map_source.to_string();
+      let source = compiler::splice(SHADER_TEMPLATE, map_source).and_then(|source| {
+         let source = source + SLICE_SOURCE;
+         compiler::validate(&source)?;
+         Ok(source)
+      });
+
+      match source {
+         Ok(source) => {
+            self.render_pipeline = Some(scene_render_pipeline::create_pipeline_for(
+               setup, &self.layout, source, "fs_slice", EguiTexturePackage::FORMAT,
+            ));
+            self.error = None;
+         }
+         Err(error) => {
+            log::error!("{error}");
+            self.error = Some(error);
+         }
+      }
+   }
+
+   pub fn render_pass(
+      &self, encoder: &mut CommandEncoder,
+      target: &TextureView,
+      view_bind_group: &BindGroup,
+      params_bind_group: &BindGroup,
+   ) {
+      let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+         label: Some("Slice Pass"),
+         color_attachments: &[
+            Some(wgpu::RenderPassColorAttachment {
+               view: target,
+               resolve_target: None,
+               ops: wgpu::Operations {
+                  load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                  store: wgpu::StoreOp::Store,
+               }
+            })
+         ],
+         depth_stencil_attachment: None,
+         occlusion_query_set: None,
+         timestamp_writes: None,
+      });
+
+      let Some(render_pipeline) = &self.render_pipeline else { return };
+      render_pass.set_pipeline(render_pipeline);
+      render_pass.set_bind_group(0, view_bind_group, &[]);
+      render_pass.set_bind_group(1, params_bind_group, &[]);
+      render_pass.set_bind_group(2, &self.slice_package.bind_group, &[]);
+      render_pass.set_vertex_buffer(0, self.vertex_package.vertex_buffer.slice(..));
+      render_pass.set_index_buffer(self.vertex_package.index_buffer.slice(..), IndexFormat::Uint16);
+      render_pass.draw_indexed(0..self.vertex_package.num_indices, 0, 0..1);
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::graph::Scene;
+
+   #[test]
+   fn shader_validates_with_the_demo_map() {
+      let source = compiler::compile(SHADER_TEMPLATE, &Scene::demo()).unwrap() + SLICE_SOURCE;
+      if let Err(error) = compiler::validate(&source) {
+         panic!("{error}");
+      }
+   }
+}
@@ -0,0 +1,39 @@
+// appended to the spliced scene shader by `SlicePipeline`, so `map` comes from there
+
+struct Slice {
+    origin: vec4<f32>, // the point at the centre of the image, w = world units per pixel
+    right: vec4<f32>, // w = distance between contour lines, 0 for none
+    up: vec4<f32>, // w = 1 to highlight where the distance is zero
+    resolution: vec2<f32>,
+    range: f32, // distance at which the colours reach full strength
+    _padding: f32,
+};
+
+@group(2) @binding(0)
+var<uniform> slice: Slice;
+
+const OUTSIDE: vec3<f32> = vec3<f32>(0.9, 0.45, 0.12);
+const INSIDE: vec3<f32> = vec3<f32>(0.12, 0.4, 0.85);
+
+@fragment
+fn fs_slice(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = in.clip_position.xy - slice.resolution * 0.5;
+    let p = slice.origin.xyz + (slice.right.xyz * pixel.x - slice.up.xyz * pixel.y) * slice.origin.w;
+    let d = map(p).dist;
+    // how much the distance changes across a pixel, the contours stay one pixel wide wherever they are.
+    // on a true distance field the lines are evenly spaced, on a bound they bunch up or spread out
+    let width = max(fwidth(d), 1e-6);
+
+    var color = select(OUTSIDE, INSIDE, d < 0.0);
+    color *= 0.15 + 0.85 * (1.0 - exp(-3.0 * abs(d) / slice.range));
+
+    let spacing = slice.right.w;
+    if spacing > 0.0 {
+        let from_line = abs(fract(d / spacing + 0.5) - 0.5) * spacing;
+        color = mix(color, color * 0.35, 1.0 - smoothstep(0.5 * width, 1.5 * width, from_line));
+    }
+    if slice.up.w > 0.0 {
+        color = mix(color, vec3<f32>(1.0), 1.0 - smoothstep(width, 2.0 * width, abs(d)));
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -5,7 +5,7 @@ use wgpu::Extent3d;
 use crate::utility::structs::EguiTexturePackage;
 
 pub fn splits(context: &Context, texture_package: &mut EguiTexturePackage) {
-   catppuccin_egui::set_theme(&context, catppuccin_egui::FRAPPE);
+   catppuccin_egui::set_theme(context, catppuccin_egui::FRAPPE);
 
 
    SidePanel::new(Side::Left, "left")
@@ -85,7 +85,7 @@ impl TestRenderPipeline {
          color_attachments: &[
             // This is what @location(0) in the fragment shader targets
             Some(wgpu::RenderPassColorAttachment {
-               view: &view,
+               view,
                resolve_target: None,
                ops: wgpu::Operations {
                   load: wgpu::LoadOp::Clear(Color {
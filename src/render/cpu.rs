@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::f32::consts::PI;
+use cgmath::{ElementWise, InnerSpace, Vector3};
+use parking_lot::Mutex;
+use crate::meshing::mesh::Progress;
+use crate::render::image::Image;
+use crate::scene::eval;
+use crate::packages::scene_render_pipeline::ViewUniform;
+use crate::scene::graph::Scene;
+use crate::scene::light::{LightKind, ShadingModel};
+use crate::scene::picking;
+use crate::utility::functions::linear_to_srgb8;
+
+/// square tiles handed to the render threads, small enough to balance and big enough to not contend
+pub const TILE_SIZE: u32 = 32;
+
+
+/// the uniform the shader would get for this image, with the shader's lighting ported over
+pub struct View {
+   uniform: ViewUniform,
+   width: u32,
+   height: u32,
+}
+impl View {
+   pub fn new(scene: &Scene, width: u32, height: u32) -> Self {
+      let (width, height) = (width.max(1), height.max(1));
+      Self { uniform: ViewUniform::new(scene, None, (width, height)), width, height }
+   }
+
+   /// direction through the middle of a pixel, the same as `fs_main`
+   pub fn ray(&self, x: u32, y: u32) -> Vector3<f32> {
+      let ndc_x = (x as f32 + 0.5) / self.width as f32 * 2.0 - 1.0;
+      let ndc_y = 1.0 - (y as f32 + 0.5) / self.height as f32 * 2.0;
+      let aspect = self.width as f32 / self.height as f32;
+      let s = self.uniform.camera_position[3];
+      let [forward, right, up] = [self.uniform.camera_forward, self.uniform.camera_right, self.uniform.camera_up]
+          .map(|v| Vector3::new(v[0], v[1], v[2]));
+      (forward + right * (ndc_x * s * aspect) + up * (ndc_y * s)).normalize()
+   }
+
+   pub fn origin(&self) -> Vector3<f32> {
+      let p = self.uniform.camera_position;
+      Vector3::new(p[0], p[1], p[2])
+   }
+
+   /// linear colour along a ray, the same as the shader's `shade`
+   pub fn shade(&self, scene: &Scene, direction: Vector3<f32>) -> [f32; 3] {
+      let view = &self.uniform;
+      let background = Vector3::new(view.background[0], view.background[1], view.background[2]);
+      let origin = self.origin();
+      let Some((t, surface)) = picking::march(scene, origin, direction) else {
+         return background.into();
+      };
+
+      let p = origin + direction * t;
+      let n = eval::gradient_with_epsilon(|p| eval::eval(scene, p), p, view.normal_epsilon).normalize();
+      let v = -direction;
+      let albedo = Vector3::from(surface.color);
+      let metallic = surface.material[0].clamp(0.0, 1.0);
+      let roughness = surface.material[1].clamp(0.02, 1.0);
+      let shadow_origin = p + n * (view.surface_epsilon * 4.0);
+
+      let mut color = albedo * (view.background[3] * self.ambient_occlusion(scene, p, n));
+      for light in &view.lights[..view.light_count as usize] {
+         let mut l = -Vector3::from(light.direction);
+         let mut radiance = Vector3::from(light.color) * light.intensity;
+         let mut max_t = view.max_distance;
+         if light.kind != LightKind::Directional as u32 {
+            let to_light = Vector3::from(light.position) - p;
+            max_t = to_light.magnitude();
+            l = to_light / max_t;
+            radiance *= attenuation(max_t, light.range);
+            if light.kind == LightKind::Spot as u32 {
+               radiance *= smoothstep(light.cos_outer, light.cos_inner, (-l).dot(Vector3::from(light.direction)));
+            }
+         }
+
+         let n_dot_l = n.dot(l);
+         if n_dot_l <= 0.0 || radiance.x.max(radiance.y).max(radiance.z) <= 0.0 {
+            continue;
+         }
+         let shadow = if light.shadows != 0 { self.soft_shadow(scene, shadow_origin, l, max_t) } else { 1.0 };
+         color += self.brdf(n, v, l, albedo, metallic, roughness).mul_element_wise(radiance) * (n_dot_l * shadow);
+      }
+      color.into()
+   }
+
+   fn soft_shadow(&self, scene: &Scene, origin: Vector3<f32>, direction: Vector3<f32>, max_t: f32) -> f32 {
+      let view = &self.uniform;
+      let mut light = 1.0f32;
+      let mut t = view.surface_epsilon * 2.0;
+      let mut i = 0;
+      while i < view.shadow_steps && t < max_t {
+         let h = eval::eval(scene, origin + direction * t);
+         if h < view.surface_epsilon {
+            return 0.0;
+         }
+         light = light.min(h / (view.penumbra * t));
+         t += (h * view.step_scale).max(view.surface_epsilon);
+         i += 1;
+      }
+      let x = light.clamp(0.0, 1.0);
+      x * x * (3.0 - 2.0 * x)
+   }
+
+   fn ambient_occlusion(&self, scene: &Scene, p: Vector3<f32>, n: Vector3<f32>) -> f32 {
+      let view = &self.uniform;
+      let mut occluded = 0.0;
+      let mut weight = 1.0;
+      let mut total = 0.0;
+      for i in 1..=view.occlusion_steps {
+         let h = view.occlusion_distance * i as f32 / view.occlusion_steps as f32;
+         occluded += weight * (h - eval::eval(scene, p + n * h)).max(0.0) / h;
+         total += weight;
+         weight *= 0.75;
+      }
+      if total == 0.0 {
+         return 1.0;
+      }
+      (1.0 - view.occlusion_strength * occluded / total).clamp(0.0, 1.0)
+   }
+
+   fn brdf(&self, n: Vector3<f32>, v: Vector3<f32>, l: Vector3<f32>, albedo: Vector3<f32>, metallic: f32, roughness: f32) -> Vector3<f32> {
+      let h = (l + v).normalize();
+      let f0 = mix3(Vector3::new(0.04, 0.04, 0.04), albedo, metallic);
+
+      match self.uniform.shading_model {
+         model if model == ShadingModel::BlinnPhong as u32 => {
+            let shininess = (2.0 / roughness.powf(4.0).max(1e-6) - 2.0).min(8192.0);
+            let highlight = n.dot(h).max(0.0).powf(shininess) * (shininess + 8.0) / 8.0;
+            albedo * (1.0 - metallic) + f0 * highlight
+         }
+         model if model == ShadingModel::Pbr as u32 => {
+            let n_dot_v = n.dot(v).max(1e-4);
+            let n_dot_l = n.dot(l).max(1e-4);
+            let n_dot_h = n.dot(h).max(0.0);
+            let v_dot_h = v.dot(h).max(0.0);
+
+            let a = roughness * roughness;
+            let a2 = a * a;
+            let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            let distribution = a2 / (PI * d * d);
+
+            let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+            let geometry = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+            let fresnel = f0 + (Vector3::new(1.0, 1.0, 1.0) - f0) * (1.0 - v_dot_h).powf(5.0);
+            let specular = fresnel * (distribution * geometry / (4.0 * n_dot_v * n_dot_l));
+            (Vector3::new(1.0, 1.0, 1.0) - fresnel).mul_element_wise(albedo) * (1.0 - metallic) + specular * PI
+         }
+         _ => albedo,
+      }
+   }
+
+   pub fn pixel(&self, scene: &Scene, x: u32, y: u32) -> [u8; 3] {
+      self.shade(scene, self.ray(x, y)).map(linear_to_srgb8)
+   }
+}
+
+fn attenuation(distance: f32, range: f32) -> f32 {
+   let x = distance / range;
+   let window = (1.0 - x * x * x * x).clamp(0.0, 1.0);
+   window * window / (distance * distance + 1.0)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+   let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+   t * t * (3.0 - 2.0 * t)
+}
+
+fn mix3(a: Vector3<f32>, b: Vector3<f32>, t: f32) -> Vector3<f32> {
+   a + (b - a) * t
+}
+
+
+/// the scene from its camera as the compiled shader would draw it, tiles spread across every core
+///
+/// every pixel is worked out on its own, so the image is the same whichever threads ran which tiles.
+/// none if cancelled part way
+pub fn render(scene: &Scene, width: u32, height: u32, progress: &Progress) -> Option<Image> {
+   let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+   render_with_threads(scene, width, height, threads, progress)
+}
+
+/// `render` on a fixed number of threads
+pub fn render_with_threads(scene: &Scene, width: u32, height: u32, threads: usize, progress: &Progress) -> Option<Image> {
+   let view = View::new(scene, width, height);
+   let mut image = Image::new(width, height);
+   let columns = width.div_ceil(TILE_SIZE);
+   let tiles = (columns * height.div_ceil(TILE_SIZE)) as usize;
+
+   progress.start("Rendering", tiles);
+   let next = AtomicUsize::new(0);
+   let output = Mutex::new(&mut image);
+   thread::scope(|scope| {
+      for _ in 0..threads.clamp(1, tiles.max(1)) {
+         scope.spawn(|| loop {
+            let tile = next.fetch_add(1, Ordering::Relaxed);
+            if tile >= tiles || progress.is_cancelled() {
+               break;
+            }
+            let (left, top) = ((tile as u32 % columns) * TILE_SIZE, (tile as u32 / columns) * TILE_SIZE);
+            let (right, bottom) = ((left + TILE_SIZE).min(width), (top + TILE_SIZE).min(height));
+
+            let pixels: Vec<[u8; 3]> = (top..bottom)
+                .flat_map(|y| (left..right).map(move |x| (x, y)))
+                .map(|(x, y)| view.pixel(scene, x, y))
+                .collect();
+
+            let mut image = output.lock();
+            let row = (right - left) as usize;
+            for (y, pixels) in (top..bottom).zip(pixels.chunks_exact(row)) {
+               let start = (left + y * width) as usize;
+               image.pixels[start..start + row].copy_from_slice(pixels);
+            }
+            progress.advance(1);
+         });
+      }
+   });
+
+   (!progress.is_cancelled()).then_some(image)
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use std::path::PathBuf;
+
+   const WIDTH: u32 = 48;
+   const HEIGHT: u32 = 32;
+
+   fn reference_path() -> PathBuf {
+      PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/render/reference/demo_48x32.png")
+   }
+
+   #[test]
+   fn same_image_on_any_number_of_threads() {
+      let scene = Scene::demo();
+      let single = render_with_threads(&scene, WIDTH, HEIGHT, 1, &Progress::default()).unwrap();
+      for threads in [2, 3, 8] {
+         let image = render_with_threads(&scene, WIDTH, HEIGHT, threads, &Progress::default()).unwrap();
+         assert!(image == single, "{threads} threads drew a different image");
+      }
+   }
+
+   #[test]
+   fn demo_matches_the_reference_image() {
+      let image = render_with_threads(&Scene::demo(), WIDTH, HEIGHT, 2, &Progress::default()).unwrap();
+      let reference = Image::load_png(&reference_path()).unwrap();
+      let difference = reference.difference(&image).expect("reference is a different size");
+      assert_eq!(difference.count_above(2), 0, "max difference {}", difference.max);
+   }
+}
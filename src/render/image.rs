@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// 8 bit srgb pixels, row by row from the top left, what the renderers write and pngs hold
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+   pub width: u32,
+   pub height: u32,
+   pub pixels: Vec<[u8; 3]>,
+}
+impl Image {
+   pub fn new(width: u32, height: u32) -> Self {
+      Self { width, height, pixels: vec![[0; 3]; (width * height) as usize] }
+   }
+
+   pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+      self.pixels[(x + y * self.width) as usize]
+   }
+
+   pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 3]) {
+      self.pixels[(x + y * self.width) as usize] = pixel;
+   }
+
+   pub fn save_png(&self, path: &Path) -> Result<(), png::EncodingError> {
+      let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+      encoder.set_color(png::ColorType::Rgb);
+      encoder.set_depth(png::BitDepth::Eight);
+      encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+      let mut writer = encoder.write_header()?;
+      writer.write_image_data(&self.pixels.concat())?;
+      writer.finish()
+   }
+
+   /// reads any 8 or 16 bit png, alpha is dropped and grey is spread to rgb
+   pub fn load_png(path: &Path) -> Result<Self, png::DecodingError> {
+      let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+      decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+      let mut reader = decoder.read_info()?;
+      let mut buffer = vec![0; reader.output_buffer_size()];
+      let frame = reader.next_frame(&mut buffer)?;
+      let bytes = &buffer[..frame.buffer_size()];
+
+      let channels = frame.color_type.samples();
+      let pixels = bytes.chunks_exact(channels)
+          .map(|pixel| match pixel {
+             [grey] | [grey, _] => [*grey; 3],
+             [r, g, b, ..] => [*r, *g, *b],
+             _ => unreachable!("pngs have one to four channels"),
+          })
+          .collect();
+      Ok(Self { width: frame.width, height: frame.height, pixels })
+   }
+
+   /// per pixel comparison, none if the sizes don't match
+   pub fn difference(&self, other: &Image) -> Option<ImageDifference> {
+      if (self.width, self.height) != (other.width, other.height) {
+         return None;
+      }
+
+      let mut image = Image::new(self.width, self.height);
+      let mut max = 0;
+      let mut total = 0u64;
+      for ((a, b), out) in self.pixels.iter().zip(&other.pixels).zip(&mut image.pixels) {
+         let channel = (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0);
+         max = max.max(channel);
+         total += channel as u64;
+         *out = [channel; 3];
+      }
+
+      Some(ImageDifference {
+         max,
+         mean: total as f32 / self.pixels.len().max(1) as f32,
+         image,
+      })
+   }
+}
+
+
+pub struct ImageDifference {
+   /// largest difference of any channel of any pixel, 0 to 255
+   pub max: u8,
+   /// of the largest channel difference per pixel
+   pub mean: f32,
+   /// the largest channel difference of every pixel as grey
+   pub image: Image,
+}
+impl ImageDifference {
+   /// pixels differing by more than `tolerance` in any channel
+   pub fn count_above(&self, tolerance: u8) -> usize {
+      self.image.pixels.iter().filter(|pixel| pixel[0] > tolerance).count()
+   }
+}
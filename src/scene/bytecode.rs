@@ -0,0 +1,371 @@
+use std::fmt::{Display, Formatter, Write};
+use bytemuck::{Pod, Zeroable};
+use cgmath::{Matrix3, Vector3};
+use crate::scene::domain::DomainOp;
+use crate::scene::graph::Scene;
+use crate::scene::node::{NodeId, NodeKind};
+use crate::scene::operators::{Blend, BlendParams, BooleanOp, Operation};
+use crate::scene::primitives::Primitive;
+use crate::scene::sdf;
+use crate::scene::sdf::Surface;
+
+/// stack sizes baked into `interpreter.wgsl`
+pub const MAX_SURFACE_STACK: usize = 32;
+pub const MAX_POINT_STACK: usize = 16;
+
+const INTERPRETER_SOURCE: &str = include_str!("interpreter.wgsl");
+const PRIMITIVES_MARKER: &str = "//#PRIMITIVES";
+
+/// most floats a primitive can pack into an instruction
+pub const MAX_PRIMITIVE_PARAMS: usize = 12;
+
+pub mod opcode {
+   pub const EMPTY: u32 = 0;
+   /// data[0..3] = inverse rotation columns, data[3].xyz = position, data[3].w = scale
+   pub const PUSH_TRANSFORM: u32 = 1;
+   /// data[0].x = scale
+   pub const POP_TRANSFORM: u32 = 2;
+   /// pushes the point and warps it, data[0].x = domain kind, data[1..3] = params
+   pub const PUSH_DOMAIN: u32 = 3;
+   /// restores the point and adjusts the distance on top of the stack, same data as the push
+   pub const POP_DOMAIN: u32 = 4;
+
+   /// booleans carry data[0] = (blend, radius, steps)
+   pub const UNION: u32 = 10;
+   pub const SUBTRACTION: u32 = 11;
+   pub const INTERSECTION: u32 = 12;
+
+   // primitives use `Primitive::opcode`, params are packed into data[0..3], the colour sits in data[3].xyz and the rest of the material in `material`
+
+   /// (name, value) pairs written into the wgsl interpreter as constants
+   pub const ALL: &[(&str, u32)] = &[
+      ("OP_EMPTY", EMPTY),
+      ("OP_PUSH_TRANSFORM", PUSH_TRANSFORM),
+      ("OP_POP_TRANSFORM", POP_TRANSFORM),
+      ("OP_PUSH_DOMAIN", PUSH_DOMAIN),
+      ("OP_POP_DOMAIN", POP_DOMAIN),
+      ("OP_UNION", UNION),
+      ("OP_SUBTRACTION", SUBTRACTION),
+      ("OP_INTERSECTION", INTERSECTION),
+   ];
+}
+
+
+/// one step of the stack machine, laid out to match `Instruction` in `interpreter.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, PartialEq)]
+pub struct Instruction {
+   pub opcode: u32,
+   /// node id, written into the surface by primitives
+   pub id: u32,
+   /// metallic and roughness, only read by primitives
+   pub material: [f32; 2],
+   pub data: [[f32; 4]; 4],
+}
+impl Instruction {
+   pub fn new(opcode: u32, id: u32) -> Self {
+      Self {
+         opcode,
+         id,
+         material: [0.0; 2],
+         data: [[0.0; 4]; 4],
+      }
+   }
+
+   fn with_data(mut self, index: usize, data: [f32; 4]) -> Self {
+      self.data[index] = data;
+      self
+   }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BytecodeError {
+   SurfaceStackOverflow { needed: usize },
+   PointStackOverflow { needed: usize },
+}
+impl Display for BytecodeError {
+   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+      match self {
+         BytecodeError::SurfaceStackOverflow { needed } => write!(
+            f, "scene needs a surface stack of {needed}, the interpreter only has {MAX_SURFACE_STACK}"
+         ),
+         BytecodeError::PointStackOverflow { needed } => write!(
+            f, "scene nests {needed} transforms, the interpreter only supports {MAX_POINT_STACK}"
+         ),
+      }
+   }
+}
+impl std::error::Error for BytecodeError {}
+
+
+/// flattens the scene tree into postfix order, never returns an empty program.
+/// with `solo` set only that node is drawn
+pub fn flatten(scene: &Scene, solo: Option<NodeId>) -> Result<Vec<Instruction>, BytecodeError> {
+   let mut flattener = Flattener {
+      scene,
+      solo,
+      program: vec![],
+      surface_depth: 0,
+      max_surface_depth: 0,
+      point_depth: 0,
+      max_point_depth: 0,
+   };
+   flattener.node(scene.root());
+
+   if flattener.max_surface_depth > MAX_SURFACE_STACK {
+      return Err(BytecodeError::SurfaceStackOverflow { needed: flattener.max_surface_depth });
+   }
+   if flattener.max_point_depth > MAX_POINT_STACK {
+      return Err(BytecodeError::PointStackOverflow { needed: flattener.max_point_depth });
+   }
+
+   Ok(flattener.program)
+}
+
+
+struct Flattener<'a> {
+   scene: &'a Scene,
+   solo: Option<NodeId>,
+   program: Vec<Instruction>,
+   surface_depth: usize,
+   max_surface_depth: usize,
+   point_depth: usize,
+   max_point_depth: usize,
+}
+impl<'a> Flattener<'a> {
+   /// leaves exactly one surface on the stack
+   fn node(&mut self, id: NodeId) {
+      let Some((node, kind)) = self.scene.drawn(id, self.solo) else {
+         self.push_surface(Instruction::new(opcode::EMPTY, id.0));
+         return;
+      };
+
+      let transform = node.transform;
+      if !transform.is_identity() {
+         let m = transform.inverse_rotation_matrix();
+         let p = transform.position;
+         self.program.push(Instruction::new(opcode::PUSH_TRANSFORM, id.0)
+             .with_data(0, [m.x.x, m.x.y, m.x.z, 0.0])
+             .with_data(1, [m.y.x, m.y.y, m.y.z, 0.0])
+             .with_data(2, [m.z.x, m.z.y, m.z.z, 0.0])
+             .with_data(3, [p.x, p.y, p.z, transform.scale]));
+         self.point_depth += 1;
+         self.max_point_depth = self.max_point_depth.max(self.point_depth);
+      }
+
+      match kind {
+         NodeKind::Primitive(primitive) => {
+            let material = self.scene.material(node.material);
+            let [r, g, b] = material.color;
+            let mut instruction = primitive_instruction(primitive, id).with_data(3, [r, g, b, 0.0]);
+            instruction.material = material.surface();
+            self.push_surface(instruction);
+         }
+
+         NodeKind::Operation(_) | NodeKind::Domain(_) | NodeKind::Group => {
+            let operation = match kind {
+               NodeKind::Operation(operation) => *operation,
+               _ => Operation::union(),
+            };
+            let domain = match kind {
+               NodeKind::Domain(domain) => Some(domain_instruction(domain, id)),
+               _ => None,
+            };
+
+            if let Some(mut push) = domain {
+               push.opcode = opcode::PUSH_DOMAIN;
+               self.program.push(push);
+               self.point_depth += 1;
+               self.max_point_depth = self.max_point_depth.max(self.point_depth);
+            }
+
+            let combine = Instruction::new(match operation.boolean {
+               BooleanOp::Union => opcode::UNION,
+               BooleanOp::Subtraction => opcode::SUBTRACTION,
+               BooleanOp::Intersection => opcode::INTERSECTION,
+            }, id.0).with_data(0, [operation.blend as u32 as f32, operation.params.radius, operation.params.steps, 0.0]);
+
+            if node.children.is_empty() {
+               self.push_surface(Instruction::new(opcode::EMPTY, id.0));
+            }
+            for (i, child) in node.children.iter().enumerate() {
+               self.node(*child);
+               if i > 0 {
+                  self.program.push(combine);
+                  self.surface_depth -= 1;
+               }
+            }
+
+            if let Some(mut pop) = domain {
+               pop.opcode = opcode::POP_DOMAIN;
+               self.program.push(pop);
+               self.point_depth -= 1;
+            }
+         }
+      }
+
+      if !transform.is_identity() {
+         self.program.push(Instruction::new(opcode::POP_TRANSFORM, id.0)
+             .with_data(0, [transform.scale, 0.0, 0.0, 0.0]));
+         self.point_depth -= 1;
+      }
+   }
+
+   fn push_surface(&mut self, instruction: Instruction) {
+      self.program.push(instruction);
+      self.surface_depth += 1;
+      self.max_surface_depth = self.max_surface_depth.max(self.surface_depth);
+   }
+}
+
+fn primitive_instruction(primitive: &Primitive, id: NodeId) -> Instruction {
+   let mut instruction = Instruction::new(primitive.opcode(), id.0);
+   let params = primitive.params();
+   let data: &mut [f32] = bytemuck::cast_slice_mut(&mut instruction.data);
+   data[..params.len()].copy_from_slice(params);
+   instruction
+}
+
+fn domain_instruction(domain: &DomainOp, id: NodeId) -> Instruction {
+   let [a, b] = domain.packed_params();
+   Instruction::new(opcode::PUSH_DOMAIN, id.0)
+       .with_data(0, [domain.kind() as f32, 0.0, 0.0, 0.0])
+       .with_data(1, a)
+       .with_data(2, b)
+}
+
+fn primitive_params(instruction: &Instruction) -> &[f32] {
+   &bytemuck::cast_slice(&instruction.data)[..MAX_PRIMITIVE_PARAMS]
+}
+
+
+/// the wgsl `map` that walks the program buffer, to be spliced in place of a compiled one
+pub fn interpreter_source() -> String {
+   let mut source = String::new();
+   for (name, value) in opcode::ALL {
+      let _ = writeln!(source, "const {name}: u32 = {value}u;");
+   }
+   let _ = writeln!(source, "const MAX_SURFACE_STACK: u32 = {MAX_SURFACE_STACK}u;");
+   let _ = writeln!(source, "const MAX_POINT_STACK: u32 = {MAX_POINT_STACK}u;");
+
+   let mut cases = String::new();
+   for primitive in Primitive::all() {
+      let mut call = primitive.wgsl_function().to_string() + "(p";
+      for i in 0..primitive.params().len() {
+         let _ = write!(call, ", ins.data[{}].{}", i / 4, ["x", "y", "z", "w"][i % 4]);
+      }
+      let _ = write!(cases,
+         "            case {}u: {{ // {}\n                surfaces[sp] = Surface({call}), color, ins.material, ins.id);\n                sp += 1u;\n            }}\n",
+         primitive.opcode(), primitive.name(),
+      );
+   }
+
+   source.push_str(&INTERPRETER_SOURCE.replace(PRIMITIVES_MARKER, cases.trim_end()));
+   source
+}
+
+
+/// cpu port of `interpreter.wgsl`
+pub fn interpret(program: &[Instruction], p: Vector3<f32>) -> Surface {
+   let mut surfaces: Vec<Surface> = Vec::with_capacity(MAX_SURFACE_STACK);
+   let mut points: Vec<Vector3<f32>> = Vec::with_capacity(MAX_POINT_STACK);
+   let mut p = p;
+
+   for instruction in program {
+      let d = &instruction.data;
+      let color = [d[3][0], d[3][1], d[3][2]];
+      let primitive = |dist: f32| Surface::new(dist, color, instruction.material, instruction.id);
+
+      match instruction.opcode {
+         opcode::EMPTY => surfaces.push(Surface::EMPTY),
+
+         opcode::PUSH_TRANSFORM => {
+            points.push(p);
+            let m = Matrix3::new(
+               d[0][0], d[0][1], d[0][2],
+               d[1][0], d[1][1], d[1][2],
+               d[2][0], d[2][1], d[2][2],
+            );
+            p = (m * (p - Vector3::new(d[3][0], d[3][1], d[3][2]))) / d[3][3];
+         }
+         opcode::POP_TRANSFORM => {
+            p = points.pop().unwrap_or(p);
+            if let Some(top) = surfaces.last_mut() {
+               top.dist *= d[0][0];
+            }
+         }
+
+         opcode::PUSH_DOMAIN | opcode::POP_DOMAIN => {
+            let params = &bytemuck::cast_slice(&d[1..3])[..8];
+            let Some(domain) = DomainOp::from_kind(d[0][0] as u32, params) else { continue };
+            if instruction.opcode == opcode::PUSH_DOMAIN {
+               points.push(p);
+               p = domain.apply_point(p);
+            } else {
+               p = points.pop().unwrap_or(p);
+               if let Some(top) = surfaces.last_mut() {
+                  top.dist = domain.apply_distance(top.dist);
+               }
+            }
+         }
+
+         opcode::UNION | opcode::SUBTRACTION | opcode::INTERSECTION => {
+            let (Some(b), Some(a)) = (surfaces.pop(), surfaces.pop()) else { break };
+            let operation = Operation {
+               boolean: match instruction.opcode {
+                  opcode::UNION => BooleanOp::Union,
+                  opcode::SUBTRACTION => BooleanOp::Subtraction,
+                  _ => BooleanOp::Intersection,
+               },
+               blend: Blend::from_index(d[0][0] as u32),
+               params: BlendParams { radius: d[0][1], steps: d[0][2] },
+            };
+            surfaces.push(sdf::op_combine(a, b, &operation));
+         }
+
+         other => {
+            if let Some(shape) = Primitive::from_opcode(other, primitive_params(instruction)) {
+               surfaces.push(primitive(shape.distance(p)));
+            }
+         }
+      }
+   }
+
+   surfaces.first().copied().unwrap_or(Surface::EMPTY)
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::eval;
+
+   /// every point of a grid across the scene
+   fn grid() -> impl Iterator<Item = Vector3<f32>> {
+      let steps = 9;
+      let coordinate = move |i: i32| -3.0 + 6.0 * i as f32 / (steps - 1) as f32;
+      (0..steps * steps * steps).map(move |i| Vector3::new(coordinate(i % steps), coordinate(i / steps % steps), coordinate(i / (steps * steps))))
+   }
+
+   fn assert_matches_eval(name: &str, scene: &Scene) {
+      let program = flatten(scene, None).unwrap();
+      for p in grid() {
+         let (interpreted, evaluated) = (interpret(&program, p), eval::eval_surface(scene, p));
+         assert!((interpreted.dist - evaluated.dist).abs() < 1e-4, "{name} at {p:?}: {} != {}", interpreted.dist, evaluated.dist);
+         assert_eq!(interpreted.id, evaluated.id, "{name} at {p:?}");
+      }
+   }
+
+   #[test]
+   fn demo_interprets_like_eval() {
+      assert_matches_eval("demo", &Scene::demo());
+   }
+
+   #[test]
+   fn every_node_kind_interprets_like_eval() {
+      for (name, scene) in Scene::one_of_each() {
+         assert_matches_eval(&name, &scene);
+      }
+   }
+}
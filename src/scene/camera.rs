@@ -0,0 +1,185 @@
+use std::f32::consts::FRAC_PI_2;
+use cgmath::{InnerSpace, Point3, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// pitch stops this far short of straight up or down, where the basis flips
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+
+/// closest the orbit camera gets to its target
+pub const MIN_DISTANCE: f32 = 0.01;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Camera {
+   pub position: Point3<f32>,
+   pub target: Point3<f32>,
+   /// vertical field of view in degrees
+   pub fov: f32,
+}
+impl Default for Camera {
+   fn default() -> Self {
+      Self {
+         position: Point3::new(0.0, 1.5, -5.0),
+         target: Point3::new(0.0, 0.0, 0.0),
+         fov: 60.0,
+      }
+   }
+}
+impl Camera {
+   /// (forward, right, up), left handed with +y up to match the shader
+   pub fn basis(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+      let forward = (self.target - self.position).normalize();
+      let mut right = Vector3::unit_y().cross(forward);
+      if right.magnitude2() < 1e-8 {
+         right = Vector3::unit_x();
+      }
+      let right = right.normalize();
+      let up = forward.cross(right);
+      (forward, right, up)
+   }
+
+   /// tan of half the vertical fov, scales ndc into view space
+   pub fn focal_scale(&self) -> f32 {
+      (self.fov.to_radians() * 0.5).tan()
+   }
+
+   /// ndc is -1..1 with +y up
+   pub fn ray_direction(&self, ndc_x: f32, ndc_y: f32, aspect: f32) -> Vector3<f32> {
+      let (forward, right, up) = self.basis();
+      let s = self.focal_scale();
+      (forward + right * (ndc_x * s * aspect) + up * (ndc_y * s)).normalize()
+   }
+
+   /// inverse of `ray_direction`, none for points behind the camera
+   pub fn project(&self, p: Point3<f32>, aspect: f32) -> Option<(f32, f32)> {
+      let (forward, right, up) = self.basis();
+      let offset = p - self.position;
+      let depth = offset.dot(forward);
+      if depth < 1e-4 {
+         return None;
+      }
+      let s = self.focal_scale();
+      Some((offset.dot(right) / (depth * s * aspect), offset.dot(up) / (depth * s)))
+   }
+
+   pub fn distance(&self) -> f32 {
+      (self.target - self.position).magnitude()
+   }
+
+   /// turns the position around the target, radians, positive yaw moves the camera left and positive pitch up
+   pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+      let (offset_yaw, offset_pitch) = angles(self.position - self.target);
+      self.position = self.target + direction(offset_yaw + yaw, offset_pitch + pitch) * self.distance();
+   }
+
+   /// moves the position and target together along the right and up axes
+   pub fn pan(&mut self, right: f32, up: f32) {
+      let (_, right_axis, up_axis) = self.basis();
+      let offset = right_axis * right + up_axis * up;
+      self.position += offset;
+      self.target += offset;
+   }
+
+   /// scales the distance to the target, below 1 moves closer
+   pub fn zoom(&mut self, factor: f32) {
+      let distance = (self.distance() * factor).max(MIN_DISTANCE);
+      self.position = self.target - (self.target - self.position).normalize() * distance;
+   }
+
+   /// turns the view around the position, radians, positive yaw looks right and positive pitch up
+   pub fn look(&mut self, yaw: f32, pitch: f32) {
+      let (view_yaw, view_pitch) = angles(self.target - self.position);
+      self.target = self.position + direction(view_yaw + yaw, view_pitch + pitch) * self.distance();
+   }
+
+   /// moves the position and target together, `movement` is (right, up, forward) in camera space
+   pub fn fly(&mut self, movement: Vector3<f32>) {
+      let (forward, right, up) = self.basis();
+      let offset = right * movement.x + up * movement.y + forward * movement.z;
+      self.position += offset;
+      self.target += offset;
+   }
+
+   /// world units covered by one pixel at the target's distance, keeps panning under the cursor
+   pub fn pixel_size(&self, viewport_height: f32) -> f32 {
+      self.pixel_size_at(self.target, viewport_height)
+   }
+
+   /// world units covered by one pixel at the depth of `p`
+   pub fn pixel_size_at(&self, p: Point3<f32>, viewport_height: f32) -> f32 {
+      let depth = (p - self.position).dot(self.basis().0).max(1e-4);
+      2.0 * depth * self.focal_scale() / viewport_height.max(1.0)
+   }
+}
+
+
+/// (yaw around +y from +z, pitch above the xz plane) of a vector
+fn angles(v: Vector3<f32>) -> (f32, f32) {
+   if v.magnitude2() < 1e-12 {
+      return (0.0, 0.0);
+   }
+   let v = v.normalize();
+   (v.x.atan2(v.z), v.y.clamp(-1.0, 1.0).asin())
+}
+
+fn direction(yaw: f32, pitch: f32) -> Vector3<f32> {
+   let pitch = pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+   Vector3::new(pitch.cos() * yaw.sin(), pitch.sin(), pitch.cos() * yaw.cos())
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn orbit_keeps_the_distance_and_target() {
+      let mut camera = Camera::default();
+      let distance = camera.distance();
+      for (yaw, pitch) in [(0.3, 0.1), (-1.2, 0.4), (2.5, -0.7), (0.0, 0.0)] {
+         camera.orbit(yaw, pitch);
+         assert!((camera.distance() - distance).abs() < 1e-4);
+         assert_eq!(camera.target, Camera::default().target);
+      }
+   }
+
+   #[test]
+   fn pitch_is_clamped() {
+      let mut camera = Camera::default();
+      camera.orbit(0.0, 10.0);
+      let (_, pitch) = angles(camera.position - camera.target);
+      assert!(pitch <= PITCH_LIMIT + 1e-4 && pitch > PITCH_LIMIT - 1e-3);
+
+      camera.look(0.0, -10.0);
+      let (_, pitch) = angles(camera.target - camera.position);
+      assert!(pitch >= -PITCH_LIMIT - 1e-4);
+      // the basis stays usable looking almost straight down
+      let (forward, right, up) = camera.basis();
+      assert!(forward.dot(right).abs() < 1e-4 && forward.dot(up).abs() < 1e-4);
+   }
+
+   #[test]
+   fn project_undoes_ray_direction() {
+      let camera = Camera { position: Point3::new(1.0, 2.0, -4.0), target: Point3::new(0.5, 0.0, 1.0), fov: 50.0 };
+      let aspect = 16.0 / 9.0;
+      for (x, y) in [(0.0, 0.0), (0.5, -0.25), (-0.9, 0.8), (1.0, 1.0)] {
+         let direction = camera.ray_direction(x, y, aspect);
+         let (px, py) = camera.project(camera.position + direction * 3.0, aspect).unwrap();
+         assert!((px - x).abs() < 1e-4 && (py - y).abs() < 1e-4, "({x}, {y}) -> ({px}, {py})");
+      }
+      assert_eq!(camera.project(camera.position - camera.basis().0, aspect), None);
+   }
+
+   #[test]
+   fn zoom_stops_at_the_min_distance() {
+      let mut camera = Camera::default();
+      let forward = camera.basis().0;
+      for _ in 0..50 {
+         camera.zoom(0.5);
+      }
+      assert!((camera.distance() - MIN_DISTANCE).abs() < 1e-6);
+      assert!((camera.basis().0 - forward).magnitude() < 1e-4);
+
+      camera.zoom(2.0);
+      assert!((camera.distance() - 2.0 * MIN_DISTANCE).abs() < 1e-6);
+   }
+}
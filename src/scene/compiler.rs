@@ -0,0 +1,331 @@
+use std::fmt::{Display, Formatter, Write};
+use bytemuck::{Pod, Zeroable};
+use crate::scene::graph::Scene;
+use crate::scene::node::{NodeId, NodeKind};
+use crate::scene::operators::Operation;
+use crate::scene::primitives::Primitive;
+
+/// line in a shader template that gets replaced by the sdf library and the scene's `map`
+pub const MAP_MARKER: &str = "//#MAP";
+
+/// distance functions and combinators every generated `map` relies on
+pub const SDF_LIBRARY: &str = include_str!("sdf_library.wgsl");
+
+/// vec4s in the scene params uniform, 16KB is the largest uniform buffer every backend allows
+pub const MAX_SCENE_PARAMS: usize = 1024;
+
+/// bound at group 1 by the compiled pipeline, must match `MAX_SCENE_PARAMS`
+const SCENE_PARAMS_DECLARATION: &str = "struct SceneParams {
+    data: array<vec4<f32>, 1024>,
+}
+
+@group(1) @binding(0) var<uniform> scene_params: SceneParams;
+";
+
+
+/// every number a compiled `map` reads, uploaded each frame so value edits don't rebuild the pipeline
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct SceneParams {
+   pub data: [[f32; 4]; MAX_SCENE_PARAMS],
+}
+impl SceneParams {
+   /// packs scalars four to a slot, anything past the end is dropped
+   pub fn new(values: &[f32]) -> Self {
+      let mut params = Self::zeroed();
+      for (i, value) in values.iter().take(MAX_SCENE_PARAMS * 4).enumerate() {
+         params.data[i / 4][i % 4] = *value;
+      }
+      params
+   }
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+   MissingMarker,
+   Parse(String),
+   Validation(String),
+   /// the scene has more values than fit in `SceneParams`
+   ParamOverflow { needed: usize },
+}
+impl Display for CompileError {
+   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+      match self {
+         CompileError::MissingMarker => write!(f, "shader template has no `{MAP_MARKER}` line"),
+         CompileError::Parse(error) => write!(f, "generated wgsl failed to parse:\n{error}"),
+         CompileError::Validation(error) => write!(f, "generated wgsl failed validation:\n{error}"),
+         CompileError::ParamOverflow { needed } => {
+            write!(f, "scene needs {needed} parameter slots but only {MAX_SCENE_PARAMS} fit, try interpreted mode")
+         }
+      }
+   }
+}
+impl std::error::Error for CompileError {}
+
+
+/// splices the scene into `template` and validates the result with naga
+pub fn compile(template: &str, scene: &Scene) -> Result<String, CompileError> {
+   let source = splice(template, &compile_map(scene, None)?.source)?;
+   validate(&source)?;
+   Ok(source)
+}
+
+/// replaces the marker with the sdf library followed by `map_source`
+pub fn splice(template: &str, map_source: &str) -> Result<String, CompileError> {
+   if !template.contains(MAP_MARKER) {
+      return Err(CompileError::MissingMarker);
+   }
+   Ok(template.replacen(MAP_MARKER, &format!("{SDF_LIBRARY}\n{map_source}"), 1))
+}
+
+pub fn validate(source: &str) -> Result<(), CompileError> {
+   let module = naga::front::wgsl::parse_str(source)
+       .map_err(|error| CompileError::Parse(error.emit_to_string(source)))?;
+
+   naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+       .validate(&module)
+       .map_err(|error| CompileError::Validation(error.emit_to_string(source)))?;
+
+   Ok(())
+}
+
+/// the scene tree as a wgsl `fn map(p: vec3<f32>) -> Surface`
+///
+/// every number is read from `scene_params` instead of being written into the source,
+/// so the source only changes when the tree's structure does. with `solo` set only that node is drawn
+pub fn compile_map(scene: &Scene, solo: Option<NodeId>) -> Result<CompiledMap, CompileError> {
+   let mut writer = MapWriter {
+      scene,
+      solo,
+      body: String::new(),
+      counter: 0,
+      params: vec![],
+      free: vec![],
+   };
+   let result = writer.node(scene.root(), "p");
+
+   let needed = writer.params.len().div_ceil(4);
+   if needed > MAX_SCENE_PARAMS {
+      return Err(CompileError::ParamOverflow { needed });
+   }
+
+   Ok(CompiledMap {
+      source: format!(
+         "{SCENE_PARAMS_DECLARATION}\nfn map(p: vec3<f32>) -> Surface {{\n{}    return {result};\n}}\n",
+         writer.body,
+      ),
+      params: SceneParams::new(&writer.params),
+   })
+}
+
+
+/// a generated `map` and the values it reads
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledMap {
+   pub source: String,
+   pub params: SceneParams,
+}
+
+
+struct MapWriter<'a> {
+   scene: &'a Scene,
+   solo: Option<NodeId>,
+   body: String,
+   counter: u32,
+   params: Vec<f32>,
+   /// unused scalars left behind by vec3s and alignment, filled before the buffer grows
+   free: Vec<usize>,
+}
+impl<'a> MapWriter<'a> {
+   /// emits the statements for `id` and returns the name of the variable holding its surface
+   fn node(&mut self, id: NodeId, point: &str) -> String {
+      let Some((node, kind)) = self.scene.drawn(id, self.solo) else {
+         return "empty_surface()".to_string();
+      };
+
+      let n = self.counter;
+      self.counter += 1;
+
+      // always emitted so moving a node away from the origin doesn't change the source
+      let transform = node.transform;
+      let m = transform.inverse_rotation_matrix();
+      let columns = [m.x.into(), m.y.into(), m.z.into()].map(|column| self.vec3(column));
+      let position = self.vec3(transform.position.into());
+      let scale = self.scalar(transform.scale);
+      self.line(format!(
+         "let p{n} = (mat3x3<f32>({}, {}, {}) * ({point} - {position})) / {scale};",
+         columns[0], columns[1], columns[2],
+      ));
+      let point = format!("p{n}");
+
+      match kind {
+         NodeKind::Primitive(primitive) => {
+            let distance = self.primitive_call(primitive, &point);
+            let material = self.scene.material(node.material);
+            let color = self.vec3(material.color);
+            let [metallic, roughness] = material.surface().map(|value| self.scalar(value));
+            self.line(format!("var s{n} = Surface({distance}, {color}, vec2<f32>({metallic}, {roughness}), {}u);", id.0));
+         }
+
+         NodeKind::Operation(_) | NodeKind::Domain(_) | NodeKind::Group => {
+            let operation = match kind {
+               NodeKind::Operation(operation) => Some(*operation),
+               _ => None,
+            };
+
+            let point = match kind {
+               NodeKind::Domain(domain) if domain.warps_point() => {
+                  let [a, b] = domain.packed_params().map(|params| self.vec4(params));
+                  self.line(format!("let d{n} = domain_point({point}, {}u, {a}, {b});", domain.kind()));
+                  format!("d{n}")
+               }
+               _ => point,
+            };
+
+            self.line(format!("var s{n} = empty_surface();"));
+            for (i, child) in node.children.iter().enumerate() {
+               let child_result = self.node(*child, &point);
+               if i == 0 {
+                  self.line(format!("s{n} = {child_result};"));
+               } else {
+                  let combined = self.combine_call(operation.as_ref(), &format!("s{n}"), &child_result);
+                  self.line(format!("s{n} = {combined};"));
+               }
+            }
+         }
+      }
+
+      if let NodeKind::Domain(domain) = kind {
+         if domain.warps_distance() {
+            let a = self.vec4(domain.packed_params()[0]);
+            self.line(format!("s{n}.dist = domain_distance(s{n}.dist, {}u, {a});", domain.kind()));
+         }
+      }
+
+      self.line(format!("s{n}.dist *= {scale};"));
+
+      format!("s{n}")
+   }
+
+   /// groups and domains union their children, operations read their boolean and blend from the params
+   fn combine_call(&mut self, operation: Option<&Operation>, a: &str, b: &str) -> String {
+      let Some(operation) = operation else {
+         return format!("op_union({a}, {b})");
+      };
+
+      let boolean = self.scalar(operation.boolean as u32 as f32);
+      let blend = self.scalar(operation.blend as u32 as f32);
+      let radius = self.scalar(operation.params.radius);
+      let steps = self.scalar(operation.params.steps);
+      format!("op_combine({a}, {b}, u32({boolean}), u32({blend}), {radius}, {steps})")
+   }
+
+   fn primitive_call(&mut self, primitive: &Primitive, point: &str) -> String {
+      let mut call = format!("{}({point}", primitive.wgsl_function());
+      for param in primitive.params() {
+         let param = self.scalar(*param);
+         let _ = write!(call, ", {param}");
+      }
+      call.push(')');
+      call
+   }
+
+   fn scalar(&mut self, value: f32) -> String {
+      let index = match self.free.pop() {
+         Some(index) => index,
+         None => {
+            self.params.push(0.0);
+            self.params.len() - 1
+         }
+      };
+      self.params[index] = finite(value);
+      format!("scene_params.data[{}].{}", index / 4, ["x", "y", "z", "w"][index % 4])
+   }
+
+   /// vec3s take a whole slot, the spare w is handed out to the next scalar
+   fn vec3(&mut self, value: [f32; 3]) -> String {
+      let slot = self.slot([value[0], value[1], value[2], 0.0]);
+      self.free.push(slot * 4 + 3);
+      format!("scene_params.data[{slot}].xyz")
+   }
+
+   fn vec4(&mut self, value: [f32; 4]) -> String {
+      format!("scene_params.data[{}]", self.slot(value))
+   }
+
+   fn slot(&mut self, value: [f32; 4]) -> usize {
+      while !self.params.len().is_multiple_of(4) {
+         self.free.push(self.params.len());
+         self.params.push(0.0);
+      }
+      let slot = self.params.len() / 4;
+      self.params.extend(value.map(finite));
+      slot
+   }
+
+   fn line(&mut self, line: String) {
+      let _ = writeln!(self.body, "    {line}");
+   }
+}
+
+/// non finite values are clamped so the shader never sees them
+fn finite(value: f32) -> f32 {
+   if value.is_nan() { 0.0 } else { value.clamp(-1e30, 1e30) }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::packages::scene_render_pipeline::SHADER_TEMPLATE;
+   use crate::scene::node::Node;
+   use crate::scene::primitives::SphereParams;
+
+   #[test]
+   fn demo_compiles_and_validates() {
+      compile(SHADER_TEMPLATE, &Scene::demo()).unwrap();
+   }
+
+   #[test]
+   fn every_node_kind_compiles_and_validates() {
+      for (name, scene) in Scene::one_of_each() {
+         if let Err(error) = compile(SHADER_TEMPLATE, &scene) {
+            panic!("{name}: {error}");
+         }
+      }
+   }
+
+   #[test]
+   fn sphere_map_snapshot() {
+      let mut scene = Scene::new();
+      let root = scene.root();
+      scene.insert(root, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 0.5 }))).unwrap();
+      let map = compile_map(&scene, None).unwrap();
+      assert_eq!(map.source, include_str!("snapshots/sphere_map.wgsl"));
+   }
+
+   #[test]
+   fn demo_map_snapshot() {
+      let map = compile_map(&Scene::demo(), None).unwrap();
+      assert_eq!(map.source, include_str!("snapshots/demo_map.wgsl"));
+   }
+
+   #[test]
+   fn editing_values_only_changes_the_params() {
+      let scene = Scene::demo();
+      let mut edited = scene.clone();
+      let ids: Vec<NodeId> = scene.iter().map(|(id, _)| id).collect();
+      for id in ids {
+         let node = edited.node_mut(id).unwrap();
+         node.transform.position.x += 0.5;
+         if let NodeKind::Primitive(Primitive::Sphere(params)) = &mut node.kind {
+            params.radius *= 2.0;
+         }
+      }
+
+      let (before, after) = (compile_map(&scene, None).unwrap(), compile_map(&edited, None).unwrap());
+      assert_eq!(before.source, after.source);
+      assert_ne!(before.params, after.params);
+   }
+}
@@ -0,0 +1,30 @@
+use crate::scene::graph::Scene;
+use crate::scene::history::History;
+
+/// the scene being edited together with its undo history
+pub struct Document {
+   pub scene: Scene,
+   pub history: History,
+}
+impl Document {
+   pub fn new(scene: Scene) -> Self {
+      Self {
+         scene,
+         history: History::default(),
+      }
+   }
+
+   pub fn undo(&mut self) -> bool {
+      self.history.undo(&mut self.scene)
+   }
+
+   pub fn redo(&mut self) -> bool {
+      self.history.redo(&mut self.scene)
+   }
+
+   /// swaps in a different scene, the old history doesn't apply to it
+   pub fn replace(&mut self, scene: Scene) {
+      self.scene = scene;
+      self.history.clear();
+   }
+}
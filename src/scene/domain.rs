@@ -0,0 +1,180 @@
+use cgmath::Vector3;
+use crate::defaults_and_sliders_gui;
+use crate::scene::sdf;
+
+defaults_and_sliders_gui!(RepeatParams,
+   /// cell size along each axis, 0 leaves that axis alone
+   spacing_x: f32 = 2.0 => 0.0..=20.0,
+   spacing_y: f32 = 0.0 => 0.0..=20.0,
+   spacing_z: f32 = 2.0 => 0.0..=20.0
+);
+
+defaults_and_sliders_gui!(RepeatLimitedParams,
+   spacing_x: f32 = 1.5 => 0.0..=20.0,
+   spacing_y: f32 = 0.0 => 0.0..=20.0,
+   spacing_z: f32 = 1.5 => 0.0..=20.0,
+   /// extra copies on each side of the original
+   #[step(1.0)]
+   count_x: f32 = 2.0 => 0.0..=32.0,
+   #[step(1.0)]
+   count_y: f32 = 0.0 => 0.0..=32.0,
+   #[step(1.0)]
+   count_z: f32 = 2.0 => 0.0..=32.0
+);
+
+defaults_and_sliders_gui!(PolarRepeatParams,
+   /// copies around the y axis
+   #[step(1.0)]
+   count: f32 = 6.0 => 1.0..=64.0
+);
+
+defaults_and_sliders_gui!(MirrorParams,
+   /// 1 mirrors across the plane facing this axis
+   #[step(1.0)]
+   x: f32 = 1.0 => 0.0..=1.0,
+   #[step(1.0)]
+   y: f32 = 0.0 => 0.0..=1.0,
+   #[step(1.0)]
+   z: f32 = 0.0 => 0.0..=1.0
+);
+
+defaults_and_sliders_gui!(TwistParams,
+   /// radians of rotation per unit along y
+   strength: f32 = 1.0 => -10.0..=10.0
+);
+
+defaults_and_sliders_gui!(BendParams,
+   /// radians of rotation per unit along x
+   strength: f32 = 0.5 => -5.0..=5.0
+);
+
+defaults_and_sliders_gui!(ElongateParams,
+   /// how far the child is stretched out from its centre along each axis
+   x: f32 = 0.5 => 0.0..=10.0,
+   y: f32 = 0.0 => 0.0..=10.0,
+   z: f32 = 0.0 => 0.0..=10.0
+);
+
+defaults_and_sliders_gui!(OnionParams,
+   thickness: f32 = 0.05 => 0.001..=1.0
+);
+
+defaults_and_sliders_gui!(RoundParams,
+   radius: f32 = 0.1 => 0.0..=2.0
+);
+
+
+/// same shape as `primitives!`, the kind values match the `DOMAIN_*` constants in `sdf_library.wgsl`
+macro_rules! domains {
+   ($($variant:ident($params:ident) = $kind:literal => $name:literal;)*) => {
+      /// warps the space its children are evaluated in, or the distance they return
+      #[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+      pub enum DomainOp {
+         $($variant($params),)*
+      }
+
+      impl DomainOp {
+         pub fn all() -> Vec<DomainOp> {
+            vec![$(DomainOp::$variant($params::default()),)*]
+         }
+
+         pub fn name(&self) -> &'static str {
+            match self { $(DomainOp::$variant(_) => $name,)* }
+         }
+
+         pub fn kind(&self) -> u32 {
+            match self { $(DomainOp::$variant(_) => $kind,)* }
+         }
+
+         pub fn params(&self) -> &[f32] {
+            match self { $(DomainOp::$variant(params) => bytemuck::cast_slice(bytemuck::bytes_of(params)),)* }
+         }
+
+         pub fn from_kind(kind: u32, values: &[f32]) -> Option<DomainOp> {
+            match kind {
+               $($kind => {
+                  let mut params = $params::default();
+                  let fields: &mut [f32] = bytemuck::cast_slice_mut(bytemuck::bytes_of_mut(&mut params));
+                  let n = fields.len().min(values.len());
+                  fields[..n].copy_from_slice(&values[..n]);
+                  Some(DomainOp::$variant(params))
+               })*
+               _ => None,
+            }
+         }
+
+         pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+            match self { $(DomainOp::$variant(params) => params.ui(ui),)* }
+         }
+      }
+   };
+}
+
+domains! {
+   Repeat(RepeatParams) = 0 => "Repeat";
+   RepeatLimited(RepeatLimitedParams) = 1 => "Repeat Limited";
+   PolarRepeat(PolarRepeatParams) = 2 => "Polar Repeat";
+   Mirror(MirrorParams) = 3 => "Mirror";
+   Twist(TwistParams) = 4 => "Twist";
+   Bend(BendParams) = 5 => "Bend";
+   Elongate(ElongateParams) = 6 => "Elongate";
+   Onion(OnionParams) = 7 => "Onion";
+   Round(RoundParams) = 8 => "Round";
+}
+
+impl DomainOp {
+   /// mirrors `domain_point` in `sdf_library.wgsl`
+   pub fn apply_point(&self, p: Vector3<f32>) -> Vector3<f32> {
+      match self {
+         DomainOp::Repeat(s) => sdf::dom_repeat(p, Vector3::new(s.spacing_x, s.spacing_y, s.spacing_z)),
+         DomainOp::RepeatLimited(s) => sdf::dom_repeat_limited(
+            p,
+            Vector3::new(s.spacing_x, s.spacing_y, s.spacing_z),
+            Vector3::new(s.count_x, s.count_y, s.count_z),
+         ),
+         DomainOp::PolarRepeat(s) => sdf::dom_polar_repeat(p, s.count),
+         DomainOp::Mirror(s) => sdf::dom_mirror(p, Vector3::new(s.x, s.y, s.z)),
+         DomainOp::Twist(s) => sdf::dom_twist(p, s.strength),
+         DomainOp::Bend(s) => sdf::dom_bend(p, s.strength),
+         DomainOp::Elongate(s) => sdf::dom_elongate(p, Vector3::new(s.x, s.y, s.z)),
+         DomainOp::Onion(_) | DomainOp::Round(_) => p,
+      }
+   }
+
+   /// mirrors `domain_distance` in `sdf_library.wgsl`
+   pub fn apply_distance(&self, d: f32) -> f32 {
+      match self {
+         DomainOp::Onion(s) => d.abs() - s.thickness,
+         DomainOp::Round(s) => d - s.radius,
+         _ => d,
+      }
+   }
+
+   /// false for ops that only touch the distance
+   pub fn warps_point(&self) -> bool {
+      !self.warps_distance()
+   }
+
+   pub fn warps_distance(&self) -> bool {
+      matches!(self, DomainOp::Onion(_) | DomainOp::Round(_))
+   }
+
+   /// params in field order, split over the two vec4s `domain_point` and the bytecode take
+   pub fn packed_params(&self) -> [[f32; 4]; 2] {
+      let mut packed = [[0.0; 4]; 2];
+      let data: &mut [f32] = bytemuck::cast_slice_mut(&mut packed);
+      let params = self.params();
+      data[..params.len()].copy_from_slice(params);
+      packed
+   }
+
+   /// how much the raymarcher should shorten its steps to stay safe inside this warp,
+   /// twist and bend stretch space so the field they produce overestimates the true distance
+   pub fn step_scale(&self) -> f32 {
+      match self {
+         DomainOp::Twist(s) => 1.0 / (1.0 + s.strength.abs()),
+         DomainOp::Bend(s) => 1.0 / (1.0 + s.strength.abs()),
+         _ => 1.0,
+      }
+   }
+}
@@ -0,0 +1,369 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::scene::graph::Scene;
+use crate::scene::material::Material;
+use crate::scene::node::{Node, NodeId, NodeKind};
+use crate::scene::operators::Operation;
+use crate::scene::sdf;
+use crate::scene::sdf::Surface;
+
+/// offset used for the tetrahedral gradient, the default `NormalParams::normal_epsilon` the shaders use
+pub const GRADIENT_EPSILON: f32 = 0.0005;
+
+/// signed distance from `p` to the scene
+pub fn eval(scene: &Scene, p: Vector3<f32>) -> f32 {
+   eval_surface(scene, p).dist
+}
+
+/// distance, colour and owning node id, the same thing the shader's `map` returns
+pub fn eval_surface(scene: &Scene, p: Vector3<f32>) -> Surface {
+   eval_drawn(scene, None, p)
+}
+
+/// `eval_surface` of just what's drawn with `solo` set, see `Scene::drawn`
+pub fn eval_drawn(scene: &Scene, solo: Option<NodeId>, p: Vector3<f32>) -> Surface {
+   eval_node(scene, scene.root(), solo, p)
+}
+
+/// distance to a single subtree, measured in the root's space
+pub fn eval_subtree(scene: &Scene, id: NodeId, p: Vector3<f32>) -> Surface {
+   let mut chain = vec![];
+   let mut current = scene.node(id).and_then(|node| node.parent);
+   while let Some(parent) = current {
+      chain.push(parent);
+      current = scene.node(parent).and_then(|node| node.parent);
+   }
+
+   // walk the point down through every ancestor transform, then scale the distance back up
+   let mut p = p;
+   let mut scale = 1.0;
+   for ancestor in chain.iter().rev() {
+      let transform = scene.node(*ancestor).unwrap().transform;
+      p = transform.to_local(p);
+      scale *= transform.scale;
+   }
+
+   let mut surface = eval_node(scene, id, None, p);
+   surface.dist *= scale;
+   surface
+}
+
+fn eval_node(scene: &Scene, id: NodeId, solo: Option<NodeId>, p: Vector3<f32>) -> Surface {
+   let Some((node, kind)) = scene.drawn(id, solo) else {
+      return Surface::EMPTY;
+   };
+
+   let transform = node.transform;
+   let local = if transform.is_identity() { p } else { transform.to_local(p) };
+
+   let mut surface = match kind {
+      NodeKind::Primitive(primitive) => {
+         let material = scene.material(node.material);
+         Surface::new(primitive.distance(local), material.color, material.surface(), id.0)
+      }
+
+      NodeKind::Operation(_) | NodeKind::Domain(_) | NodeKind::Group => {
+         let operation = match kind {
+            NodeKind::Operation(operation) => *operation,
+            _ => Operation::union(),
+         };
+
+         let local = match kind {
+            NodeKind::Domain(domain) => domain.apply_point(local),
+            _ => local,
+         };
+
+         let mut children = node.children.iter().map(|child| eval_node(scene, *child, solo, local));
+         match children.next() {
+            Some(first) => children.fold(first, |a, b| sdf::op_combine(a, b, &operation)),
+            None => Surface::EMPTY,
+         }
+      }
+   };
+
+   if let NodeKind::Domain(domain) = kind {
+      surface.dist = domain.apply_distance(surface.dist);
+   }
+
+   surface.dist *= transform.scale;
+   surface
+}
+
+
+/// tetrahedral central difference, unnormalised so it can be used to check the lipschitz bound
+pub fn gradient(scene: &Scene, p: Vector3<f32>) -> Vector3<f32> {
+   gradient_of(|p| eval(scene, p), p)
+}
+
+pub fn gradient_of(f: impl Fn(Vector3<f32>) -> f32, p: Vector3<f32>) -> Vector3<f32> {
+   gradient_with_epsilon(f, p, GRADIENT_EPSILON)
+}
+
+/// tetrahedral differences `e` either side of `p`, the same four samples as the shader's `calc_normal`
+pub fn gradient_with_epsilon(f: impl Fn(Vector3<f32>) -> f32, p: Vector3<f32>, e: f32) -> Vector3<f32> {
+   let k0 = Vector3::new(1.0, -1.0, -1.0);
+   let k1 = Vector3::new(-1.0, -1.0, 1.0);
+   let k2 = Vector3::new(-1.0, 1.0, -1.0);
+   let k3 = Vector3::new(1.0, 1.0, 1.0);
+
+   (k0 * f(p + k0 * e) + k1 * f(p + k1 * e) + k2 * f(p + k2 * e) + k3 * f(p + k3 * e)) / (4.0 * e)
+}
+
+/// surface normal at `p`, falls back to +y where the field is flat
+pub fn normal(scene: &Scene, p: Vector3<f32>) -> Vector3<f32> {
+   let g = gradient(scene, p);
+   if g.magnitude2() > 1e-12 { g.normalize() } else { Vector3::unit_y() }
+}
+
+/// the primitive node that owns the surface nearest to `p`
+pub fn nearest_node(scene: &Scene, p: Vector3<f32>) -> Option<(NodeId, &Node)> {
+   let id = NodeId(eval_surface(scene, p).id);
+   scene.node(id).map(|node| (id, node))
+}
+
+/// material of the surface nearest to `p`
+pub fn material_at(scene: &Scene, p: Vector3<f32>) -> Option<&Material> {
+   nearest_node(scene, p).map(|(_, node)| scene.material(node.material))
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::node::Transform;
+   use cgmath::{ElementWise, Vector2};
+   use crate::scene::primitives::*;
+
+   fn scene_of(node: Node) -> Scene {
+      let mut scene = Scene::new();
+      let root = scene.root();
+      scene.insert(root, node).unwrap();
+      scene
+   }
+
+   fn points() -> Vec<Vector3<f32>> {
+      vec![
+         Vector3::new(2.0, 0.3, -0.4),
+         Vector3::new(-1.1, 1.7, 0.9),
+         Vector3::new(0.2, -0.1, 0.15),
+         Vector3::new(0.9, 1.0, 2.5),
+         Vector3::new(-2.5, -1.5, -1.0),
+      ]
+   }
+
+   fn assert_close(a: f32, b: f32, tolerance: f32) {
+      assert!((a - b).abs() < tolerance, "{a} != {b}");
+   }
+
+   #[test]
+   fn sphere_matches_the_analytic_distance() {
+      let center = Vector3::new(0.5, -0.25, 1.0);
+      let scene = scene_of(Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 0.8 }))
+          .with_transform(Transform::from_position(center)));
+
+      for p in points() {
+         assert_close(eval(&scene, p), (p - center).magnitude() - 0.8, 1e-5);
+         let expected = (p - center).normalize();
+         assert!((gradient(&scene, p) - expected).magnitude() < 1e-2, "{p:?}");
+         assert!((normal(&scene, p) - expected).magnitude() < 1e-2, "{p:?}");
+      }
+   }
+
+   #[test]
+   fn box_matches_the_analytic_distance() {
+      let half = Vector3::new(0.5, 0.75, 1.0);
+      let scene = scene_of(Node::primitive("Box", Primitive::Box(BoxParams { half_x: half.x, half_y: half.y, half_z: half.z })));
+
+      for p in points() {
+         let q = p.map(f32::abs) - half;
+         let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+         let inside = q.x.max(q.y).max(q.z).min(0.0);
+         assert_close(eval(&scene, p), outside + inside, 1e-5);
+      }
+
+      // straight out of a face the normal is the face's
+      assert!((normal(&scene, Vector3::new(0.0, 2.0, 0.1)) - Vector3::unit_y()).magnitude() < 1e-3);
+      assert!((normal(&scene, Vector3::new(-1.5, 0.1, 0.2)) + Vector3::unit_x()).magnitude() < 1e-3);
+   }
+
+   #[test]
+   fn plane_matches_the_analytic_distance() {
+      let scene = scene_of(Node::primitive("Ground", Primitive::Plane(PlaneParams { height: -1.0 })));
+
+      for p in points() {
+         assert_close(eval(&scene, p), p.y + 1.0, 1e-5);
+         assert!((gradient(&scene, p) - Vector3::unit_y()).magnitude() < 1e-3);
+      }
+   }
+
+   /// an 8x8x8 grid through and around the primitives, off every symmetry plane
+   fn grid() -> Vec<Vector3<f32>> {
+      let coordinate = |i: usize| -1.3 + i as f32 * 0.37;
+      (0..8 * 8 * 8).map(|i| Vector3::new(coordinate(i % 8), coordinate(i / 8 % 8), coordinate(i / 64))).collect()
+   }
+
+   fn eval_primitive(primitive: Primitive, p: Vector3<f32>) -> f32 {
+      eval(&scene_of(Node::primitive("Shape", primitive)), p)
+   }
+
+   fn box_distance(p: Vector3<f32>, half: Vector3<f32>) -> f32 {
+      let q = p.map(f32::abs) - half;
+      let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+      outside + q.x.max(q.y).max(q.z).min(0.0)
+   }
+
+   fn segment_distance(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+      let t = ((p - a).dot(b - a) / (b - a).magnitude2()).clamp(0.0, 1.0);
+      (p - (a + (b - a) * t)).magnitude()
+   }
+
+   /// signed distance to a convex polygon with its corners listed anticlockwise
+   fn polygon_distance(p: Vector2<f32>, corners: &[Vector2<f32>]) -> f32 {
+      let edges = || corners.iter().zip(corners.iter().cycle().skip(1));
+      let distance = edges().map(|(a, b)| segment_distance(p, *a, *b)).fold(f32::MAX, f32::min);
+      let inside = edges().all(|(a, b)| (b - a).perp_dot(p - a) >= 0.0);
+      if inside { -distance } else { distance }
+   }
+
+   /// a solid of revolution about y, measured in the plane through the axis and `p`
+   fn revolved(p: Vector3<f32>, profile: &[Vector2<f32>]) -> f32 {
+      polygon_distance(Vector2::new(Vector2::new(p.x, p.z).magnitude(), p.y), profile)
+   }
+
+   /// a cross-section in xy pushed out `half_length` either way along z
+   fn extruded(p: Vector3<f32>, section: &[Vector2<f32>], half_length: f32) -> f32 {
+      let d = Vector2::new(polygon_distance(Vector2::new(p.x, p.y), section), p.z.abs() - half_length);
+      Vector2::new(d.x.max(0.0), d.y.max(0.0)).magnitude() + d.x.max(d.y).min(0.0)
+   }
+
+   fn assert_matches(primitive: Primitive, exact: impl Fn(Vector3<f32>) -> f32) {
+      for p in grid() {
+         let (evaluated, expected) = (eval_primitive(primitive, p), exact(p));
+         assert!((evaluated - expected).abs() < 1e-4, "{} at {p:?}: {evaluated} != {expected}", primitive.name());
+      }
+   }
+
+   #[test]
+   fn round_box_is_a_smaller_box_grown_by_its_radius() {
+      let (half, radius) = (Vector3::new(0.6, 0.4, 0.9), 0.15);
+      let primitive = Primitive::RoundBox(RoundBoxParams { half_x: half.x, half_y: half.y, half_z: half.z, radius });
+      assert_matches(primitive, |p| box_distance(p, half - Vector3::new(radius, radius, radius)) - radius);
+   }
+
+   #[test]
+   fn torus_is_a_tube_round_a_circle() {
+      let primitive = Primitive::Torus(TorusParams { major_radius: 0.8, minor_radius: 0.25 });
+      assert_matches(primitive, |p| {
+         let around = Vector3::new(p.x, 0.0, p.z).normalize() * 0.8;
+         (p - around).magnitude() - 0.25
+      });
+   }
+
+   #[test]
+   fn capsule_is_a_tube_round_a_segment() {
+      let primitive = Primitive::Capsule(CapsuleParams { half_height: 0.6, radius: 0.3 });
+      assert_matches(primitive, |p| {
+         let q = Vector2::new(Vector2::new(p.x, p.z).magnitude(), p.y);
+         segment_distance(q, Vector2::new(0.0, -0.6), Vector2::new(0.0, 0.6)) - 0.3
+      });
+   }
+
+   #[test]
+   fn cylinder_and_cone_are_revolved_profiles() {
+      let cylinder = Primitive::Cylinder(CylinderParams { half_height: 0.7, radius: 0.5 });
+      let rectangle = [Vector2::new(-0.5, -0.7), Vector2::new(0.5, -0.7), Vector2::new(0.5, 0.7), Vector2::new(-0.5, 0.7)];
+      assert_matches(cylinder, |p| revolved(p, &rectangle));
+
+      let cone = Primitive::Cone(ConeParams { half_height: 0.6, bottom_radius: 0.8, top_radius: 0.25 });
+      let trapezium = [Vector2::new(-0.8, -0.6), Vector2::new(0.8, -0.6), Vector2::new(0.25, 0.6), Vector2::new(-0.25, 0.6)];
+      assert_matches(cone, |p| revolved(p, &trapezium));
+   }
+
+   #[test]
+   fn hex_prism_is_an_extruded_hexagon() {
+      // `radius` is the distance to the middle of an edge, the top and bottom edges are flat
+      let (radius, half_length) = (0.5, 0.7);
+      let corner = radius / 3f32.sqrt();
+      let hexagon = [
+         Vector2::new(2.0 * corner, 0.0), Vector2::new(corner, radius), Vector2::new(-corner, radius),
+         Vector2::new(-2.0 * corner, 0.0), Vector2::new(-corner, -radius), Vector2::new(corner, -radius),
+      ];
+      assert_matches(Primitive::HexPrism(HexPrismParams { radius, half_length }), |p| extruded(p, &hexagon, half_length));
+   }
+
+   #[test]
+   fn tri_prism_never_overestimates() {
+      // an equilateral triangle reaching `radius` from its centre, pointing up
+      let (radius, half_length) = (0.8, 0.6);
+      let triangle = [
+         Vector2::new(0.0, radius),
+         Vector2::new(-radius * 0.5 * 3f32.sqrt(), -radius * 0.5),
+         Vector2::new(radius * 0.5 * 3f32.sqrt(), -radius * 0.5),
+      ];
+      let primitive = Primitive::TriPrism(TriPrismParams { radius, half_length });
+      assert!(!primitive.is_exact());
+      for p in grid() {
+         let (evaluated, exact) = (eval_primitive(primitive, p), extruded(p, &triangle, half_length));
+         assert!(evaluated <= exact + 1e-5, "at {p:?}: {evaluated} > {exact}");
+         assert_eq!(evaluated < 0.0, exact < 0.0, "{p:?}");
+      }
+   }
+
+   /// exact signed distance to an ellipsoid, `p` can't sit on a symmetry plane
+   ///
+   /// the nearest point is `radius² p / (t + radius²)` per axis, with `t` the one root of
+   /// `sum((radius p / (t + radius²))²) = 1` above minus the smallest squared radius
+   fn ellipsoid_distance(p: Vector3<f32>, radius: Vector3<f64>) -> f32 {
+      let p = Vector3::new(p.x as f64, p.y as f64, p.z as f64);
+      let squared = radius.mul_element_wise(radius);
+      let nearest = |t: f64| Vector3::new(
+         squared.x * p.x / (t + squared.x),
+         squared.y * p.y / (t + squared.y),
+         squared.z * p.z / (t + squared.z),
+      );
+      let f = |t: f64| nearest(t).div_element_wise(radius).magnitude2() - 1.0;
+
+      let (mut low, mut high) = (-squared.x.min(squared.y).min(squared.z), 1.0);
+      while f(high) > 0.0 {
+         high *= 2.0;
+      }
+      for _ in 0..200 {
+         let middle = 0.5 * (low + high);
+         if f(middle) > 0.0 { low = middle } else { high = middle }
+      }
+
+      let distance = (p - nearest(0.5 * (low + high))).magnitude();
+      let inside = p.div_element_wise(radius).magnitude2() < 1.0;
+      (if inside { -distance } else { distance }) as f32
+   }
+
+   #[test]
+   fn ellipsoid_never_overestimates() {
+      let radius = Vector3::new(0.9, 0.5, 0.7);
+      let primitive = Primitive::Ellipsoid(EllipsoidParams { radius_x: 0.9, radius_y: 0.5, radius_z: 0.7 });
+      assert!(!primitive.is_exact());
+      for p in grid() {
+         let (evaluated, exact) = (eval_primitive(primitive, p), ellipsoid_distance(p, radius));
+         assert!(evaluated <= exact + 1e-5, "at {p:?}: {evaluated} > {exact}");
+         assert_eq!(evaluated < 0.0, exact < 0.0, "{p:?}");
+      }
+   }
+
+   #[test]
+   fn normals_are_unit_length() {
+      let scene = Scene::demo();
+      for p in points() {
+         assert_close(normal(&scene, p).magnitude(), 1.0, 1e-5);
+      }
+   }
+
+   #[test]
+   fn drawn_solo_matches_the_soloed_copy() {
+      let scene = Scene::demo();
+      for (id, _) in scene.iter() {
+         let soloed = scene.soloed(id);
+         for p in points() {
+            assert_eq!(eval_drawn(&scene, Some(id), p), eval_surface(&soloed, p), "{id:?} at {p:?}");
+         }
+      }
+   }
+}
@@ -0,0 +1,190 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::scene::graph::Scene;
+
+/// bump whenever the scene layout changes in a way `#[serde(default)]` can't absorb, and add a `migrate` arm
+pub const FORMAT_VERSION: u32 = 2;
+
+pub const EXTENSION: &str = "json";
+
+
+#[derive(Debug)]
+pub enum FileError {
+   Io(std::io::Error),
+   Parse(serde_json::Error),
+   MissingVersion,
+   /// written by a newer build, or by a version we no longer know how to migrate
+   UnsupportedVersion { found: u32 },
+   Invalid(String),
+}
+impl Display for FileError {
+   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+      match self {
+         FileError::Io(error) => write!(f, "{error}"),
+         FileError::Parse(error) => write!(f, "failed to parse scene file: {error}"),
+         FileError::MissingVersion => write!(f, "scene file has no version field"),
+         FileError::UnsupportedVersion { found } => write!(
+            f, "scene file is version {found}, this build reads up to version {FORMAT_VERSION}"
+         ),
+         FileError::Invalid(reason) => write!(f, "scene file is inconsistent: {reason}"),
+      }
+   }
+}
+impl std::error::Error for FileError {}
+
+impl From<std::io::Error> for FileError {
+   fn from(error: std::io::Error) -> Self {
+      FileError::Io(error)
+   }
+}
+
+impl From<serde_json::Error> for FileError {
+   fn from(error: serde_json::Error) -> Self {
+      FileError::Parse(error)
+   }
+}
+
+
+#[derive(Serialize)]
+struct SceneFileRef<'a> {
+   version: u32,
+   scene: &'a Scene,
+}
+
+#[derive(Deserialize)]
+struct SceneFile {
+   #[allow(dead_code)]
+   version: u32,
+   scene: Scene,
+}
+
+
+/// pretty printed json, stable between saves so files diff nicely
+pub fn to_string(scene: &Scene) -> Result<String, FileError> {
+   Ok(serde_json::to_string_pretty(&SceneFileRef { version: FORMAT_VERSION, scene })?)
+}
+
+/// the version a scene file was written as, without reading the scene
+pub fn version(source: &str) -> Result<u32, FileError> {
+   version_of(&serde_json::from_str(source)?)
+}
+
+fn version_of(file: &Value) -> Result<u32, FileError> {
+   file.get("version")
+       .and_then(Value::as_u64)
+       .map(|version| version as u32)
+       .ok_or(FileError::MissingVersion)
+}
+
+/// parses and migrates a scene file of any supported version
+pub fn from_str(source: &str) -> Result<Scene, FileError> {
+   let mut file: Value = serde_json::from_str(source)?;
+
+   let mut version = version_of(&file)?;
+   if version > FORMAT_VERSION {
+      return Err(FileError::UnsupportedVersion { found: version });
+   }
+
+   while version < FORMAT_VERSION {
+      migrate(version, &mut file)?;
+      version += 1;
+      file["version"] = Value::from(version);
+   }
+
+   let file: SceneFile = serde_json::from_value(file)?;
+   file.scene.validate().map_err(FileError::Invalid)?;
+   Ok(file.scene)
+}
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` file to `n + 2` in place, working on raw json so old layouts
+/// don't need to stay in the code
+const MIGRATIONS: &[Migration] = &[
+   light_direction_to_array,
+];
+
+type Migration = fn(&mut Value) -> Result<(), FileError>;
+
+/// 1 -> 2, light directions went from `{ x, y, z }` to `[x, y, z]`
+fn light_direction_to_array(file: &mut Value) -> Result<(), FileError> {
+   let Some(lights) = file.pointer_mut("/scene/lights").and_then(Value::as_array_mut) else { return Ok(()) };
+   for light in lights {
+      let Some(direction) = light.get_mut("direction") else { continue };
+      if let Some(vector) = direction.as_object() {
+         let axis = |name: &str| vector.get(name).cloned().unwrap_or(Value::from(0.0));
+         *direction = Value::Array(vec![axis("x"), axis("y"), axis("z")]);
+      }
+   }
+   Ok(())
+}
+
+fn migrate(from: u32, file: &mut Value) -> Result<(), FileError> {
+   let migration = from.checked_sub(1)
+       .and_then(|index| MIGRATIONS.get(index as usize))
+       .ok_or(FileError::UnsupportedVersion { found: from })?;
+   migration(file)
+}
+
+pub fn save(path: &Path, scene: &Scene) -> Result<(), FileError> {
+   std::fs::write(path, to_string(scene)?)?;
+   Ok(())
+}
+
+pub fn load(path: &Path) -> Result<Scene, FileError> {
+   from_str(&std::fs::read_to_string(path)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::node::NodeId;
+
+   #[test]
+   fn round_trip_keeps_the_scene_and_its_ids() {
+      let mut scene = Scene::demo();
+      // leave a gap in the ids, they're map keys and must come back as the same numbers
+      let (removed, _) = scene.iter().find(|(_, node)| node.name == "Torus").unwrap();
+      scene.remove(removed).unwrap();
+
+      let loaded = from_str(&to_string(&scene).unwrap()).unwrap();
+      assert_eq!(loaded, scene);
+      let ids = |scene: &Scene| scene.iter().map(|(id, _)| id).collect::<Vec<NodeId>>();
+      assert_eq!(ids(&loaded), ids(&scene));
+      assert!(!loaded.contains(removed));
+   }
+
+   #[test]
+   fn version_1_migrates_to_version_2() {
+      let scene = Scene::demo();
+      let mut file: Value = serde_json::from_str(&to_string(&scene).unwrap()).unwrap();
+      file["version"] = Value::from(1);
+      for light in file.pointer_mut("/scene/lights").and_then(Value::as_array_mut).unwrap() {
+         let [x, y, z] = [0, 1, 2].map(|i| light["direction"][i].clone());
+         light["direction"] = serde_json::json!({ "x": x, "y": y, "z": z });
+      }
+
+      let source = serde_json::to_string(&file).unwrap();
+      assert_eq!(version(&source).unwrap(), 1);
+      assert_eq!(from_str(&source).unwrap(), scene);
+   }
+
+   #[test]
+   fn light_parameters_are_stored_flat() {
+      let file: Value = serde_json::from_str(&to_string(&Scene::demo()).unwrap()).unwrap();
+      let light = file.pointer("/scene/lights/0").unwrap();
+      assert!(light.get("color").is_some() && light.get("intensity").is_some());
+      let lighting = file.pointer("/scene/lighting").unwrap();
+      for key in ["normal_epsilon", "penumbra", "shadow_steps", "occlusion_strength", "occlusion_distance", "occlusion_steps"] {
+         assert!(lighting.get(key).is_some(), "{key} missing");
+      }
+   }
+
+   #[test]
+   fn newer_and_unversioned_files_are_rejected() {
+      let newer = to_string(&Scene::new()).unwrap().replacen(&format!("\"version\": {FORMAT_VERSION}"), "\"version\": 99", 1);
+      assert!(matches!(from_str(&newer), Err(FileError::UnsupportedVersion { found: 99 })));
+      assert!(matches!(from_str("{ \"scene\": {} }"), Err(FileError::MissingVersion)));
+   }
+}
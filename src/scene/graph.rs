@@ -0,0 +1,517 @@
+use std::collections::BTreeMap;
+use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
+use crate::scene::camera::Camera;
+use crate::scene::domain::{DomainOp, TwistParams};
+use crate::scene::light::{Light, LightKind, Lighting};
+use crate::scene::material::{Material, MaterialId};
+use crate::scene::node::{Frame, Node, NodeId, NodeKind, Transform};
+use crate::scene::operators::{Blend, BooleanOp, Operation};
+use crate::scene::primitives::{BoxParams, PlaneParams, Primitive, SphereParams, TorusParams};
+use crate::scene::settings::RenderSettings;
+
+/// The full sdf scene, the node tree is stored flat and linked through ids
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+   nodes: BTreeMap<NodeId, Node>,
+   root: NodeId,
+   next_id: u32,
+
+   pub materials: Vec<Material>,
+   pub camera: Camera,
+   pub lights: Vec<Light>,
+   /// files from before shading options existed get the defaults
+   #[serde(default)]
+   pub lighting: Lighting,
+   pub settings: RenderSettings,
+}
+/// a node and everything below it, cut out of a scene by `Scene::detach`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Subtree {
+   pub parent: NodeId,
+   /// position in the parent's children
+   pub index: usize,
+   /// the subtree root comes first
+   pub nodes: Vec<(NodeId, Node)>,
+}
+impl Subtree {
+   pub fn root(&self) -> Option<NodeId> {
+      self.nodes.first().map(|(id, _)| *id)
+   }
+}
+
+impl Default for Scene {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl Scene {
+   /// empty scene with only a root group
+   pub fn new() -> Self {
+      let root = NodeId(0);
+      let mut nodes = BTreeMap::new();
+      nodes.insert(root, Node::group("Root"));
+
+      Self {
+         nodes,
+         root,
+         next_id: 1,
+         materials: vec![Material::default()],
+         camera: Camera::default(),
+         lights: vec![Light::default()],
+         lighting: Lighting::default(),
+         settings: RenderSettings::default(),
+      }
+   }
+
+   /// small scene that touches every node kind
+   pub fn demo() -> Self {
+      let mut scene = Self::new();
+      let root = scene.root();
+
+      let red = scene.add_material(Material::new("Red", [0.85, 0.2, 0.15]));
+      let blue = scene.add_material(Material::new("Blue", [0.2, 0.4, 0.85]).with_surface(0.0, 0.25));
+      let gold = scene.add_material(Material::new("Gold", [1.0, 0.77, 0.34]).with_surface(1.0, 0.3));
+      scene.lights.push(Light {
+         position: [-3.0, 2.0, -2.0],
+         color: [0.6, 0.75, 1.0],
+         intensity: 8.0,
+         ..Light::new(LightKind::Point)
+      });
+
+      let cut = scene.insert(root, Node::operation("Cut", Operation::subtraction())).unwrap();
+      scene.insert(cut, Node::primitive("Box", Primitive::Box(BoxParams { half_x: 0.75, half_y: 0.75, half_z: 0.75 }))
+          .with_material(red)).unwrap();
+      scene.insert(cut, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 1.0 }))
+          .with_material(red)).unwrap();
+
+      scene.insert(root, Node::primitive("Torus", Primitive::Torus(TorusParams { major_radius: 0.8, minor_radius: 0.2 }))
+          .with_transform(Transform {
+             position: Vector3::new(2.0, 0.0, 0.0),
+             rotation: Vector3::new(90.0, 0.0, 0.0),
+             scale: 1.0,
+          })
+          .with_material(blue)).unwrap();
+
+      let blob = scene.insert(root, Node::operation("Blob", Operation::blended(BooleanOp::Union, Blend::Smooth, 0.4))
+          .with_transform(Transform::from_position(Vector3::new(-2.0, -0.25, 0.0)))).unwrap();
+      scene.insert(blob, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 0.6 }))
+          .with_material(blue)).unwrap();
+      scene.insert(blob, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 0.4 }))
+          .with_transform(Transform::from_position(Vector3::new(0.0, 0.8, 0.0)))
+          .with_material(red)).unwrap();
+
+      let twist = scene.insert(root, Node::domain("Twist", DomainOp::Twist(TwistParams { strength: 1.2 }))
+          .with_transform(Transform::from_position(Vector3::new(0.0, 0.0, 2.5)))).unwrap();
+      scene.insert(twist, Node::primitive("Box", Primitive::Box(BoxParams { half_x: 0.3, half_y: 0.9, half_z: 0.3 }))
+          .with_material(gold)).unwrap();
+
+      scene.insert(root, Node::primitive("Ground", Primitive::Plane(PlaneParams { height: -1.0 }))).unwrap();
+
+      scene
+   }
+
+   pub fn root(&self) -> NodeId {
+      self.root
+   }
+
+   pub fn node(&self, id: NodeId) -> Option<&Node> {
+      self.nodes.get(&id)
+   }
+
+   pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+      self.nodes.get_mut(&id)
+   }
+
+   pub fn contains(&self, id: NodeId) -> bool {
+      self.nodes.contains_key(&id)
+   }
+
+   pub fn len(&self) -> usize {
+      self.nodes.len()
+   }
+
+   pub fn is_empty(&self) -> bool {
+      self.nodes.len() <= 1
+   }
+
+   /// every node in id order, the root included
+   pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+      self.nodes.iter().map(|(id, node)| (*id, node))
+   }
+
+   pub fn children(&self, id: NodeId) -> &[NodeId] {
+      self.nodes.get(&id).map(|node| node.children.as_slice()).unwrap_or(&[])
+   }
+
+   /// depth first, parents before children, starting with `id` itself
+   pub fn descendants(&self, id: NodeId) -> Vec<NodeId> {
+      let mut out = vec![];
+      let mut stack = vec![id];
+      while let Some(current) = stack.pop() {
+         if let Some(node) = self.nodes.get(&current) {
+            out.push(current);
+            stack.extend(node.children.iter().rev());
+         }
+      }
+      out
+   }
+
+   pub fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+      let mut current = self.node(id).and_then(|node| node.parent);
+      while let Some(parent) = current {
+         if parent == ancestor {
+            return true;
+         }
+         current = self.node(parent).and_then(|node| node.parent);
+      }
+      false
+   }
+
+   /// adds `node` as the last child of `parent`, returns None if the parent can't hold children
+   pub fn insert(&mut self, parent: NodeId, node: Node) -> Option<NodeId> {
+      let index = self.children(parent).len();
+      self.insert_at(parent, index, node)
+   }
+
+   pub fn insert_at(&mut self, parent: NodeId, index: usize, mut node: Node) -> Option<NodeId> {
+      let parent_node = self.nodes.get_mut(&parent)?;
+      if !parent_node.kind.accepts_children() {
+         return None;
+      }
+
+      let id = NodeId(self.next_id);
+      self.next_id += 1;
+
+      let index = index.min(parent_node.children.len());
+      parent_node.children.insert(index, id);
+
+      node.parent = Some(parent);
+      node.children.clear();
+      self.nodes.insert(id, node);
+      Some(id)
+   }
+
+   /// removes a node and its whole subtree, the root can't be removed
+   pub fn remove(&mut self, id: NodeId) -> Option<Node> {
+      if id == self.root {
+         return None;
+      }
+
+      let removed_ids = self.descendants(id);
+      let node = self.nodes.get(&id)?.clone();
+
+      if let Some(parent) = node.parent.and_then(|parent| self.nodes.get_mut(&parent)) {
+         parent.children.retain(|child| *child != id);
+      }
+
+      for removed in removed_ids {
+         self.nodes.remove(&removed);
+      }
+
+      Some(node)
+   }
+
+   /// takes a node and its subtree out of the scene, keeping the ids so `attach` can put it back exactly
+   pub fn detach(&mut self, id: NodeId) -> Option<Subtree> {
+      if id == self.root {
+         return None;
+      }
+      let parent = self.nodes.get(&id)?.parent?;
+      let index = self.children(parent).iter().position(|child| *child == id)?;
+
+      let nodes = self.descendants(id).into_iter()
+          .filter_map(|id| self.nodes.remove(&id).map(|node| (id, node)))
+          .collect();
+      self.nodes.get_mut(&parent).unwrap().children.remove(index);
+
+      Some(Subtree { parent, index, nodes })
+   }
+
+   /// reinserts a detached subtree, refuses if its parent is gone or any of its ids are taken
+   pub fn attach(&mut self, subtree: Subtree) -> bool {
+      let Some((root, _)) = subtree.nodes.first() else { return false };
+      let root = *root;
+      match self.nodes.get(&subtree.parent) {
+         Some(parent) if parent.kind.accepts_children() => {}
+         _ => return false,
+      }
+      if subtree.nodes.iter().any(|(id, _)| self.nodes.contains_key(id)) {
+         return false;
+      }
+
+      let parent = self.nodes.get_mut(&subtree.parent).unwrap();
+      let index = subtree.index.min(parent.children.len());
+      parent.children.insert(index, root);
+
+      for (id, mut node) in subtree.nodes {
+         if id == root {
+            node.parent = Some(subtree.parent);
+         }
+         self.next_id = self.next_id.max(id.0 + 1);
+         self.nodes.insert(id, node);
+      }
+      true
+   }
+
+   /// moves `id` under `new_parent` at `index`, refuses moves that would create a cycle
+   pub fn reparent(&mut self, id: NodeId, new_parent: NodeId, index: usize) -> bool {
+      if id == self.root || id == new_parent || self.is_ancestor(id, new_parent) {
+         return false;
+      }
+      match self.nodes.get(&new_parent) {
+         Some(parent) if parent.kind.accepts_children() => {}
+         _ => return false,
+      }
+      let Some(old_parent) = self.nodes.get(&id).and_then(|node| node.parent) else { return false };
+
+      let mut index = index;
+      if let Some(parent) = self.nodes.get_mut(&old_parent) {
+         if let Some(old_index) = parent.children.iter().position(|child| *child == id) {
+            parent.children.remove(old_index);
+            // removing from the same list shifts everything after it down by one
+            if old_parent == new_parent && old_index < index {
+               index -= 1;
+            }
+         }
+      }
+
+      let parent = self.nodes.get_mut(&new_parent).unwrap();
+      let index = index.min(parent.children.len());
+      parent.children.insert(index, id);
+      self.nodes.get_mut(&id).unwrap().parent = Some(new_parent);
+      true
+   }
+
+   pub fn add_material(&mut self, material: Material) -> MaterialId {
+      self.materials.push(material);
+      MaterialId(self.materials.len() - 1)
+   }
+
+   /// falls back to the first material for dangling ids
+   pub fn material(&self, id: MaterialId) -> &Material {
+      self.materials.get(id.0)
+          .or_else(|| self.materials.first())
+          .expect("scene has no materials")
+   }
+
+   /// where `id`'s local space sits in the world, domain warps along the way are ignored
+   pub fn world_frame(&self, id: NodeId) -> Frame {
+      let Some(node) = self.node(id) else { return Frame::default() };
+      self.parent_frame(id).then(&node.transform)
+   }
+
+   /// the space `id`'s transform is relative to
+   pub fn parent_frame(&self, id: NodeId) -> Frame {
+      match self.node(id).and_then(|node| node.parent) {
+         Some(parent) => self.world_frame(parent),
+         None => Frame::default(),
+      }
+   }
+
+   /// true if the node and all of its parents are visible
+   pub fn is_visible(&self, id: NodeId) -> bool {
+      let mut current = Some(id);
+      while let Some(node) = current.and_then(|id| self.node(id)) {
+         if !node.visible {
+            return false;
+         }
+         current = node.parent;
+      }
+      true
+   }
+
+   /// checks the links between nodes, used on anything that didn't come from the editing methods
+   pub fn validate(&self) -> Result<(), String> {
+      let root = self.nodes.get(&self.root).ok_or("root node is missing")?;
+      if root.parent.is_some() {
+         return Err("root node has a parent".into());
+      }
+      if self.materials.is_empty() {
+         return Err("scene has no materials".into());
+      }
+
+      for (id, node) in &self.nodes {
+         if id.0 >= self.next_id {
+            return Err(format!("node {} is past the next free id", id.0));
+         }
+         if *id != self.root {
+            let parent = node.parent.and_then(|parent| self.nodes.get(&parent));
+            if !parent.is_some_and(|parent| parent.children.contains(id)) {
+               return Err(format!("node {} is not a child of its parent", id.0));
+            }
+         }
+         if !node.children.is_empty() && !node.kind.accepts_children() {
+            return Err(format!("node {} can't have children", id.0));
+         }
+         for child in &node.children {
+            if self.nodes.get(child).and_then(|child| child.parent) != Some(*id) {
+               return Err(format!("node {} lists {} as a child", id.0, child.0));
+            }
+         }
+      }
+
+      // every node reachable exactly once rules out cycles and shared children
+      if self.descendants(self.root).len() != self.nodes.len() {
+         return Err("node tree has cycles or unreachable nodes".into());
+      }
+      Ok(())
+   }
+
+   /// copy that only draws `id`, its ancestors turn into plain groups so the rest of the tree can be hidden
+   pub fn soloed(&self, id: NodeId) -> Scene {
+      let mut scene = self.clone();
+      if !self.contains(id) {
+         return scene;
+      }
+
+      let keep = self.descendants(id);
+      let mut ancestors = vec![];
+      let mut current = self.node(id).and_then(|node| node.parent);
+      while let Some(parent) = current {
+         ancestors.push(parent);
+         current = self.node(parent).and_then(|node| node.parent);
+      }
+
+      for (node_id, node) in scene.nodes.iter_mut() {
+         if ancestors.contains(node_id) {
+            // domain warps still shape the soloed node, booleans would need the hidden siblings
+            if matches!(node.kind, NodeKind::Operation(_)) {
+               node.kind = NodeKind::Group;
+            }
+         } else if !keep.contains(node_id) {
+            node.visible = false;
+         }
+      }
+      scene
+   }
+
+   /// the node and what it's drawn as, none if it's hidden
+   ///
+   /// with `solo` set only that subtree is drawn, the same as drawing `soloed(solo)` without the copy
+   pub fn drawn(&self, id: NodeId, solo: Option<NodeId>) -> Option<(&Node, &NodeKind)> {
+      let node = self.node(id).filter(|node| node.visible)?;
+      let Some(solo) = solo.filter(|solo| self.contains(*solo)) else {
+         return Some((node, &node.kind));
+      };
+
+      if id == solo || self.is_ancestor(solo, id) {
+         Some((node, &node.kind))
+      } else if self.is_ancestor(id, solo) {
+         match node.kind {
+            NodeKind::Operation(_) => Some((node, &NodeKind::Group)),
+            _ => Some((node, &node.kind)),
+         }
+      } else {
+         None
+      }
+   }
+
+   /// factor the raymarcher scales its steps by, the tightest product of warp and bound factors along any drawn path
+   pub fn step_scale(&self, solo: Option<NodeId>) -> f32 {
+      self.path_step_scale(self.root, solo).clamp(0.05, 1.0)
+   }
+
+   fn path_step_scale(&self, id: NodeId, solo: Option<NodeId>) -> f32 {
+      let Some((node, kind)) = self.drawn(id, solo) else { return 1.0 };
+      let own = match kind {
+         NodeKind::Domain(domain) => domain.step_scale(),
+         NodeKind::Primitive(primitive) => primitive.step_scale(),
+         _ => 1.0,
+      };
+      let children = node.children.iter()
+          .map(|child| self.path_step_scale(*child, solo))
+          .fold(1.0, f32::min);
+      own * children
+   }
+
+   /// counts primitives, handy for stats and for sizing gpu buffers
+   pub fn primitive_count(&self) -> usize {
+      self.nodes.values().filter(|node| matches!(node.kind, NodeKind::Primitive(_))).count()
+   }
+}
+
+
+#[cfg(test)]
+impl Scene {
+   /// a scene for every primitive, boolean and blend, and domain warp, each node placed off the origin
+   /// so transforms are exercised too
+   pub fn one_of_each() -> Vec<(String, Scene)> {
+      let moved = Transform {
+         position: Vector3::new(0.3, -0.2, 0.1),
+         rotation: Vector3::new(20.0, 35.0, -10.0),
+         scale: 1.25,
+      };
+      let pair = |scene: &mut Scene, parent: NodeId| {
+         scene.insert(parent, Node::primitive("Box", Primitive::Box(BoxParams { half_x: 0.6, half_y: 0.5, half_z: 0.4 }))).unwrap();
+         scene.insert(parent, Node::primitive("Sphere", Primitive::Sphere(SphereParams { radius: 0.6 }))
+             .with_transform(Transform::from_position(Vector3::new(0.5, 0.3, 0.0)))).unwrap();
+      };
+      let mut scenes = vec![];
+
+      for primitive in Primitive::all() {
+         let mut scene = Scene::new();
+         let root = scene.root();
+         scene.insert(root, Node::primitive(primitive.name(), primitive).with_transform(moved)).unwrap();
+         scenes.push((primitive.name().to_string(), scene));
+      }
+
+      for boolean in BooleanOp::ALL {
+         for blend in Blend::ALL {
+            let operation = Operation::blended(boolean, blend, 0.3);
+            let mut scene = Scene::new();
+            let root = scene.root();
+            let node = scene.insert(root, Node::operation(&operation.name(), operation).with_transform(moved)).unwrap();
+            pair(&mut scene, node);
+            scenes.push((operation.name(), scene));
+         }
+      }
+
+      for domain in DomainOp::all() {
+         let mut scene = Scene::new();
+         let root = scene.root();
+         let node = scene.insert(root, Node::domain(domain.name(), domain).with_transform(moved)).unwrap();
+         pair(&mut scene, node);
+         scenes.push((domain.name().to_string(), scene));
+      }
+
+      let mut scene = Scene::new();
+      let root = scene.root();
+      let node = scene.insert(root, Node::group("Group").with_transform(moved)).unwrap();
+      pair(&mut scene, node);
+      scenes.push(("Group".to_string(), scene));
+
+      scenes
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::{bytecode, compiler};
+
+   #[test]
+   fn drawing_a_solo_matches_the_soloed_copy() {
+      let scene = Scene::demo();
+      for (id, _) in scene.iter() {
+         let soloed = scene.soloed(id);
+         assert_eq!(compiler::compile_map(&scene, Some(id)), compiler::compile_map(&soloed, None));
+         assert_eq!(bytecode::flatten(&scene, Some(id)), bytecode::flatten(&soloed, None));
+         assert_eq!(scene.step_scale(Some(id)), soloed.step_scale(None));
+      }
+   }
+
+   #[test]
+   fn only_warps_and_bounds_shorten_the_steps() {
+      let scene = Scene::demo();
+      assert_eq!(scene.step_scale(None), 1.0 / 2.2);
+      let find = |name: &str| scene.iter().find(|(_, node)| node.name == name).unwrap().0;
+      assert_eq!(scene.step_scale(Some(find("Cut"))), 1.0);
+      assert_eq!(scene.step_scale(Some(find("Twist"))), 1.0 / 2.2);
+
+      let mut scene = Scene::new();
+      let root = scene.root();
+      scene.insert(root, Node::primitive("Ellipsoid", Primitive::Ellipsoid(Default::default()))).unwrap();
+      assert_eq!(scene.step_scale(None), 0.8);
+   }
+}
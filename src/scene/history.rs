@@ -0,0 +1,501 @@
+use crate::scene::graph::{Scene, Subtree};
+use crate::scene::light::{Light, Lighting};
+use crate::scene::material::Material;
+use crate::scene::node::{Node, NodeId};
+use crate::scene::settings::RenderSettings;
+
+/// undo steps kept before the oldest ones are dropped
+pub const DEFAULT_DEPTH: usize = 128;
+
+
+/// one reversible scene mutation, every edit made from the ui goes through one of these
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+   /// only the node's own fields are swapped, its parent and children links are left alone
+   EditNode { id: NodeId, before: Node, after: Node },
+   Insert { subtree: Subtree },
+   Remove { subtree: Subtree },
+   /// (parent, index) pairs, the index is the final position in the parent's children
+   Reparent { id: NodeId, from: (NodeId, usize), to: (NodeId, usize) },
+   EditMaterial { index: usize, before: Material, after: Material },
+   AddMaterial { material: Material },
+   EditLight { index: usize, before: Light, after: Light },
+   AddLight { index: usize, light: Light },
+   RemoveLight { index: usize, light: Light },
+   EditLighting { before: Lighting, after: Lighting },
+   EditSettings { before: RenderSettings, after: RenderSettings },
+   /// several commands undone and redone as one step
+   Batch { name: String, commands: Vec<Command> },
+}
+impl Command {
+   pub fn name(&self) -> String {
+      match self {
+         Command::EditNode { after, .. } => format!("Edit {}", after.name),
+         Command::Insert { subtree } => format!("Add {}", subtree_name(subtree)),
+         Command::Remove { subtree } => format!("Delete {}", subtree_name(subtree)),
+         Command::Reparent { .. } => "Move node".to_string(),
+         Command::EditMaterial { after, .. } => format!("Edit material {}", after.name),
+         Command::AddMaterial { material } => format!("Add material {}", material.name),
+         Command::EditLight { .. } => "Edit light".to_string(),
+         Command::AddLight { light, .. } => format!("Add {} light", light.kind.name().to_lowercase()),
+         Command::RemoveLight { light, .. } => format!("Delete {} light", light.kind.name().to_lowercase()),
+         Command::EditLighting { .. } => "Edit lighting".to_string(),
+         Command::EditSettings { .. } => "Edit render settings".to_string(),
+         Command::Batch { name, .. } => name.clone(),
+      }
+   }
+
+   pub fn apply(&self, scene: &mut Scene) -> bool {
+      match self {
+         Command::EditNode { id, after, .. } => set_node(scene, *id, after),
+         Command::Insert { subtree } => scene.attach(subtree.clone()),
+         Command::Remove { subtree } => subtree.root().and_then(|root| scene.detach(root)).is_some(),
+         Command::Reparent { id, to, .. } => place(scene, *id, *to),
+         Command::EditMaterial { index, after, .. } => set_at(&mut scene.materials, *index, after),
+         Command::AddMaterial { material } => {
+            scene.add_material(material.clone());
+            true
+         }
+         Command::EditLight { index, after, .. } => set_at(&mut scene.lights, *index, after),
+         Command::AddLight { index, light } => insert_at(&mut scene.lights, *index, light),
+         Command::RemoveLight { index, light } => remove_at(&mut scene.lights, *index, light),
+         Command::EditLighting { after, .. } => {
+            scene.lighting = *after;
+            true
+         }
+         Command::EditSettings { after, .. } => {
+            scene.settings = *after;
+            true
+         }
+         Command::Batch { commands, .. } => apply_all(scene, commands),
+      }
+   }
+
+   pub fn revert(&self, scene: &mut Scene) -> bool {
+      match self {
+         Command::EditNode { id, before, .. } => set_node(scene, *id, before),
+         Command::Insert { subtree } => subtree.root().and_then(|root| scene.detach(root)).is_some(),
+         Command::Remove { subtree } => scene.attach(subtree.clone()),
+         Command::Reparent { id, from, .. } => place(scene, *id, *from),
+         Command::EditMaterial { index, before, .. } => set_at(&mut scene.materials, *index, before),
+         // the first material is the fallback and is never removed
+         Command::AddMaterial { .. } => scene.materials.len() > 1 && scene.materials.pop().is_some(),
+         Command::EditLight { index, before, .. } => set_at(&mut scene.lights, *index, before),
+         Command::AddLight { index, light } => remove_at(&mut scene.lights, *index, light),
+         Command::RemoveLight { index, light } => insert_at(&mut scene.lights, *index, light),
+         Command::EditLighting { before, .. } => {
+            scene.lighting = *before;
+            true
+         }
+         Command::EditSettings { before, .. } => {
+            scene.settings = *before;
+            true
+         }
+         Command::Batch { commands, .. } => revert_all(scene, commands),
+      }
+   }
+
+   /// folds `next` into `self` if both edit the same thing, so a slider drag becomes one step
+   pub fn merge(&mut self, next: &Command) -> bool {
+      match (self, next) {
+         (Command::EditNode { id, after, .. }, Command::EditNode { id: next_id, after: next_after, .. })
+         if id == next_id => *after = next_after.clone(),
+
+         (Command::EditMaterial { index, after, .. }, Command::EditMaterial { index: next_index, after: next_after, .. })
+         if index == next_index => *after = next_after.clone(),
+
+         (Command::EditLight { index, after, .. }, Command::EditLight { index: next_index, after: next_after, .. })
+         if index == next_index => *after = *next_after,
+
+         (Command::EditLighting { after, .. }, Command::EditLighting { after: next_after, .. }) => *after = *next_after,
+
+         (Command::EditSettings { after, .. }, Command::EditSettings { after: next_after, .. }) => *after = *next_after,
+
+         _ => return false,
+      }
+      true
+   }
+
+   fn is_edit(&self) -> bool {
+      matches!(self,
+         Command::EditNode { .. } | Command::EditMaterial { .. } | Command::EditLight { .. }
+         | Command::EditLighting { .. } | Command::EditSettings { .. }
+      )
+   }
+}
+
+/// all or nothing, if one fails the ones before it are reverted so a batch never lands half done
+fn apply_all(scene: &mut Scene, commands: &[Command]) -> bool {
+   for (i, command) in commands.iter().enumerate() {
+      if !command.apply(scene) {
+         for done in commands[..i].iter().rev() {
+            done.revert(scene);
+         }
+         return false;
+      }
+   }
+   true
+}
+
+/// `apply_all` backwards
+fn revert_all(scene: &mut Scene, commands: &[Command]) -> bool {
+   for (i, command) in commands.iter().enumerate().rev() {
+      if !command.revert(scene) {
+         for done in &commands[i + 1..] {
+            done.apply(scene);
+         }
+         return false;
+      }
+   }
+   true
+}
+
+fn subtree_name(subtree: &Subtree) -> &str {
+   subtree.nodes.first().map(|(_, node)| node.name.as_str()).unwrap_or("node")
+}
+
+fn set_node(scene: &mut Scene, id: NodeId, value: &Node) -> bool {
+   let Some(node) = scene.node_mut(id) else { return false };
+   node.name.clone_from(&value.name);
+   node.kind = value.kind.clone();
+   node.transform = value.transform;
+   node.material = value.material;
+   node.visible = value.visible;
+   true
+}
+
+fn set_at<T: Clone>(items: &mut [T], index: usize, value: &T) -> bool {
+   let Some(item) = items.get_mut(index) else { return false };
+   *item = value.clone();
+   true
+}
+
+fn insert_at<T: Clone>(items: &mut Vec<T>, index: usize, value: &T) -> bool {
+   if index > items.len() {
+      return false;
+   }
+   items.insert(index, value.clone());
+   true
+}
+
+/// only removes `value` if it is still what sits at `index`
+fn remove_at<T: PartialEq>(items: &mut Vec<T>, index: usize, value: &T) -> bool {
+   if items.get(index) != Some(value) {
+      return false;
+   }
+   items.remove(index);
+   true
+}
+
+/// moves `id` so it ends up at exactly `index` in `parent`, undoing the shift `Scene::reparent` applies
+fn place(scene: &mut Scene, id: NodeId, (parent, index): (NodeId, usize)) -> bool {
+   let current = scene.children(parent).iter().position(|child| *child == id);
+   match current {
+      Some(current) if current < index => scene.reparent(id, parent, index + 1),
+      _ => scene.reparent(id, parent, index),
+   }
+}
+
+
+/// undo and redo stacks, commands are recorded after they have been applied to the scene
+#[derive(Clone, Debug)]
+pub struct History {
+   undo: Vec<Command>,
+   redo: Vec<Command>,
+   depth: usize,
+   /// while set, an edit of the same target as the last one is merged into it, cleared by `seal`
+   open: bool,
+}
+impl Default for History {
+   fn default() -> Self {
+      Self::new(DEFAULT_DEPTH)
+   }
+}
+impl History {
+   pub fn new(depth: usize) -> Self {
+      Self {
+         undo: vec![],
+         redo: vec![],
+         depth: depth.max(1),
+         open: false,
+      }
+   }
+
+   /// applies `command` and records it, returns false and records nothing if it didn't apply
+   pub fn execute(&mut self, scene: &mut Scene, command: Command) -> bool {
+      if !command.apply(scene) {
+         return false;
+      }
+      self.record(command);
+      true
+   }
+
+   /// records a command the caller already applied, like a widget writing straight into the scene
+   pub fn record(&mut self, command: Command) {
+      self.redo.clear();
+
+      if self.open {
+         if let Some(last) = self.undo.last_mut() {
+            if last.merge(&command) {
+               return;
+            }
+         }
+      }
+
+      self.open = command.is_edit();
+      self.undo.push(command);
+      if self.undo.len() > self.depth {
+         let excess = self.undo.len() - self.depth;
+         self.undo.drain(..excess);
+      }
+   }
+
+   /// ends the current merge, call it once the pointer is released
+   pub fn seal(&mut self) {
+      self.open = false;
+   }
+
+   /// a command that no longer reverts doesn't match the scene any more, so it's dropped rather than offered as a redo
+   pub fn undo(&mut self, scene: &mut Scene) -> bool {
+      self.seal();
+      let Some(command) = self.undo.pop() else { return false };
+      let reverted = command.revert(scene);
+      if reverted {
+         self.redo.push(command);
+      }
+      reverted
+   }
+
+   /// like `undo`, a command that no longer applies is dropped
+   pub fn redo(&mut self, scene: &mut Scene) -> bool {
+      self.seal();
+      let Some(command) = self.redo.pop() else { return false };
+      let applied = command.apply(scene);
+      if applied {
+         self.undo.push(command);
+      }
+      applied
+   }
+
+   pub fn clear(&mut self) {
+      self.undo.clear();
+      self.redo.clear();
+      self.open = false;
+   }
+
+   pub fn can_undo(&self) -> bool {
+      !self.undo.is_empty()
+   }
+
+   pub fn can_redo(&self) -> bool {
+      !self.redo.is_empty()
+   }
+
+   pub fn undo_name(&self) -> Option<String> {
+      self.undo.last().map(Command::name)
+   }
+
+   pub fn redo_name(&self) -> Option<String> {
+      self.redo.last().map(Command::name)
+   }
+
+   pub fn undo_len(&self) -> usize {
+      self.undo.len()
+   }
+
+   pub fn redo_len(&self) -> usize {
+      self.redo.len()
+   }
+
+   pub fn depth(&self) -> usize {
+      self.depth
+   }
+
+
+   /// everything `edit` records lands as a single undo step called `name`
+   pub fn batch<R>(&mut self, name: &str, edit: impl FnOnce(&mut History) -> R) -> R {
+      let mut inner = History::new(usize::MAX);
+      let result = edit(&mut inner);
+      if !inner.undo.is_empty() {
+         self.record(Command::Batch { name: name.to_string(), commands: inner.undo });
+      }
+      result
+   }
+
+
+   // helpers for the structural edits, they return what the plain `Scene` methods would
+
+   pub fn insert(&mut self, scene: &mut Scene, parent: NodeId, index: usize, node: Node) -> Option<NodeId> {
+      let id = scene.insert_at(parent, index, node)?;
+      let index = scene.children(parent).iter().position(|child| *child == id)?;
+      let node = scene.node(id)?.clone();
+      self.record(Command::Insert { subtree: Subtree { parent, index, nodes: vec![(id, node)] } });
+      Some(id)
+   }
+
+   pub fn remove(&mut self, scene: &mut Scene, id: NodeId) -> bool {
+      let Some(subtree) = scene.detach(id) else { return false };
+      self.record(Command::Remove { subtree });
+      true
+   }
+
+   pub fn reparent(&mut self, scene: &mut Scene, id: NodeId, new_parent: NodeId, index: usize) -> bool {
+      let Some(old_parent) = scene.node(id).and_then(|node| node.parent) else { return false };
+      let Some(old_index) = scene.children(old_parent).iter().position(|child| *child == id) else { return false };
+      if !scene.reparent(id, new_parent, index) {
+         return false;
+      }
+      let new_index = scene.children(new_parent).iter().position(|child| *child == id).unwrap_or(0);
+      if (old_parent, old_index) != (new_parent, new_index) {
+         self.record(Command::Reparent { id, from: (old_parent, old_index), to: (new_parent, new_index) });
+      }
+      true
+   }
+
+   /// records an edit if the node changed since `before` was taken
+   pub fn edit_node(&mut self, scene: &Scene, id: NodeId, before: Node) {
+      let Some(after) = scene.node(id) else { return };
+      if *after != before {
+         self.record(Command::EditNode { id, before, after: after.clone() });
+      }
+   }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::light::LightKind;
+
+   /// the demo's first box
+   fn box_id(scene: &Scene) -> NodeId {
+      scene.iter().find(|(_, node)| node.name == "Box").unwrap().0
+   }
+
+   fn rename(scene: &Scene, id: NodeId, name: &str) -> Command {
+      let before = scene.node(id).unwrap().clone();
+      let after = Node { name: name.to_string(), ..before.clone() };
+      Command::EditNode { id, before, after }
+   }
+
+   fn name(scene: &Scene, id: NodeId) -> &str {
+      &scene.node(id).unwrap().name
+   }
+
+   #[test]
+   fn undo_and_redo() {
+      let mut scene = Scene::demo();
+      let mut history = History::default();
+      let id = box_id(&scene);
+
+      let command = rename(&scene, id, "Crate");
+      assert!(history.execute(&mut scene, command));
+      assert_eq!(name(&scene, id), "Crate");
+      assert_eq!(history.undo_name().as_deref(), Some("Edit Crate"));
+
+      assert!(history.undo(&mut scene));
+      assert_eq!(name(&scene, id), "Box");
+      assert!(!history.can_undo());
+      assert!(!history.undo(&mut scene));
+
+      assert!(history.redo(&mut scene));
+      assert_eq!(name(&scene, id), "Crate");
+      assert!(!history.can_redo());
+   }
+
+   #[test]
+   fn recording_clears_redo() {
+      let mut scene = Scene::demo();
+      let mut history = History::default();
+      let command = rename(&scene, box_id(&scene), "Crate");
+      history.execute(&mut scene, command);
+      history.undo(&mut scene);
+      history.execute(&mut scene, Command::AddMaterial { material: Material::default() });
+      assert!(!history.can_redo());
+   }
+
+   #[test]
+   fn edits_merge_until_sealed() {
+      let mut scene = Scene::demo();
+      let mut history = History::default();
+      let id = box_id(&scene);
+
+      for typed in ["C", "Cr", "Cra"] {
+         let command = rename(&scene, id, typed);
+         history.execute(&mut scene, command);
+      }
+      assert_eq!(history.undo_len(), 1);
+
+      history.seal();
+      let command = rename(&scene, id, "Crate");
+      history.execute(&mut scene, command);
+      assert_eq!(history.undo_len(), 2);
+
+      history.undo(&mut scene);
+      assert_eq!(name(&scene, id), "Cra");
+      history.undo(&mut scene);
+      assert_eq!(name(&scene, id), "Box");
+   }
+
+   #[test]
+   fn oldest_steps_fall_off_past_the_depth() {
+      let mut scene = Scene::demo();
+      let mut history = History::new(3);
+      let id = box_id(&scene);
+
+      for typed in ["A", "B", "C", "D", "E"] {
+         let command = rename(&scene, id, typed);
+         history.execute(&mut scene, command);
+         history.seal();
+      }
+      assert_eq!(history.undo_len(), 3);
+
+      while history.undo(&mut scene) {}
+      assert_eq!(name(&scene, id), "B");
+      assert_eq!(history.redo_len(), 3);
+   }
+
+   #[test]
+   fn a_command_that_fails_is_dropped() {
+      let mut scene = Scene::demo();
+      let mut history = History::default();
+      let id = box_id(&scene);
+      let command = rename(&scene, id, "Crate");
+      history.execute(&mut scene, command);
+
+      // removed behind the history's back, the rename can't be undone any more
+      scene.remove(id);
+      assert!(!history.undo(&mut scene));
+      assert!(!history.can_undo());
+      assert!(!history.can_redo());
+   }
+
+   #[test]
+   fn a_batch_that_fails_partway_is_rolled_back() {
+      let mut scene = Scene::demo();
+      let id = box_id(&scene);
+      let light = scene.lights[0];
+
+      // the second command has nothing to edit, so the rename before it comes back off
+      let batch = Command::Batch {
+         name: "Broken".to_string(),
+         commands: vec![rename(&scene, id, "Crate"), Command::EditLight { index: 99, before: light, after: light }],
+      };
+      assert!(!batch.apply(&mut scene));
+      assert_eq!(scene, Scene::demo());
+
+      // undone back to front, the light fails after the rename was reverted, so the rename goes back on
+      let mut history = History::default();
+      let batch = Command::Batch {
+         name: "Wrap".to_string(),
+         commands: vec![Command::AddLight { index: 0, light }, rename(&scene, id, "Crate")],
+      };
+      assert!(history.execute(&mut scene, batch));
+      scene.lights[0].kind = LightKind::Spot;
+      let before = scene.clone();
+      assert!(!history.undo(&mut scene));
+      assert_eq!(scene, before);
+      assert_eq!(name(&scene, id), "Crate");
+   }
+}
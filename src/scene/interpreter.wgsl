@@ -0,0 +1,77 @@
+// opcodes and stack sizes are prepended and the primitive cases filled in by `bytecode::interpreter_source`
+
+struct Instruction {
+    opcode: u32,
+    id: u32,
+    material: vec2<f32>,
+    data: array<vec4<f32>, 4>,
+};
+
+@group(1) @binding(0)
+var<storage, read> program: array<Instruction>;
+
+fn map(p_in: vec3<f32>) -> Surface {
+    var surfaces: array<Surface, MAX_SURFACE_STACK>;
+    var points: array<vec3<f32>, MAX_POINT_STACK>;
+    var sp = 0u;
+    var pp = 0u;
+    var p = p_in;
+
+    let count = arrayLength(&program);
+    for (var pc = 0u; pc < count; pc++) {
+        let ins = program[pc];
+        let color = ins.data[3].xyz;
+
+        switch ins.opcode {
+            case OP_EMPTY: {
+                surfaces[sp] = empty_surface();
+                sp += 1u;
+            }
+
+            case OP_PUSH_TRANSFORM: {
+                points[pp] = p;
+                pp += 1u;
+                let m = mat3x3<f32>(ins.data[0].xyz, ins.data[1].xyz, ins.data[2].xyz);
+                p = (m * (p - ins.data[3].xyz)) / ins.data[3].w;
+            }
+            case OP_POP_TRANSFORM: {
+                pp -= 1u;
+                p = points[pp];
+                surfaces[sp - 1u].dist *= ins.data[0].x;
+            }
+
+            case OP_PUSH_DOMAIN: {
+                points[pp] = p;
+                pp += 1u;
+                p = domain_point(p, u32(ins.data[0].x), ins.data[1], ins.data[2]);
+            }
+            case OP_POP_DOMAIN: {
+                pp -= 1u;
+                p = points[pp];
+                surfaces[sp - 1u].dist = domain_distance(surfaces[sp - 1u].dist, u32(ins.data[0].x), ins.data[1]);
+            }
+
+            case OP_UNION: {
+                sp -= 1u;
+                surfaces[sp - 1u] = op_combine(surfaces[sp - 1u], surfaces[sp], BOOLEAN_UNION, u32(ins.data[0].x), ins.data[0].y, ins.data[0].z);
+            }
+            case OP_SUBTRACTION: {
+                sp -= 1u;
+                surfaces[sp - 1u] = op_combine(surfaces[sp - 1u], surfaces[sp], BOOLEAN_SUBTRACTION, u32(ins.data[0].x), ins.data[0].y, ins.data[0].z);
+            }
+            case OP_INTERSECTION: {
+                sp -= 1u;
+                surfaces[sp - 1u] = op_combine(surfaces[sp - 1u], surfaces[sp], BOOLEAN_INTERSECTION, u32(ins.data[0].x), ins.data[0].y, ins.data[0].z);
+            }
+
+//#PRIMITIVES
+
+            default: {}
+        }
+    }
+
+    if sp == 0u {
+        return empty_surface();
+    }
+    return surfaces[0];
+}
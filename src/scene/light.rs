@@ -0,0 +1,212 @@
+use egui::{CollapsingHeader, ComboBox, Ui};
+use serde::{Deserialize, Serialize};
+use crate::defaults_and_sliders_gui;
+use crate::utility::widgets::{FieldOptions, ParamField};
+
+/// lights the shaders have room for, the rest of `Scene::lights` is ignored
+pub const MAX_LIGHTS: usize = 8;
+
+
+/// values match the `LIGHT_*` constants in the scene shader
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LightKind {
+   /// parallel rays from infinitely far away, like the sun
+   #[default]
+   Directional = 0,
+   /// shines every way from a position and fades with distance
+   Point = 1,
+   /// a point light limited to a cone around its direction
+   Spot = 2,
+}
+impl LightKind {
+   pub const ALL: [LightKind; 3] = [LightKind::Directional, LightKind::Point, LightKind::Spot];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         LightKind::Directional => "Directional",
+         LightKind::Point => "Point",
+         LightKind::Spot => "Spot",
+      }
+   }
+
+   pub fn has_position(&self) -> bool {
+      *self != LightKind::Directional
+   }
+
+   pub fn has_direction(&self) -> bool {
+      *self != LightKind::Point
+   }
+}
+
+
+/// the kind picker for `Light`'s panel
+impl ParamField for LightKind {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      let before = *self;
+      ComboBox::from_label(options.label)
+          .selected_text(self.name())
+          .show_ui(ui, |ui| {
+             for kind in LightKind::ALL {
+                ui.selectable_value(self, kind, kind.name());
+             }
+          });
+      *self != before
+   }
+}
+
+
+defaults_and_sliders_gui!(
+   #[derive(Copy)]
+   struct Light,
+   kind: LightKind = LightKind::Directional,
+   /// direction the light travels in, doesn't need to be normalised
+   #[visible(|light: &Light| light.kind.has_direction())]
+   direction: [f32; 3] = [-0.4, -1.0, 0.6] => -1.0..=1.0,
+   #[visible(|light: &Light| light.kind.has_position())]
+   position: [f32; 3] = [2.0, 3.0, -2.0],
+   #[colour]
+   color: [f32; 3] = [1.0, 0.97, 0.9],
+   #[logarithmic]
+   intensity: f32 = 1.0 => 0.01..=100.0,
+   /// distance at which point and spot lights have faded to nothing
+   #[logarithmic]
+   #[visible(|light: &Light| light.kind.has_position())]
+   range: f32 = 10.0 => 0.1..=1000.0,
+   /// half angle of a spot light's cone in degrees
+   #[visible(|light: &Light| light.kind == LightKind::Spot)]
+   spot_angle: f32 = 30.0 => 1.0..=89.0,
+   /// share of the cone, from the edge in, that fades out
+   #[visible(|light: &Light| light.kind == LightKind::Spot)]
+   spot_softness: f32 = 0.2 => 0.0..=1.0,
+   cast_shadows: bool = true
+);
+impl Light {
+   /// point and spot lights start brighter, they lose most of it to distance
+   pub fn new(kind: LightKind) -> Self {
+      Self {
+         kind,
+         intensity: if kind.has_position() { 10.0 } else { 1.0 },
+         ..Default::default()
+      }
+   }
+
+   /// returns true if any field changed
+   pub fn ui(&mut self, ui: &mut Ui) -> bool {
+      let before = *self;
+      CollapsingHeader::new(format!("{} light", self.kind.name()))
+          .id_source("light")
+          .default_open(true)
+          .show(ui, |ui| {
+             if ui.button("Reset").clicked() {
+                *self = Self::new(self.kind);
+             }
+             self.fields_ui(ui);
+          });
+      *self != before
+   }
+}
+
+
+/// values match the `SHADING_*` constants in the scene shader
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShadingModel {
+   /// diffuse only, ignores metallic and roughness
+   Lambert = 0,
+   /// diffuse plus a highlight whose size follows roughness
+   BlinnPhong = 1,
+   /// cook-torrance with a ggx distribution, metals reflect their own colour
+   #[default]
+   Pbr = 2,
+}
+impl ShadingModel {
+   pub const ALL: [ShadingModel; 3] = [ShadingModel::Lambert, ShadingModel::BlinnPhong, ShadingModel::Pbr];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         ShadingModel::Lambert => "Lambert",
+         ShadingModel::BlinnPhong => "Blinn-Phong",
+         ShadingModel::Pbr => "PBR",
+      }
+   }
+}
+
+
+defaults_and_sliders_gui!(NormalParams,
+   /// offset of the distance samples whose gradient gives the surface normal
+   #[logarithmic]
+   normal_epsilon: f32 = 0.0005 => 0.00001..=0.01
+);
+
+defaults_and_sliders_gui!(ShadowParams,
+   /// how far light creeps round an occluder's edge, bigger is softer
+   #[logarithmic]
+   penumbra: f32 = 0.05 => 0.001..=1.0,
+   /// march steps towards each light
+   shadow_steps: u32 = 64 => 8u32..=256
+);
+
+defaults_and_sliders_gui!(OcclusionParams,
+   occlusion_strength: f32 = 1.0 => 0.0..=4.0,
+   /// how far from the surface nearby geometry darkens it
+   occlusion_distance: f32 = 0.3 => 0.01..=2.0,
+   occlusion_steps: u32 = 5 => 1u32..=16
+);
+
+
+/// how surfaces respond to the lights, shared by every light in the scene
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lighting {
+   pub model: ShadingModel,
+   #[serde(flatten)]
+   pub normals: NormalParams,
+   pub shadows: bool,
+   #[serde(flatten)]
+   pub soft_shadows: ShadowParams,
+   pub occlusion: bool,
+   #[serde(flatten)]
+   pub ambient_occlusion: OcclusionParams,
+}
+impl Default for Lighting {
+   fn default() -> Self {
+      Self {
+         model: ShadingModel::Pbr,
+         normals: NormalParams::default(),
+         shadows: true,
+         soft_shadows: ShadowParams::default(),
+         occlusion: true,
+         ambient_occlusion: OcclusionParams::default(),
+      }
+   }
+}
+impl Lighting {
+   /// returns true if any field changed
+   pub fn ui(&mut self, ui: &mut Ui) -> bool {
+      let before = *self;
+      CollapsingHeader::new("Lighting")
+          .default_open(true)
+          .show(ui, |ui| {
+             ui.horizontal(|ui| {
+                ComboBox::from_id_source("shading_model")
+                    .selected_text(self.model.name())
+                    .show_ui(ui, |ui| {
+                       for model in ShadingModel::ALL {
+                          ui.selectable_value(&mut self.model, model, model.name());
+                       }
+                    });
+                if ui.button("Reset").clicked() {
+                   *self = Self::default();
+                }
+             });
+
+             self.normals.fields_ui(ui);
+
+             ui.checkbox(&mut self.shadows, "soft shadows");
+             ui.add_enabled_ui(self.shadows, |ui| self.soft_shadows.fields_ui(ui));
+
+             ui.checkbox(&mut self.occlusion, "ambient occlusion");
+             ui.add_enabled_ui(self.occlusion, |ui| self.ambient_occlusion.fields_ui(ui));
+          });
+      *self != before
+   }
+}
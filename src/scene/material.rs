@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+use crate::defaults_and_sliders_gui;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct MaterialId(pub usize);
+
+
+defaults_and_sliders_gui!(
+   struct Material,
+   name: String = "Default".to_string(),
+   /// linear rgb, the diffuse colour or for metals the reflected colour
+   #[colour]
+   color: [f32; 3] = [0.8, 0.8, 0.8],
+   /// 0 for plastics and stone, 1 for bare metal
+   metallic: f32 = 0.0 => 0.0..=1.0,
+   /// 0 is a mirror, 1 is chalk
+   roughness: f32 = 0.5 => 0.0..=1.0
+);
+impl Material {
+   pub fn new(name: &str, color: [f32; 3]) -> Self {
+      Self {
+         name: name.to_string(),
+         color,
+         ..Default::default()
+      }
+   }
+
+   pub fn with_surface(mut self, metallic: f32, roughness: f32) -> Self {
+      self.metallic = metallic;
+      self.roughness = roughness;
+      self
+   }
+
+   /// (metallic, roughness) as carried by `Surface`
+   pub fn surface(&self) -> [f32; 2] {
+      [self.metallic, self.roughness]
+   }
+}
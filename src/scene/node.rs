@@ -0,0 +1,205 @@
+use cgmath::{Deg, Matrix3, Rad, Vector3};
+use serde::{Deserialize, Serialize};
+use crate::defaults_and_sliders_gui;
+use crate::scene::domain::DomainOp;
+use crate::scene::material::MaterialId;
+use crate::scene::operators::Operation;
+use crate::scene::primitives::Primitive;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId(pub u32);
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+   Primitive(Primitive),
+   Operation(Operation),
+   /// warps the space of its children, which are unioned like a group
+   Domain(DomainOp),
+   /// behaves like a union of its children
+   Group,
+}
+impl NodeKind {
+   pub fn accepts_children(&self) -> bool {
+      !matches!(self, NodeKind::Primitive(_))
+   }
+
+   /// short description shown next to node names
+   pub fn label(&self) -> String {
+      match self {
+         NodeKind::Primitive(primitive) => primitive.name().to_string(),
+         NodeKind::Operation(operation) => operation.name(),
+         NodeKind::Domain(domain) => domain.name().to_string(),
+         NodeKind::Group => "Group".to_string(),
+      }
+   }
+}
+
+
+defaults_and_sliders_gui!(
+   /// rotation is xyz euler angles in degrees, scale is uniform so distances stay exact
+   #[derive(Copy)]
+   struct Transform,
+   position: Vector3<f32> = Vector3::new(0.0, 0.0, 0.0),
+   /// xyz euler angles in degrees
+   #[step(1.0)]
+   rotation: Vector3<f32> = Vector3::new(0.0, 0.0, 0.0),
+   /// uniform so distances stay exact
+   #[logarithmic]
+   scale: f32 = 1.0 => 0.01..=100.0
+);
+impl Transform {
+   pub fn from_position(position: Vector3<f32>) -> Self {
+      Self { position, ..Default::default() }
+   }
+
+   pub fn is_identity(&self) -> bool {
+      *self == Self::default()
+   }
+
+   pub fn rotation_matrix(&self) -> Matrix3<f32> {
+      Matrix3::from_angle_z(Deg(self.rotation.z))
+          * Matrix3::from_angle_y(Deg(self.rotation.y))
+          * Matrix3::from_angle_x(Deg(self.rotation.x))
+   }
+
+   /// world -> local, the rotation matrix is orthonormal so the inverse is the transpose
+   pub fn inverse_rotation_matrix(&self) -> Matrix3<f32> {
+      use cgmath::Matrix;
+      self.rotation_matrix().transpose()
+   }
+
+   /// moves a point from the parent space into this node's local space
+   pub fn to_local(&self, p: Vector3<f32>) -> Vector3<f32> {
+      (self.inverse_rotation_matrix() * (p - self.position)) / self.scale
+   }
+
+   /// sets the euler angles from an orthonormal matrix, the inverse of `rotation_matrix`
+   pub fn set_rotation_matrix(&mut self, m: Matrix3<f32>) {
+      let sin_y = (-m.x.z).clamp(-1.0, 1.0);
+      let (x, y, z) = if sin_y.abs() < 0.9999 {
+         (m.y.z.atan2(m.z.z), sin_y.asin(), m.x.y.atan2(m.x.x))
+      } else {
+         // gimbal lock, z is folded into x
+         (m.y.x.atan2(m.y.y) * sin_y.signum(), sin_y.asin(), 0.0)
+      };
+      let degrees = |angle: f32| Deg::from(Rad(angle)).0;
+      self.rotation = Vector3::new(degrees(x), degrees(y), degrees(z));
+   }
+}
+
+
+/// where a node's local space sits in the world, points map to `position + scale * rotation * p`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frame {
+   pub position: Vector3<f32>,
+   pub rotation: Matrix3<f32>,
+   pub scale: f32,
+}
+impl Default for Frame {
+   fn default() -> Self {
+      Self {
+         position: Vector3::new(0.0, 0.0, 0.0),
+         rotation: Matrix3::from_scale(1.0),
+         scale: 1.0,
+      }
+   }
+}
+impl Frame {
+   /// the frame of a child placed in this one with `transform`
+   pub fn then(&self, transform: &Transform) -> Frame {
+      Frame {
+         position: self.to_world(transform.position),
+         rotation: self.rotation * transform.rotation_matrix(),
+         scale: self.scale * transform.scale,
+      }
+   }
+
+   pub fn to_world(&self, p: Vector3<f32>) -> Vector3<f32> {
+      self.position + self.rotation * p * self.scale
+   }
+
+   pub fn to_local(&self, p: Vector3<f32>) -> Vector3<f32> {
+      use cgmath::Matrix;
+      self.rotation.transpose() * (p - self.position) / self.scale
+   }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+   pub name: String,
+   pub kind: NodeKind,
+   pub transform: Transform,
+   pub material: MaterialId,
+   pub visible: bool,
+   pub parent: Option<NodeId>,
+   pub children: Vec<NodeId>,
+}
+impl Node {
+   pub fn new(name: &str, kind: NodeKind) -> Self {
+      Self {
+         name: name.to_string(),
+         kind,
+         transform: Transform::default(),
+         material: MaterialId::default(),
+         visible: true,
+         parent: None,
+         children: vec![],
+      }
+   }
+
+   pub fn primitive(name: &str, primitive: Primitive) -> Self {
+      Self::new(name, NodeKind::Primitive(primitive))
+   }
+
+   pub fn operation(name: &str, operation: Operation) -> Self {
+      Self::new(name, NodeKind::Operation(operation))
+   }
+
+   pub fn domain(name: &str, domain: DomainOp) -> Self {
+      Self::new(name, NodeKind::Domain(domain))
+   }
+
+   pub fn group(name: &str) -> Self {
+      Self::new(name, NodeKind::Group)
+   }
+
+   pub fn with_transform(mut self, transform: Transform) -> Self {
+      self.transform = transform;
+      self
+   }
+
+   pub fn with_material(mut self, material: MaterialId) -> Self {
+      self.material = material;
+      self
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn assert_matrix_eq(a: Matrix3<f32>, b: Matrix3<f32>) {
+      let (a, b): ([[f32; 3]; 3], [[f32; 3]; 3]) = (a.into(), b.into());
+      for (a, b) in a.iter().flatten().zip(b.iter().flatten()) {
+         assert!((a - b).abs() < 1e-4, "{a:?} != {b:?}");
+      }
+   }
+
+   #[test]
+   fn set_rotation_matrix_round_trips() {
+      let angles = [-170.0, -90.0, -45.0, 0.0, 30.0, 90.0, 135.0];
+      for x in angles {
+         for y in angles {
+            for z in angles {
+               let rotated = Transform { rotation: Vector3::new(x, y, z), ..Default::default() };
+               let mut transform = Transform::default();
+               transform.set_rotation_matrix(rotated.rotation_matrix());
+               assert_matrix_eq(transform.rotation_matrix(), rotated.rotation_matrix());
+            }
+         }
+      }
+   }
+}
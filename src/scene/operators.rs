@@ -0,0 +1,141 @@
+use egui::{ComboBox, Ui};
+use serde::{Deserialize, Serialize};
+use crate::defaults_and_sliders_gui;
+
+/// values match the `BOOLEAN_*` constants in `sdf_library.wgsl`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BooleanOp {
+   Union = 0,
+   /// first child minus every other child, the carved faces keep the first child's material
+   Subtraction = 1,
+   Intersection = 2,
+}
+impl BooleanOp {
+   pub const ALL: [BooleanOp; 3] = [BooleanOp::Union, BooleanOp::Subtraction, BooleanOp::Intersection];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         BooleanOp::Union => "Union",
+         BooleanOp::Subtraction => "Subtraction",
+         BooleanOp::Intersection => "Intersection",
+      }
+   }
+}
+
+/// values match the `BLEND_*` constants in `sdf_library.wgsl`, the shapes follow hg_sdf
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blend {
+   Sharp = 0,
+   /// polynomial smooth min
+   Smooth = 1,
+   /// 45 degree bevel
+   Chamfer = 2,
+   /// `steps` terraces inside the blend radius
+   Stairs = 3,
+   /// `steps` round columns inside the blend radius
+   Columns = 4,
+}
+impl Blend {
+   pub const ALL: [Blend; 5] = [Blend::Sharp, Blend::Smooth, Blend::Chamfer, Blend::Stairs, Blend::Columns];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         Blend::Sharp => "Sharp",
+         Blend::Smooth => "Smooth",
+         Blend::Chamfer => "Chamfer",
+         Blend::Stairs => "Stairs",
+         Blend::Columns => "Columns",
+      }
+   }
+
+   pub fn from_index(index: u32) -> Blend {
+      Blend::ALL.get(index as usize).copied().unwrap_or(Blend::Sharp)
+   }
+
+   pub fn uses_steps(&self) -> bool {
+      matches!(self, Blend::Stairs | Blend::Columns)
+   }
+}
+
+defaults_and_sliders_gui!(BlendParams,
+   /// how far the blend reaches from where the shapes meet
+   radius: f32 = 0.2 => 0.0..=2.0,
+   /// terraces or columns, only used by those blends
+   #[step(1.0)]
+   steps: f32 = 4.0 => 1.0..=12.0
+);
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Operation {
+   pub boolean: BooleanOp,
+   pub blend: Blend,
+   pub params: BlendParams,
+}
+impl Operation {
+   pub fn new(boolean: BooleanOp) -> Self {
+      Self {
+         boolean,
+         blend: Blend::Sharp,
+         params: BlendParams::default(),
+      }
+   }
+
+   pub fn blended(boolean: BooleanOp, blend: Blend, radius: f32) -> Self {
+      Self {
+         boolean,
+         blend,
+         params: BlendParams { radius, ..Default::default() },
+      }
+   }
+
+   pub fn union() -> Self {
+      Self::new(BooleanOp::Union)
+   }
+
+   pub fn subtraction() -> Self {
+      Self::new(BooleanOp::Subtraction)
+   }
+
+   pub fn intersection() -> Self {
+      Self::new(BooleanOp::Intersection)
+   }
+
+   pub fn is_sharp(&self) -> bool {
+      self.blend == Blend::Sharp || self.params.radius <= 0.0
+   }
+
+   pub fn name(&self) -> String {
+      if self.blend == Blend::Sharp {
+         self.boolean.name().to_string()
+      } else {
+         format!("{} {}", self.blend.name(), self.boolean.name())
+      }
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui) -> bool {
+      let before = *self;
+      ui.horizontal(|ui| {
+         ComboBox::from_id_source("boolean")
+             .selected_text(self.boolean.name())
+             .show_ui(ui, |ui| {
+                for boolean in BooleanOp::ALL {
+                   ui.selectable_value(&mut self.boolean, boolean, boolean.name());
+                }
+             });
+
+         ComboBox::from_id_source("blend")
+             .selected_text(self.blend.name())
+             .show_ui(ui, |ui| {
+                for blend in Blend::ALL {
+                   ui.selectable_value(&mut self.blend, blend, blend.name());
+                }
+             });
+      });
+
+      if self.blend != Blend::Sharp {
+         self.params.ui(ui);
+      }
+      *self != before
+   }
+}
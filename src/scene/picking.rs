@@ -0,0 +1,108 @@
+use cgmath::{InnerSpace, Vector3};
+use crate::scene::eval;
+use crate::scene::graph::Scene;
+use crate::scene::node::NodeId;
+use crate::scene::sdf::Surface;
+
+/// what a ray from the viewport ran into
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PickHit {
+   /// the primitive that owns the surface
+   pub id: NodeId,
+   pub position: Vector3<f32>,
+   pub normal: Vector3<f32>,
+   /// along the ray from its origin
+   pub distance: f32,
+}
+
+
+/// sphere traces the scene the same way the shader's `march` does, with the scene's render settings
+///
+/// returns the distance along the ray and the surface that was hit
+pub fn march(scene: &Scene, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, Surface)> {
+   let settings = &scene.settings;
+   let step_scale = scene.step_scale(None);
+   let direction = direction.normalize();
+
+   let mut t = 0.0;
+   for _ in 0..settings.max_steps {
+      let surface = eval::eval_surface(scene, origin + direction * t);
+      if surface.dist.abs() < settings.surface_epsilon {
+         return Some((t, surface));
+      }
+      t += surface.dist * step_scale;
+      if t > settings.max_distance {
+         break;
+      }
+   }
+   None
+}
+
+/// the node under a ray, none if it hits nothing or a surface without a node
+pub fn pick(scene: &Scene, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<PickHit> {
+   let (t, surface) = march(scene, origin, direction)?;
+   let id = NodeId(surface.id);
+   if !scene.contains(id) {
+      return None;
+   }
+
+   let position = origin + direction.normalize() * t;
+   Some(PickHit {
+      id,
+      position,
+      normal: eval::normal(scene, position),
+      distance: t,
+   })
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use cgmath::{EuclideanSpace, Point3};
+
+   /// the demo node called `name` under the node called `parent`
+   fn find(scene: &Scene, parent: &str, name: &str) -> NodeId {
+      scene.iter()
+          .find(|(_, node)| node.name == name && node.parent.and_then(|parent| scene.node(parent)).is_some_and(|p| p.name == parent))
+          .unwrap().0
+   }
+
+   /// picks along the ray from the demo's camera through `target`
+   fn pick_at(scene: &Scene, target: Vector3<f32>) -> Option<NodeId> {
+      let origin = scene.camera.position.to_vec();
+      pick(scene, origin, target - origin).map(|hit| hit.id)
+   }
+
+   #[test]
+   fn clicking_demo_nodes_picks_them() {
+      let scene = Scene::demo();
+      assert_eq!(scene.camera.position, Point3::new(0.0, 1.5, -5.0));
+
+      // the sphere is cut out of the box's middle, its corners are left
+      assert_eq!(pick_at(&scene, Vector3::new(0.7, 0.7, -0.7)), Some(find(&scene, "Cut", "Box")));
+      // the torus stands facing the camera, aim at its tube rather than the hole
+      assert_eq!(pick_at(&scene, Vector3::new(2.8, 0.0, 0.0)), Some(find(&scene, "Root", "Torus")));
+      assert_eq!(pick_at(&scene, Vector3::new(-2.0, -0.25, 0.0)), Some(find(&scene, "Blob", "Sphere")));
+      assert_eq!(pick_at(&scene, Vector3::new(0.0, 0.85, 2.5)), Some(find(&scene, "Twist", "Box")));
+      assert_eq!(pick_at(&scene, Vector3::new(0.0, -1.0, -2.0)), Some(find(&scene, "Root", "Ground")));
+   }
+
+   #[test]
+   fn empty_space_picks_nothing() {
+      let scene = Scene::demo();
+      let origin = scene.camera.position.to_vec();
+      assert_eq!(pick(&scene, origin, Vector3::unit_y()), None);
+      assert_eq!(pick(&scene, origin, Vector3::new(-0.2, 0.3, -1.0)), None);
+   }
+
+   #[test]
+   fn hits_sit_on_the_surface() {
+      let scene = Scene::demo();
+      let origin = scene.camera.position.to_vec();
+      let hit = pick(&scene, origin, Vector3::new(-2.0, -0.25, 0.0) - origin).unwrap();
+      assert!(eval::eval(&scene, hit.position).abs() < scene.settings.surface_epsilon);
+      assert!((hit.normal.magnitude() - 1.0).abs() < 1e-4);
+      assert!(hit.normal.dot(origin - hit.position) > 0.0);
+   }
+}
@@ -0,0 +1,306 @@
+use cgmath::Vector3;
+use crate::defaults_and_sliders_gui;
+use crate::scene::sdf;
+
+defaults_and_sliders_gui!(SphereParams,
+   #[logarithmic]
+   radius: f32 = 0.5 => 0.01..=10.0
+);
+
+defaults_and_sliders_gui!(BoxParams,
+   #[logarithmic]
+   half_x: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   half_y: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   half_z: f32 = 0.5 => 0.01..=10.0
+);
+
+defaults_and_sliders_gui!(RoundBoxParams,
+   #[logarithmic]
+   half_x: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   half_y: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   half_z: f32 = 0.5 => 0.01..=10.0,
+   radius: f32 = 0.1 => 0.0..=2.0
+);
+
+defaults_and_sliders_gui!(TorusParams,
+   #[logarithmic]
+   major_radius: f32 = 0.5 => 0.01..=10.0,
+   minor_radius: f32 = 0.15 => 0.01..=5.0
+);
+
+defaults_and_sliders_gui!(CapsuleParams,
+   /// length of the straight section, 0 gives a sphere
+   half_height: f32 = 0.5 => 0.0..=10.0,
+   radius: f32 = 0.25 => 0.01..=5.0
+);
+
+defaults_and_sliders_gui!(CylinderParams,
+   #[logarithmic]
+   half_height: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   radius: f32 = 0.35 => 0.01..=10.0
+);
+
+defaults_and_sliders_gui!(ConeParams,
+   #[logarithmic]
+   half_height: f32 = 0.5 => 0.01..=10.0,
+   bottom_radius: f32 = 0.5 => 0.0..=10.0,
+   /// above 0 the cone is cut off flat
+   top_radius: f32 = 0.0 => 0.0..=10.0
+);
+
+defaults_and_sliders_gui!(PlaneParams,
+   /// offset along y, the plane faces up
+   height: f32 = 0.0 => -10.0..=10.0
+);
+
+defaults_and_sliders_gui!(HexPrismParams,
+   #[logarithmic]
+   radius: f32 = 0.4 => 0.01..=10.0,
+   #[logarithmic]
+   half_length: f32 = 0.5 => 0.01..=10.0
+);
+
+defaults_and_sliders_gui!(TriPrismParams,
+   #[logarithmic]
+   radius: f32 = 0.5 => 0.01..=10.0,
+   #[logarithmic]
+   half_length: f32 = 0.5 => 0.01..=10.0
+);
+
+defaults_and_sliders_gui!(EllipsoidParams,
+   #[logarithmic]
+   radius_x: f32 = 0.6 => 0.01..=10.0,
+   #[logarithmic]
+   radius_y: f32 = 0.35 => 0.01..=10.0,
+   #[logarithmic]
+   radius_z: f32 = 0.45 => 0.01..=10.0
+);
+
+
+/// declares `Primitive` and all the per variant plumbing that only depends on the param struct
+macro_rules! primitives {
+   ($($variant:ident($params:ident) = $opcode:literal => $name:literal, $wgsl:literal;)*) => {
+      #[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+      pub enum Primitive {
+         $($variant($params),)*
+      }
+
+      impl Primitive {
+         /// one of each primitive with default parameters, in opcode order
+         pub fn all() -> Vec<Primitive> {
+            vec![$(Primitive::$variant($params::default()),)*]
+         }
+
+         pub fn name(&self) -> &'static str {
+            match self { $(Primitive::$variant(_) => $name,)* }
+         }
+
+         /// name of the distance function in `sdf_library.wgsl`, called as `f(p, params...)`
+         pub fn wgsl_function(&self) -> &'static str {
+            match self { $(Primitive::$variant(_) => $wgsl,)* }
+         }
+
+         /// the param struct's fields in declaration order
+         pub fn params(&self) -> &[f32] {
+            match self { $(Primitive::$variant(params) => bytemuck::cast_slice(bytemuck::bytes_of(params)),)* }
+         }
+
+         /// bytecode opcode, stable so saved programs and the interpreter agree
+         pub fn opcode(&self) -> u32 {
+            match self { $(Primitive::$variant(_) => $opcode,)* }
+         }
+
+         /// inverse of `opcode` + `params`, missing trailing params keep their defaults
+         pub fn from_opcode(opcode: u32, values: &[f32]) -> Option<Primitive> {
+            match opcode {
+               $($opcode => {
+                  let mut params = $params::default();
+                  let fields: &mut [f32] = bytemuck::cast_slice_mut(bytemuck::bytes_of_mut(&mut params));
+                  let n = fields.len().min(values.len());
+                  fields[..n].copy_from_slice(&values[..n]);
+                  Some(Primitive::$variant(params))
+               })*
+               _ => None,
+            }
+         }
+
+         pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+            match self { $(Primitive::$variant(params) => params.ui(ui),)* }
+         }
+      }
+   };
+}
+
+primitives! {
+   Sphere(SphereParams) = 100 => "Sphere", "sd_sphere";
+   Box(BoxParams) = 101 => "Box", "sd_box";
+   RoundBox(RoundBoxParams) = 102 => "Round Box", "sd_round_box";
+   Torus(TorusParams) = 103 => "Torus", "sd_torus";
+   Capsule(CapsuleParams) = 104 => "Capsule", "sd_capsule";
+   Cylinder(CylinderParams) = 105 => "Cylinder", "sd_cylinder";
+   Cone(ConeParams) = 106 => "Cone", "sd_cone";
+   Plane(PlaneParams) = 107 => "Plane", "sd_plane";
+   HexPrism(HexPrismParams) = 108 => "Hex Prism", "sd_hex_prism";
+   TriPrism(TriPrismParams) = 109 => "Tri Prism", "sd_tri_prism";
+   Ellipsoid(EllipsoidParams) = 110 => "Ellipsoid", "sd_ellipsoid";
+}
+
+impl Primitive {
+   /// distance from `p` to the primitive sitting at the origin of its local space
+   pub fn distance(&self, p: Vector3<f32>) -> f32 {
+      match self {
+         Primitive::Sphere(s) => sdf::sd_sphere(p, s.radius),
+         Primitive::Box(s) => sdf::sd_box(p, s.half_x, s.half_y, s.half_z),
+         Primitive::RoundBox(s) => sdf::sd_round_box(p, s.half_x, s.half_y, s.half_z, s.radius),
+         Primitive::Torus(s) => sdf::sd_torus(p, s.major_radius, s.minor_radius),
+         Primitive::Capsule(s) => sdf::sd_capsule(p, s.half_height, s.radius),
+         Primitive::Cylinder(s) => sdf::sd_cylinder(p, s.half_height, s.radius),
+         Primitive::Cone(s) => sdf::sd_cone(p, s.half_height, s.bottom_radius, s.top_radius),
+         Primitive::Plane(s) => sdf::sd_plane(p, s.height),
+         Primitive::HexPrism(s) => sdf::sd_hex_prism(p, s.radius, s.half_length),
+         Primitive::TriPrism(s) => sdf::sd_tri_prism(p, s.radius, s.half_length),
+         Primitive::Ellipsoid(s) => sdf::sd_ellipsoid(p, s.radius_x, s.radius_y, s.radius_z),
+      }
+   }
+
+   /// false for the primitives whose function is only a bound on the true distance
+   pub fn is_exact(&self) -> bool {
+      !matches!(self, Primitive::TriPrism(_) | Primitive::Ellipsoid(_))
+   }
+
+   /// bounds can overshoot round their edges, so the raymarcher takes shorter steps near them
+   pub fn step_scale(&self) -> f32 {
+      if self.is_exact() { 1.0 } else { 0.8 }
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use wgpu::util::DeviceExt;
+   use crate::scene::compiler;
+   use crate::scene::compiler::SDF_LIBRARY;
+
+   /// a grid that doesn't line up with any primitive's symmetry planes
+   fn points() -> Vec<[f32; 4]> {
+      let coordinate = |i: usize| -1.3 + i as f32 * 0.37;
+      (0..8 * 8 * 8).map(|i| [coordinate(i % 8), coordinate(i / 8 % 8), coordinate(i / 64), 0.0]).collect()
+   }
+
+   /// any adapter will do, llvmpipe included
+   fn device() -> (wgpu::Device, wgpu::Queue) {
+      let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+      let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+          .expect("no gpu adapter");
+      pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).unwrap()
+   }
+
+   /// compute shader writing the primitive's `sdf_library.wgsl` distance at each of `count` points
+   fn check_source(primitive: &Primitive, count: usize) -> String {
+      let mut call = format!("{}(p", primitive.wgsl_function());
+      for value in primitive.params() {
+         call += &format!(", {value:?}");
+      }
+      format!("{SDF_LIBRARY}
+@group(0) @binding(0) var<storage, read> points: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read_write> distances: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+    if id.x >= {count}u {{
+        return;
+    }}
+    let p = points[id.x].xyz;
+    distances[id.x] = {call});
+}}
+")
+   }
+
+   fn gpu_distances(device: &wgpu::Device, queue: &wgpu::Queue, primitive: &Primitive, points: &[[f32; 4]]) -> Vec<f32> {
+      let source = check_source(primitive, points.len());
+      let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+         label: Some("Primitive Check"),
+         source: wgpu::ShaderSource::Wgsl(source.into()),
+      });
+      let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+         label: Some("Primitive Check"),
+         layout: None,
+         module: &module,
+         entry_point: "main",
+         compilation_options: Default::default(),
+      });
+
+      let size = (points.len() * std::mem::size_of::<f32>()) as u64;
+      let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+         label: Some("Points"),
+         contents: bytemuck::cast_slice(points),
+         usage: wgpu::BufferUsages::STORAGE,
+      });
+      let output = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("Distances"),
+         size,
+         usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+         mapped_at_creation: false,
+      });
+      let readback = device.create_buffer(&wgpu::BufferDescriptor {
+         label: Some("Readback"),
+         size,
+         usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+         mapped_at_creation: false,
+      });
+      let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+         label: Some("Primitive Check"),
+         layout: &pipeline.get_bind_group_layout(0),
+         entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: input.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: output.as_entire_binding() },
+         ],
+      });
+
+      let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+      {
+         let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+         pass.set_pipeline(&pipeline);
+         pass.set_bind_group(0, &bind_group, &[]);
+         pass.dispatch_workgroups(points.len().div_ceil(64) as u32, 1, 1);
+      }
+      encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, size);
+      queue.submit([encoder.finish()]);
+
+      let slice = readback.slice(..);
+      slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+      device.poll(wgpu::Maintain::Wait);
+      let distances = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+      distances
+   }
+
+   #[test]
+   fn every_primitive_check_shader_validates() {
+      for primitive in Primitive::all() {
+         if let Err(error) = compiler::validate(&check_source(&primitive, 512)) {
+            panic!("{}: {error}", primitive.name());
+         }
+      }
+   }
+
+   #[test]
+   #[ignore = "needs a GPU adapter"]
+   fn cpu_and_wgsl_primitives_agree() {
+      let (device, queue) = device();
+      let points = points();
+      for primitive in Primitive::all() {
+         let gpu = gpu_distances(&device, &queue, &primitive, &points);
+         for (point, gpu) in points.iter().zip(gpu) {
+            let p = Vector3::new(point[0], point[1], point[2]);
+            let cpu = primitive.distance(p);
+            assert!((cpu - gpu).abs() < 1e-4 * (1.0 + cpu.abs()), "{} at {p:?}: cpu {cpu}, gpu {gpu}", primitive.name());
+         }
+      }
+   }
+}
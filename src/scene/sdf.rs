@@ -0,0 +1,344 @@
+use cgmath::{InnerSpace, Vector2, Vector3};
+use crate::scene::operators::{Blend, BooleanOp, Operation};
+
+// cpu side copies of the functions in `sdf_library.wgsl`, keep the two in step
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Surface {
+   pub dist: f32,
+   pub color: [f32; 3],
+   /// metallic, roughness
+   pub material: [f32; 2],
+   pub id: u32,
+}
+impl Surface {
+   pub const EMPTY: Surface = Surface {
+      dist: 1e10,
+      color: [0.0; 3],
+      material: [0.0; 2],
+      id: u32::MAX,
+   };
+
+   pub fn new(dist: f32, color: [f32; 3], material: [f32; 2], id: u32) -> Self {
+      Self { dist, color, material, id }
+   }
+}
+
+
+pub fn sd_sphere(p: Vector3<f32>, radius: f32) -> f32 {
+   p.magnitude() - radius
+}
+
+pub fn sd_box(p: Vector3<f32>, half_x: f32, half_y: f32, half_z: f32) -> f32 {
+   let q = abs3(p) - Vector3::new(half_x, half_y, half_z);
+   max3(q, 0.0).magnitude() + q.x.max(q.y.max(q.z)).min(0.0)
+}
+
+pub fn sd_round_box(p: Vector3<f32>, half_x: f32, half_y: f32, half_z: f32, radius: f32) -> f32 {
+   let q = abs3(p) - Vector3::new(half_x, half_y, half_z) + Vector3::new(radius, radius, radius);
+   max3(q, 0.0).magnitude() + q.x.max(q.y.max(q.z)).min(0.0) - radius
+}
+
+pub fn sd_torus(p: Vector3<f32>, major_radius: f32, minor_radius: f32) -> f32 {
+   let q = Vector2::new(Vector2::new(p.x, p.z).magnitude() - major_radius, p.y);
+   q.magnitude() - minor_radius
+}
+
+pub fn sd_capsule(p: Vector3<f32>, half_height: f32, radius: f32) -> f32 {
+   let q = Vector3::new(p.x, p.y - p.y.clamp(-half_height, half_height), p.z);
+   q.magnitude() - radius
+}
+
+pub fn sd_cylinder(p: Vector3<f32>, half_height: f32, radius: f32) -> f32 {
+   let d = Vector2::new(
+      Vector2::new(p.x, p.z).magnitude() - radius,
+      p.y.abs() - half_height,
+   );
+   d.x.max(d.y).min(0.0) + Vector2::new(d.x.max(0.0), d.y.max(0.0)).magnitude()
+}
+
+pub fn sd_cone(p: Vector3<f32>, half_height: f32, bottom_radius: f32, top_radius: f32) -> f32 {
+   let q = Vector2::new(Vector2::new(p.x, p.z).magnitude(), p.y);
+   let k1 = Vector2::new(top_radius, half_height);
+   let k2 = Vector2::new(top_radius - bottom_radius, 2.0 * half_height);
+   let r = if q.y < 0.0 { bottom_radius } else { top_radius };
+   let ca = Vector2::new(q.x - q.x.min(r), q.y.abs() - half_height);
+   let cb = q - k1 + k2 * ((k1 - q).dot(k2) / k2.dot(k2)).clamp(0.0, 1.0);
+   let s = if cb.x < 0.0 && ca.y < 0.0 { -1.0 } else { 1.0 };
+   s * ca.dot(ca).min(cb.dot(cb)).sqrt()
+}
+
+pub fn sd_plane(p: Vector3<f32>, height: f32) -> f32 {
+   p.y - height
+}
+
+pub fn sd_hex_prism(p: Vector3<f32>, radius: f32, half_length: f32) -> f32 {
+   let k = Vector3::new(-0.8660254, 0.5, 0.57735);
+   let p = abs3(p);
+   let kxy = Vector2::new(k.x, k.y);
+   let xy = Vector2::new(p.x, p.y) - kxy * (2.0 * kxy.dot(Vector2::new(p.x, p.y)).min(0.0));
+   let d = Vector2::new(
+      (xy - Vector2::new(xy.x.clamp(-k.z * radius, k.z * radius), radius)).magnitude() * sign(xy.y - radius),
+      p.z - half_length,
+   );
+   d.x.max(d.y).min(0.0) + Vector2::new(d.x.max(0.0), d.y.max(0.0)).magnitude()
+}
+
+/// bound, not exact
+pub fn sd_tri_prism(p: Vector3<f32>, radius: f32, half_length: f32) -> f32 {
+   let q = abs3(p);
+   (q.z - half_length).max((q.x * 0.866025 + p.y * 0.5).max(-p.y) - radius * 0.5)
+}
+
+/// bound, not exact
+pub fn sd_ellipsoid(p: Vector3<f32>, radius_x: f32, radius_y: f32, radius_z: f32) -> f32 {
+   let k0 = Vector3::new(p.x / radius_x, p.y / radius_y, p.z / radius_z).magnitude();
+   let k1 = Vector3::new(p.x / (radius_x * radius_x), p.y / (radius_y * radius_y), p.z / (radius_z * radius_z)).magnitude();
+   k0 * (k0 - 1.0) / k1.max(1e-6)
+}
+
+
+pub fn op_union(a: Surface, b: Surface) -> Surface {
+   if b.dist < a.dist { b } else { a }
+}
+
+pub fn op_subtraction(a: Surface, b: Surface) -> Surface {
+   if -b.dist > a.dist { Surface::new(-b.dist, a.color, a.material, a.id) } else { a }
+}
+
+pub fn op_intersection(a: Surface, b: Surface) -> Surface {
+   if b.dist > a.dist { b } else { a }
+}
+
+
+// blended booleans, mirrors `op_combine` and the `fd_union_*` functions
+
+pub fn glsl_mod(x: f32, y: f32) -> f32 {
+   x - y * (x / y).floor()
+}
+
+pub fn fd_union_smooth(a: f32, b: f32, r: f32) -> f32 {
+   let h = (0.5 + 0.5 * (b - a) / r).clamp(0.0, 1.0);
+   mix(b, a, h) - r * h * (1.0 - h)
+}
+
+pub fn fd_union_chamfer(a: f32, b: f32, r: f32) -> f32 {
+   a.min(b).min((a - r + b) * 0.5f32.sqrt())
+}
+
+pub fn fd_union_stairs(a: f32, b: f32, r: f32, n: f32) -> f32 {
+   let s = r / n;
+   let u = b - r;
+   a.min(b).min(0.5 * (u + a + (glsl_mod(u - a + s, 2.0 * s) - s).abs()))
+}
+
+pub fn fd_union_columns(a: f32, b: f32, r: f32, n: f32) -> f32 {
+   if a >= r || b >= r {
+      return a.min(b);
+   }
+
+   let sqrt2 = 2.0f32.sqrt();
+   let column_radius = r * sqrt2 / ((n - 1.0) * 2.0 + sqrt2);
+   let mut p = (Vector2::new(a, b) + Vector2::new(b, -a)) * 0.5f32.sqrt();
+   p.x -= sqrt2 / 2.0 * r;
+   p.x += column_radius * sqrt2;
+   if glsl_mod(n, 2.0) == 1.0 {
+      p.y += column_radius;
+   }
+   let size = column_radius * 2.0;
+   p.y = glsl_mod(p.y + size * 0.5, size) - size * 0.5;
+
+   let result = p.magnitude() - column_radius;
+   result.min(p.x).min(a).min(b)
+}
+
+pub fn fd_union_blend(a: f32, b: f32, blend: Blend, r: f32, n: f32) -> f32 {
+   match blend {
+      Blend::Sharp => a.min(b),
+      Blend::Smooth => fd_union_smooth(a, b, r),
+      Blend::Chamfer => fd_union_chamfer(a, b, r),
+      Blend::Stairs => fd_union_stairs(a, b, r, n),
+      Blend::Columns => fd_union_columns(a, b, r, n),
+   }
+}
+
+pub fn op_combine(a: Surface, b: Surface, operation: &Operation) -> Surface {
+   let radius = operation.params.radius;
+   if operation.is_sharp() {
+      return match operation.boolean {
+         BooleanOp::Union => op_union(a, b),
+         BooleanOp::Subtraction => op_subtraction(a, b),
+         BooleanOp::Intersection => op_intersection(a, b),
+      };
+   }
+
+   let sa = if operation.boolean != BooleanOp::Union { -1.0 } else { 1.0 };
+   let sb = if operation.boolean == BooleanOp::Intersection { -1.0 } else { 1.0 };
+   let x = a.dist * sa;
+   let y = b.dist * sb;
+   let dist = sa * fd_union_blend(x, y, operation.blend, radius, operation.params.steps.max(1.0));
+
+   if operation.boolean == BooleanOp::Subtraction {
+      return Surface::new(dist, a.color, a.material, a.id);
+   }
+
+   let h = (0.5 + 0.5 * (y - x) / radius).clamp(0.0, 1.0);
+   let color = std::array::from_fn(|c| mix(b.color[c], a.color[c], h));
+   let material = std::array::from_fn(|c| mix(b.material[c], a.material[c], h));
+   Surface::new(dist, color, material, if h >= 0.5 { a.id } else { b.id })
+}
+
+
+// domain warps, mirrors `domain_point` in the wgsl library, a spacing or count of 0 leaves that axis alone
+
+fn repeat_axis(x: f32, spacing: f32, limit: Option<f32>) -> f32 {
+   if spacing <= 0.0 {
+      return x;
+   }
+   // floor(x + 0.5) rather than round so both sides agree on the cell boundaries
+   let mut cell = (x / spacing + 0.5).floor();
+   if let Some(limit) = limit {
+      cell = cell.clamp(-limit, limit);
+   }
+   x - spacing * cell
+}
+
+pub fn dom_repeat(p: Vector3<f32>, spacing: Vector3<f32>) -> Vector3<f32> {
+   Vector3::new(
+      repeat_axis(p.x, spacing.x, None),
+      repeat_axis(p.y, spacing.y, None),
+      repeat_axis(p.z, spacing.z, None),
+   )
+}
+
+pub fn dom_repeat_limited(p: Vector3<f32>, spacing: Vector3<f32>, count: Vector3<f32>) -> Vector3<f32> {
+   Vector3::new(
+      repeat_axis(p.x, spacing.x, Some(count.x)),
+      repeat_axis(p.y, spacing.y, Some(count.y)),
+      repeat_axis(p.z, spacing.z, Some(count.z)),
+   )
+}
+
+/// `count` copies around the y axis
+pub fn dom_polar_repeat(p: Vector3<f32>, count: f32) -> Vector3<f32> {
+   let angle = std::f32::consts::TAU / count.max(1.0);
+   let a = p.z.atan2(p.x) + angle * 0.5;
+   let r = Vector2::new(p.x, p.z).magnitude();
+   let a = glsl_mod(a, angle) - angle * 0.5;
+   Vector3::new(a.cos() * r, p.y, a.sin() * r)
+}
+
+pub fn dom_mirror(p: Vector3<f32>, axes: Vector3<f32>) -> Vector3<f32> {
+   let pick = |value: f32, axis: f32| if axis > 0.5 { value.abs() } else { value };
+   Vector3::new(pick(p.x, axes.x), pick(p.y, axes.y), pick(p.z, axes.z))
+}
+
+/// rotates the xz plane by `strength` radians per unit of y
+pub fn dom_twist(p: Vector3<f32>, strength: f32) -> Vector3<f32> {
+   let (s, c) = (strength * p.y).sin_cos();
+   Vector3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z)
+}
+
+/// rotates the xy plane by `strength` radians per unit of x
+pub fn dom_bend(p: Vector3<f32>, strength: f32) -> Vector3<f32> {
+   let (s, c) = (strength * p.x).sin_cos();
+   Vector3::new(c * p.x - s * p.y, s * p.x + c * p.y, p.z)
+}
+
+pub fn dom_elongate(p: Vector3<f32>, h: Vector3<f32>) -> Vector3<f32> {
+   Vector3::new(
+      p.x - p.x.clamp(-h.x, h.x),
+      p.y - p.y.clamp(-h.y, h.y),
+      p.z - p.z.clamp(-h.z, h.z),
+   )
+}
+
+
+pub fn abs3(v: Vector3<f32>) -> Vector3<f32> {
+   Vector3::new(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+pub fn max3(v: Vector3<f32>, value: f32) -> Vector3<f32> {
+   Vector3::new(v.x.max(value), v.y.max(value), v.z.max(value))
+}
+
+/// wgsl `sign`, unlike `f32::signum` it returns 0 for 0
+pub fn sign(value: f32) -> f32 {
+   if value > 0.0 { 1.0 } else if value < 0.0 { -1.0 } else { 0.0 }
+}
+
+pub fn mix(x: f32, y: f32, a: f32) -> f32 {
+   x * (1.0 - a) + y * a
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn surface(dist: f32, id: u32) -> Surface {
+      Surface::new(dist, [id as f32; 3], [0.0, 0.5], id)
+   }
+
+   fn combine(a: f32, b: f32, boolean: BooleanOp, blend: Blend) -> Surface {
+      let operation = Operation::blended(boolean, blend, 0.4);
+      op_combine(surface(a, 1), surface(b, 2), &operation)
+   }
+
+   fn assert_close(a: f32, b: f32) {
+      assert!((a - b).abs() < 1e-5, "{a} != {b}");
+   }
+
+   #[test]
+   fn sharp_booleans() {
+      let union = combine(0.3, 0.5, BooleanOp::Union, Blend::Sharp);
+      assert_eq!((union.dist, union.id), (0.3, 1));
+      let union = combine(0.3, -0.5, BooleanOp::Union, Blend::Sharp);
+      assert_eq!((union.dist, union.id), (-0.5, 2));
+
+      // cutting keeps the first shape's surface whichever side is nearer
+      let subtraction = combine(0.3, -0.5, BooleanOp::Subtraction, Blend::Sharp);
+      assert_eq!((subtraction.dist, subtraction.id, subtraction.color), (0.5, 1, [1.0; 3]));
+      let subtraction = combine(0.3, 0.2, BooleanOp::Subtraction, Blend::Sharp);
+      assert_eq!((subtraction.dist, subtraction.id), (0.3, 1));
+
+      let intersection = combine(0.3, 0.5, BooleanOp::Intersection, Blend::Sharp);
+      assert_eq!((intersection.dist, intersection.id), (0.5, 2));
+   }
+
+   #[test]
+   fn blends_where_the_shapes_meet() {
+      assert_close(combine(0.0, 0.0, BooleanOp::Union, Blend::Smooth).dist, -0.1);
+      assert_close(combine(0.0, 0.0, BooleanOp::Subtraction, Blend::Smooth).dist, 0.1);
+      assert_close(combine(0.0, 0.0, BooleanOp::Intersection, Blend::Smooth).dist, 0.1);
+
+      assert_close(combine(0.0, 0.0, BooleanOp::Union, Blend::Chamfer).dist, -0.4 * 0.5f32.sqrt());
+      assert_close(combine(0.1, 0.1, BooleanOp::Union, Blend::Chamfer).dist, -0.2 * 0.5f32.sqrt());
+      assert_close(combine(0.0, 0.0, BooleanOp::Intersection, Blend::Chamfer).dist, 0.4 * 0.5f32.sqrt());
+
+      assert_close(combine(0.0, 0.0, BooleanOp::Union, Blend::Stairs).dist, -0.2);
+      assert_close(combine(0.0, 0.0, BooleanOp::Subtraction, Blend::Stairs).dist, 0.2);
+
+      assert_close(combine(0.0, 0.0, BooleanOp::Union, Blend::Columns).dist, -0.174942);
+      assert_close(combine(0.0, 0.0, BooleanOp::Intersection, Blend::Columns).dist, 0.174942);
+   }
+
+   /// far outside one shape there's nothing to blend
+   #[test]
+   fn blends_leave_distant_shapes_alone() {
+      for blend in Blend::ALL {
+         assert_close(combine(1.0, 0.5, BooleanOp::Union, blend).dist, 0.5);
+         assert_close(combine(0.5, 2.0, BooleanOp::Subtraction, blend).dist, 0.5);
+         assert_close(combine(1.0, -2.0, BooleanOp::Intersection, blend).dist, 1.0);
+      }
+   }
+
+   #[test]
+   fn smooth_union_mixes_colour_by_the_nearer_shape() {
+      let even = combine(0.0, 0.0, BooleanOp::Union, Blend::Smooth);
+      assert_eq!((even.color, even.id), ([1.5; 3], 1));
+      let nearer_b = combine(0.3, 0.0, BooleanOp::Union, Blend::Smooth);
+      assert_eq!(nearer_b.id, 2);
+      assert!(nearer_b.color[0] > 1.5);
+   }
+}
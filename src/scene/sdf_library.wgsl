@@ -0,0 +1,246 @@
+struct Surface {
+    dist: f32,
+    color: vec3<f32>,
+    // metallic, roughness
+    material: vec2<f32>,
+    id: u32,
+};
+
+// primitives take their params as loose floats in the order of the rust param structs
+
+fn sd_sphere(p: vec3<f32>, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+
+fn sd_box(p: vec3<f32>, half_x: f32, half_y: f32, half_z: f32) -> f32 {
+    let q = abs(p) - vec3<f32>(half_x, half_y, half_z);
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+}
+
+fn sd_round_box(p: vec3<f32>, half_x: f32, half_y: f32, half_z: f32, radius: f32) -> f32 {
+    let q = abs(p) - vec3<f32>(half_x, half_y, half_z) + radius;
+    return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0) - radius;
+}
+
+fn sd_torus(p: vec3<f32>, major_radius: f32, minor_radius: f32) -> f32 {
+    let q = vec2<f32>(length(p.xz) - major_radius, p.y);
+    return length(q) - minor_radius;
+}
+
+fn sd_capsule(p: vec3<f32>, half_height: f32, radius: f32) -> f32 {
+    let q = vec3<f32>(p.x, p.y - clamp(p.y, -half_height, half_height), p.z);
+    return length(q) - radius;
+}
+
+fn sd_cylinder(p: vec3<f32>, half_height: f32, radius: f32) -> f32 {
+    let d = abs(vec2<f32>(length(p.xz), p.y)) - vec2<f32>(radius, half_height);
+    return min(max(d.x, d.y), 0.0) + length(max(d, vec2<f32>(0.0)));
+}
+
+fn sd_cone(p: vec3<f32>, half_height: f32, bottom_radius: f32, top_radius: f32) -> f32 {
+    let q = vec2<f32>(length(p.xz), p.y);
+    let k1 = vec2<f32>(top_radius, half_height);
+    let k2 = vec2<f32>(top_radius - bottom_radius, 2.0 * half_height);
+    let r = select(top_radius, bottom_radius, q.y < 0.0);
+    let ca = vec2<f32>(q.x - min(q.x, r), abs(q.y) - half_height);
+    let cb = q - k1 + k2 * clamp(dot(k1 - q, k2) / dot(k2, k2), 0.0, 1.0);
+    let s = select(1.0, -1.0, cb.x < 0.0 && ca.y < 0.0);
+    return s * sqrt(min(dot(ca, ca), dot(cb, cb)));
+}
+
+fn sd_plane(p: vec3<f32>, height: f32) -> f32 {
+    return p.y - height;
+}
+
+fn sd_hex_prism(p_in: vec3<f32>, radius: f32, half_length: f32) -> f32 {
+    let k = vec3<f32>(-0.8660254, 0.5, 0.57735);
+    let p = abs(p_in);
+    let xy = p.xy - 2.0 * min(dot(k.xy, p.xy), 0.0) * k.xy;
+    let d = vec2<f32>(
+        length(xy - vec2<f32>(clamp(xy.x, -k.z * radius, k.z * radius), radius)) * sign(xy.y - radius),
+        p.z - half_length,
+    );
+    return min(max(d.x, d.y), 0.0) + length(max(d, vec2<f32>(0.0)));
+}
+
+// bound, not exact
+fn sd_tri_prism(p: vec3<f32>, radius: f32, half_length: f32) -> f32 {
+    let q = abs(p);
+    return max(q.z - half_length, max(q.x * 0.866025 + p.y * 0.5, -p.y) - radius * 0.5);
+}
+
+// bound, not exact
+fn sd_ellipsoid(p: vec3<f32>, radius_x: f32, radius_y: f32, radius_z: f32) -> f32 {
+    let r = vec3<f32>(radius_x, radius_y, radius_z);
+    let k0 = length(p / r);
+    let k1 = length(p / (r * r));
+    return k0 * (k0 - 1.0) / max(k1, 1e-6);
+}
+
+fn op_union(a: Surface, b: Surface) -> Surface {
+    if b.dist < a.dist { return b; }
+    return a;
+}
+
+fn op_subtraction(a: Surface, b: Surface) -> Surface {
+    if -b.dist > a.dist { return Surface(-b.dist, a.color, a.material, a.id); }
+    return a;
+}
+
+fn op_intersection(a: Surface, b: Surface) -> Surface {
+    if b.dist > a.dist { return b; }
+    return a;
+}
+
+
+// blended booleans, see `operators.rs`. every variant is written as a union,
+// intersection(a, b) = -union(-a, -b) and subtraction(a, b) = -union(-a, b) like hg_sdf
+
+const BOOLEAN_UNION: u32 = 0u;
+const BOOLEAN_SUBTRACTION: u32 = 1u;
+const BOOLEAN_INTERSECTION: u32 = 2u;
+
+const BLEND_SHARP: u32 = 0u;
+const BLEND_SMOOTH: u32 = 1u;
+const BLEND_CHAMFER: u32 = 2u;
+const BLEND_STAIRS: u32 = 3u;
+const BLEND_COLUMNS: u32 = 4u;
+
+fn glsl_mod(x: f32, y: f32) -> f32 {
+    return x - y * floor(x / y);
+}
+
+fn fd_union_smooth(a: f32, b: f32, r: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / r, 0.0, 1.0);
+    return mix(b, a, h) - r * h * (1.0 - h);
+}
+
+fn fd_union_chamfer(a: f32, b: f32, r: f32) -> f32 {
+    return min(min(a, b), (a - r + b) * sqrt(0.5));
+}
+
+fn fd_union_stairs(a: f32, b: f32, r: f32, n: f32) -> f32 {
+    let s = r / n;
+    let u = b - r;
+    return min(min(a, b), 0.5 * (u + a + abs(glsl_mod(u - a + s, 2.0 * s) - s)));
+}
+
+fn fd_union_columns(a: f32, b: f32, r: f32, n: f32) -> f32 {
+    if a >= r || b >= r {
+        return min(a, b);
+    }
+
+    let column_radius = r * sqrt(2.0) / ((n - 1.0) * 2.0 + sqrt(2.0));
+    // rotate 45 degrees onto the diagonal the columns sit on
+    var p = (vec2<f32>(a, b) + vec2<f32>(b, -a)) * sqrt(0.5);
+    p.x -= sqrt(2.0) / 2.0 * r;
+    p.x += column_radius * sqrt(2.0);
+    if glsl_mod(n, 2.0) == 1.0 {
+        p.y += column_radius;
+    }
+    let size = column_radius * 2.0;
+    p.y = glsl_mod(p.y + size * 0.5, size) - size * 0.5;
+
+    var result = length(p) - column_radius;
+    result = min(result, p.x);
+    result = min(result, a);
+    return min(result, b);
+}
+
+fn fd_union_blend(a: f32, b: f32, blend: u32, r: f32, n: f32) -> f32 {
+    switch blend {
+        case BLEND_SMOOTH: { return fd_union_smooth(a, b, r); }
+        case BLEND_CHAMFER: { return fd_union_chamfer(a, b, r); }
+        case BLEND_STAIRS: { return fd_union_stairs(a, b, r, n); }
+        case BLEND_COLUMNS: { return fd_union_columns(a, b, r, n); }
+        default: { return min(a, b); }
+    }
+}
+
+// colours and materials mix across the blend radius, subtraction keeps the first surface's
+fn op_combine(a: Surface, b: Surface, boolean: u32, blend: u32, radius: f32, steps: f32) -> Surface {
+    if blend == BLEND_SHARP || radius <= 0.0 {
+        switch boolean {
+            case BOOLEAN_SUBTRACTION: { return op_subtraction(a, b); }
+            case BOOLEAN_INTERSECTION: { return op_intersection(a, b); }
+            default: { return op_union(a, b); }
+        }
+    }
+
+    let sa = select(1.0, -1.0, boolean != BOOLEAN_UNION);
+    let sb = select(1.0, -1.0, boolean == BOOLEAN_INTERSECTION);
+    let x = a.dist * sa;
+    let y = b.dist * sb;
+    let dist = sa * fd_union_blend(x, y, blend, radius, max(steps, 1.0));
+
+    if boolean == BOOLEAN_SUBTRACTION {
+        return Surface(dist, a.color, a.material, a.id);
+    }
+
+    // weight of `a`, the same factor the smooth union mixes with
+    let h = clamp(0.5 + 0.5 * (y - x) / radius, 0.0, 1.0);
+    return Surface(dist, mix(b.color, a.color, h), mix(b.material, a.material, h), select(b.id, a.id, h >= 0.5));
+}
+
+fn empty_surface() -> Surface {
+    return Surface(1e10, vec3<f32>(0.0), vec2<f32>(0.0), 0xffffffffu);
+}
+
+// domain warps, the params are the rust param struct fields packed in order into a then b
+
+const DOMAIN_REPEAT: u32 = 0u;
+const DOMAIN_REPEAT_LIMITED: u32 = 1u;
+const DOMAIN_POLAR_REPEAT: u32 = 2u;
+const DOMAIN_MIRROR: u32 = 3u;
+const DOMAIN_TWIST: u32 = 4u;
+const DOMAIN_BEND: u32 = 5u;
+const DOMAIN_ELONGATE: u32 = 6u;
+const DOMAIN_ONION: u32 = 7u;
+const DOMAIN_ROUND: u32 = 8u;
+
+// floor(x + 0.5) rather than round so the cpu side agrees on the cell boundaries
+fn dom_repeat(p: vec3<f32>, spacing: vec3<f32>, limit: vec3<f32>) -> vec3<f32> {
+    let safe = select(vec3<f32>(1.0), spacing, spacing > vec3<f32>(0.0));
+    let cell = clamp(floor(p / safe + 0.5), -limit, limit);
+    return select(p, p - safe * cell, spacing > vec3<f32>(0.0));
+}
+
+fn dom_polar_repeat(p: vec3<f32>, count: f32) -> vec3<f32> {
+    let angle = 6.28318530718 / max(count, 1.0);
+    let a = glsl_mod(atan2(p.z, p.x) + angle * 0.5, angle) - angle * 0.5;
+    let r = length(p.xz);
+    return vec3<f32>(cos(a) * r, p.y, sin(a) * r);
+}
+
+fn dom_twist(p: vec3<f32>, strength: f32) -> vec3<f32> {
+    let c = cos(strength * p.y);
+    let s = sin(strength * p.y);
+    return vec3<f32>(c * p.x - s * p.z, p.y, s * p.x + c * p.z);
+}
+
+fn dom_bend(p: vec3<f32>, strength: f32) -> vec3<f32> {
+    let c = cos(strength * p.x);
+    let s = sin(strength * p.x);
+    return vec3<f32>(c * p.x - s * p.y, s * p.x + c * p.y, p.z);
+}
+
+fn domain_point(p: vec3<f32>, kind: u32, a: vec4<f32>, b: vec4<f32>) -> vec3<f32> {
+    switch kind {
+        case DOMAIN_REPEAT: { return dom_repeat(p, a.xyz, vec3<f32>(1e20)); }
+        case DOMAIN_REPEAT_LIMITED: { return dom_repeat(p, a.xyz, vec3<f32>(a.w, b.x, b.y)); }
+        case DOMAIN_POLAR_REPEAT: { return dom_polar_repeat(p, a.x); }
+        case DOMAIN_MIRROR: { return select(p, abs(p), a.xyz > vec3<f32>(0.5)); }
+        case DOMAIN_TWIST: { return dom_twist(p, a.x); }
+        case DOMAIN_BEND: { return dom_bend(p, a.x); }
+        case DOMAIN_ELONGATE: { return p - clamp(p, -a.xyz, a.xyz); }
+        default: { return p; }
+    }
+}
+
+fn domain_distance(d: f32, kind: u32, a: vec4<f32>) -> f32 {
+    switch kind {
+        case DOMAIN_ONION: { return abs(d) - a.x; }
+        case DOMAIN_ROUND: { return d - a.x; }
+        default: { return d; }
+    }
+}
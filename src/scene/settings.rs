@@ -0,0 +1,14 @@
+use crate::defaults_and_drag_value_gui;
+
+defaults_and_drag_value_gui!(RenderSettings,
+   /// raymarch iterations before a ray gives up
+   #[step(1.0)]
+   max_steps: u32 = 128 => 8..=2048,
+   max_distance: f32 = 100.0 => 1.0..=10000.0,
+   /// how close a ray has to get to count as a hit
+   #[step(0.0001)]
+   surface_epsilon: f32 = 0.001 => 0.00001..=0.1,
+   ambient: f32 = 0.15 => 0.0..=1.0,
+   #[colour]
+   background: [f32; 3] = [0.09, 0.1, 0.13]
+);
@@ -0,0 +1,53 @@
+struct SceneParams {
+    data: array<vec4<f32>, 1024>,
+}
+
+@group(1) @binding(0) var<uniform> scene_params: SceneParams;
+
+fn map(p: vec3<f32>) -> Surface {
+    let p0 = (mat3x3<f32>(scene_params.data[0].xyz, scene_params.data[1].xyz, scene_params.data[2].xyz) * (p - scene_params.data[3].xyz)) / scene_params.data[3].w;
+    var s0 = empty_surface();
+    let p1 = (mat3x3<f32>(scene_params.data[4].xyz, scene_params.data[5].xyz, scene_params.data[6].xyz) * (p0 - scene_params.data[7].xyz)) / scene_params.data[7].w;
+    var s1 = empty_surface();
+    let p2 = (mat3x3<f32>(scene_params.data[8].xyz, scene_params.data[9].xyz, scene_params.data[10].xyz) * (p1 - scene_params.data[11].xyz)) / scene_params.data[11].w;
+    var s2 = Surface(sd_box(p2, scene_params.data[10].w, scene_params.data[9].w, scene_params.data[8].w), scene_params.data[12].xyz, vec2<f32>(scene_params.data[12].w, scene_params.data[6].w), 2u);
+    s2.dist *= scene_params.data[11].w;
+    s1 = s2;
+    let p3 = (mat3x3<f32>(scene_params.data[13].xyz, scene_params.data[14].xyz, scene_params.data[15].xyz) * (p1 - scene_params.data[16].xyz)) / scene_params.data[16].w;
+    var s3 = Surface(sd_sphere(p3, scene_params.data[15].w), scene_params.data[17].xyz, vec2<f32>(scene_params.data[17].w, scene_params.data[14].w), 3u);
+    s3.dist *= scene_params.data[16].w;
+    s1 = op_combine(s1, s3, u32(scene_params.data[13].w), u32(scene_params.data[5].w), scene_params.data[4].w, scene_params.data[2].w);
+    s1.dist *= scene_params.data[7].w;
+    s0 = s1;
+    let p4 = (mat3x3<f32>(scene_params.data[18].xyz, scene_params.data[19].xyz, scene_params.data[20].xyz) * (p0 - scene_params.data[21].xyz)) / scene_params.data[21].w;
+    var s4 = Surface(sd_torus(p4, scene_params.data[20].w, scene_params.data[19].w), scene_params.data[22].xyz, vec2<f32>(scene_params.data[22].w, scene_params.data[18].w), 4u);
+    s4.dist *= scene_params.data[21].w;
+    s0 = op_union(s0, s4);
+    let p5 = (mat3x3<f32>(scene_params.data[23].xyz, scene_params.data[24].xyz, scene_params.data[25].xyz) * (p0 - scene_params.data[26].xyz)) / scene_params.data[26].w;
+    var s5 = empty_surface();
+    let p6 = (mat3x3<f32>(scene_params.data[27].xyz, scene_params.data[28].xyz, scene_params.data[29].xyz) * (p5 - scene_params.data[30].xyz)) / scene_params.data[30].w;
+    var s6 = Surface(sd_sphere(p6, scene_params.data[29].w), scene_params.data[31].xyz, vec2<f32>(scene_params.data[31].w, scene_params.data[28].w), 6u);
+    s6.dist *= scene_params.data[30].w;
+    s5 = s6;
+    let p7 = (mat3x3<f32>(scene_params.data[32].xyz, scene_params.data[33].xyz, scene_params.data[34].xyz) * (p5 - scene_params.data[35].xyz)) / scene_params.data[35].w;
+    var s7 = Surface(sd_sphere(p7, scene_params.data[34].w), scene_params.data[36].xyz, vec2<f32>(scene_params.data[36].w, scene_params.data[33].w), 7u);
+    s7.dist *= scene_params.data[35].w;
+    s5 = op_combine(s5, s7, u32(scene_params.data[32].w), u32(scene_params.data[27].w), scene_params.data[25].w, scene_params.data[24].w);
+    s5.dist *= scene_params.data[26].w;
+    s0 = op_union(s0, s5);
+    let p8 = (mat3x3<f32>(scene_params.data[37].xyz, scene_params.data[38].xyz, scene_params.data[39].xyz) * (p0 - scene_params.data[40].xyz)) / scene_params.data[40].w;
+    let d8 = domain_point(p8, 4u, scene_params.data[41], scene_params.data[42]);
+    var s8 = empty_surface();
+    let p9 = (mat3x3<f32>(scene_params.data[43].xyz, scene_params.data[44].xyz, scene_params.data[45].xyz) * (d8 - scene_params.data[46].xyz)) / scene_params.data[46].w;
+    var s9 = Surface(sd_box(p9, scene_params.data[45].w, scene_params.data[44].w, scene_params.data[43].w), scene_params.data[47].xyz, vec2<f32>(scene_params.data[47].w, scene_params.data[39].w), 9u);
+    s9.dist *= scene_params.data[46].w;
+    s8 = s9;
+    s8.dist *= scene_params.data[40].w;
+    s0 = op_union(s0, s8);
+    let p10 = (mat3x3<f32>(scene_params.data[48].xyz, scene_params.data[49].xyz, scene_params.data[50].xyz) * (p0 - scene_params.data[51].xyz)) / scene_params.data[51].w;
+    var s10 = Surface(sd_plane(p10, scene_params.data[50].w), scene_params.data[52].xyz, vec2<f32>(scene_params.data[52].w, scene_params.data[49].w), 10u);
+    s10.dist *= scene_params.data[51].w;
+    s0 = op_union(s0, s10);
+    s0.dist *= scene_params.data[3].w;
+    return s0;
+}
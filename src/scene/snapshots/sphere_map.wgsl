@@ -0,0 +1,16 @@
+struct SceneParams {
+    data: array<vec4<f32>, 1024>,
+}
+
+@group(1) @binding(0) var<uniform> scene_params: SceneParams;
+
+fn map(p: vec3<f32>) -> Surface {
+    let p0 = (mat3x3<f32>(scene_params.data[0].xyz, scene_params.data[1].xyz, scene_params.data[2].xyz) * (p - scene_params.data[3].xyz)) / scene_params.data[3].w;
+    var s0 = empty_surface();
+    let p1 = (mat3x3<f32>(scene_params.data[4].xyz, scene_params.data[5].xyz, scene_params.data[6].xyz) * (p0 - scene_params.data[7].xyz)) / scene_params.data[7].w;
+    var s1 = Surface(sd_sphere(p1, scene_params.data[6].w), scene_params.data[8].xyz, vec2<f32>(scene_params.data[8].w, scene_params.data[5].w), 1u);
+    s1.dist *= scene_params.data[7].w;
+    s0 = s1;
+    s0.dist *= scene_params.data[3].w;
+    return s0;
+}
@@ -0,0 +1,524 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix3, Point3, Rad, Vector2, Vector3};
+use egui::{Color32, Key, Painter, Pos2, Shape, Stroke};
+use crate::scene::camera::Camera;
+use crate::scene::node::{Frame, Node, NodeId, Transform};
+
+/// length of the axis handles on screen, in pixels
+const GIZMO_PIXELS: f32 = 90.0;
+
+/// how far from a handle the pointer can be and still grab it, in pixels
+pub const HIT_PIXELS: f32 = 7.0;
+
+const RING_SEGMENTS: usize = 48;
+
+const AXIS_COLORS: [Color32; 3] = [
+   Color32::from_rgb(230, 70, 70),
+   Color32::from_rgb(90, 200, 90),
+   Color32::from_rgb(70, 120, 240),
+];
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+   Translate,
+   Rotate,
+   /// scale is uniform, so there is a single handle
+   Scale,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GizmoSpace {
+   World,
+   /// the axes follow the node's own rotation
+   Local,
+}
+
+/// the part of the gizmo being hovered or dragged
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Handle {
+   Axis(usize),
+   /// the plane with this axis as its normal
+   Plane(usize),
+   /// moves in or turns around the screen plane
+   View,
+   Uniform,
+}
+impl Handle {
+   pub fn label(&self) -> String {
+      const AXES: [&str; 3] = ["X", "Y", "Z"];
+      match self {
+         Handle::Axis(axis) => format!("{} axis", AXES[*axis]),
+         Handle::Plane(axis) => format!("{}{} plane", AXES[(axis + 1) % 3], AXES[(axis + 2) % 3]),
+         Handle::View => "Screen".to_string(),
+         Handle::Uniform => "Uniform".to_string(),
+      }
+   }
+}
+
+
+/// maps between world space and pixels of the viewport image
+#[derive(Copy, Clone, Debug)]
+pub struct Screen {
+   pub camera: Camera,
+   /// top left corner of the image
+   pub min: Vector2<f32>,
+   pub size: Vector2<f32>,
+}
+impl Screen {
+   fn aspect(&self) -> f32 {
+      self.size.x / self.size.y.max(1.0)
+   }
+
+   pub fn project(&self, p: Vector3<f32>) -> Option<Vector2<f32>> {
+      let (x, y) = self.camera.project(Point3::from_vec(p), self.aspect())?;
+      Some(Vector2::new(
+         self.min.x + (x + 1.0) * 0.5 * self.size.x,
+         self.min.y + (1.0 - y) * 0.5 * self.size.y,
+      ))
+   }
+
+   /// (origin, direction) of the ray through a pixel
+   pub fn ray(&self, pixel: Vector2<f32>) -> (Vector3<f32>, Vector3<f32>) {
+      let x = (pixel.x - self.min.x) / self.size.x.max(1.0) * 2.0 - 1.0;
+      let y = 1.0 - (pixel.y - self.min.y) / self.size.y.max(1.0) * 2.0;
+      (self.camera.position.to_vec(), self.camera.ray_direction(x, y, self.aspect()))
+   }
+
+   /// world length that covers `pixels` at the depth of `p`
+   pub fn world_length(&self, p: Vector3<f32>, pixels: f32) -> f32 {
+      self.camera.pixel_size_at(Point3::from_vec(p), self.size.y) * pixels
+   }
+}
+
+
+/// screen space geometry of one handle, shared by drawing and hit testing
+#[derive(Clone, Debug, PartialEq)]
+pub enum HandleShape {
+   Line(Vector2<f32>, Vector2<f32>),
+   /// closed when it's a ring
+   Polyline(Vec<Vector2<f32>>),
+   Quad([Vector2<f32>; 4]),
+   Disc(Vector2<f32>, f32),
+}
+impl HandleShape {
+   /// pixels from the pointer to the shape, zero inside filled shapes
+   pub fn distance(&self, pointer: Vector2<f32>) -> f32 {
+      match self {
+         HandleShape::Line(a, b) => segment_distance(pointer, *a, *b),
+         HandleShape::Polyline(points) => points.windows(2)
+             .map(|pair| segment_distance(pointer, pair[0], pair[1]))
+             .fold(f32::INFINITY, f32::min),
+         HandleShape::Quad(corners) => {
+            if inside_convex(pointer, corners) {
+               return 0.0;
+            }
+            (0..4).map(|i| segment_distance(pointer, corners[i], corners[(i + 1) % 4]))
+                .fold(f32::INFINITY, f32::min)
+         }
+         HandleShape::Disc(center, radius) => ((pointer - center).magnitude() - radius).max(0.0),
+      }
+   }
+}
+
+fn segment_distance(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+   let ab = b - a;
+   let t = if ab.magnitude2() > 0.0 { ((p - a).dot(ab) / ab.magnitude2()).clamp(0.0, 1.0) } else { 0.0 };
+   (p - (a + ab * t)).magnitude()
+}
+
+fn inside_convex(p: Vector2<f32>, corners: &[Vector2<f32>; 4]) -> bool {
+   let side = |i: usize| {
+      let (a, b) = (corners[i], corners[(i + 1) % 4]);
+      (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+   };
+   let sides = [side(0), side(1), side(2), side(3)];
+   sides.iter().all(|s| *s >= 0.0) || sides.iter().all(|s| *s <= 0.0)
+}
+
+
+/// the handles for a gizmo at `origin` with unit `axes`, in priority order
+pub fn handles(screen: &Screen, mode: GizmoMode, origin: Vector3<f32>, axes: [Vector3<f32>; 3]) -> Vec<(Handle, HandleShape)> {
+   let Some(center) = screen.project(origin) else { return vec![] };
+   let length = screen.world_length(origin, GIZMO_PIXELS);
+   let mut handles = vec![];
+
+   match mode {
+      GizmoMode::Translate => {
+         handles.push((Handle::View, HandleShape::Disc(center, 6.0)));
+         for axis in 0..3 {
+            let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+            let corners = [(0.25, 0.25), (0.45, 0.25), (0.45, 0.45), (0.25, 0.45)]
+                .map(|(a, b)| screen.project(origin + (u * a + v * b) * length));
+            if let [Some(a), Some(b), Some(c), Some(d)] = corners {
+               handles.push((Handle::Plane(axis), HandleShape::Quad([a, b, c, d])));
+            }
+         }
+         for (axis, direction) in axes.iter().enumerate() {
+            if let Some(end) = screen.project(origin + direction * length) {
+               handles.push((Handle::Axis(axis), HandleShape::Line(center, end)));
+            }
+         }
+      }
+
+      GizmoMode::Rotate => {
+         for axis in 0..3 {
+            let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+            let ring: Option<Vec<_>> = (0..=RING_SEGMENTS)
+                .map(|i| {
+                   let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                   screen.project(origin + (u * angle.cos() + v * angle.sin()) * length * 0.8)
+                })
+                .collect();
+            if let Some(ring) = ring {
+               handles.push((Handle::Axis(axis), HandleShape::Polyline(ring)));
+            }
+         }
+         // the outer ring turns around the view direction
+         let view_ring = (0..=RING_SEGMENTS)
+             .map(|i| {
+                let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + Vector2::new(angle.cos(), angle.sin()) * GIZMO_PIXELS
+             })
+             .collect();
+         handles.push((Handle::View, HandleShape::Polyline(view_ring)));
+      }
+
+      GizmoMode::Scale => {
+         // drags measure the distance to the center, so there's no handle in the middle
+         let ring = (0..=RING_SEGMENTS)
+             .map(|i| {
+                let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                center + Vector2::new(angle.cos(), angle.sin()) * GIZMO_PIXELS * 0.8
+             })
+             .collect();
+         handles.push((Handle::Uniform, HandleShape::Polyline(ring)));
+      }
+   }
+
+   handles
+}
+
+/// the handle under the pointer, the first one in the list wins ties
+pub fn hit_test(handles: &[(Handle, HandleShape)], pointer: Vector2<f32>) -> Option<Handle> {
+   let mut best: Option<(Handle, f32)> = None;
+   for (handle, shape) in handles {
+      let distance = shape.distance(pointer);
+      if distance <= HIT_PIXELS && best.is_none_or(|(_, best)| distance < best) {
+         best = Some((*handle, distance));
+      }
+   }
+   best.map(|(handle, _)| handle)
+}
+
+
+/// closest point on the line `origin + axis * t` to a ray, as `t`
+pub fn line_parameter(origin: Vector3<f32>, axis: Vector3<f32>, (ray_origin, ray_direction): (Vector3<f32>, Vector3<f32>)) -> Option<f32> {
+   let b = axis.dot(ray_direction);
+   let denominator = 1.0 - b * b;
+   if denominator < 1e-6 {
+      return None;
+   }
+   let w = origin - ray_origin;
+   Some((b * ray_direction.dot(w) - axis.dot(w)) / denominator)
+}
+
+fn plane_hit(origin: Vector3<f32>, normal: Vector3<f32>, (ray_origin, ray_direction): (Vector3<f32>, Vector3<f32>)) -> Option<Vector3<f32>> {
+   let facing = normal.dot(ray_direction);
+   if facing.abs() < 1e-6 {
+      return None;
+   }
+   let t = normal.dot(origin - ray_origin) / facing;
+   (t > 0.0).then(|| ray_origin + ray_direction * t)
+}
+
+
+/// a drag in progress, the node is edited from the state it had when the drag started
+#[derive(Clone, Debug)]
+pub struct GizmoDrag {
+   pub id: NodeId,
+   pub handle: Handle,
+   /// the node before the drag, restored on cancel and used for the undo step
+   pub before: Node,
+   parent: Frame,
+   start_pointer: Vector2<f32>,
+   axes: [Vector3<f32>; 3],
+   /// typed while dragging, replaces the pointer's distance, angle in degrees or factor
+   pub typed: String,
+}
+impl GizmoDrag {
+   pub fn new(id: NodeId, handle: Handle, before: Node, parent: Frame, pointer: Vector2<f32>, axes: [Vector3<f32>; 3]) -> Self {
+      Self {
+         id,
+         handle,
+         before,
+         parent,
+         start_pointer: pointer,
+         axes,
+         typed: String::new(),
+      }
+   }
+
+   fn typed_value(&self) -> Option<f32> {
+      self.typed.parse().ok()
+   }
+
+   /// reads digits, `-`, `.`, backspace, and x, y, z to constrain to an axis or, with shift, a plane
+   pub fn handle_keys(&mut self, input: &egui::InputState, mode: GizmoMode) {
+      for event in &input.events {
+         match event {
+            egui::Event::Text(text) => {
+               for c in text.chars() {
+                  match c {
+                     '0'..='9' | '.' => self.typed.push(c),
+                     '-' if self.typed.is_empty() => self.typed.push(c),
+                     _ => {}
+                  }
+               }
+            }
+            egui::Event::Key { key, pressed: true, modifiers, .. } => {
+               let axis = match key {
+                  Key::X => Some(0),
+                  Key::Y => Some(1),
+                  Key::Z => Some(2),
+                  Key::Backspace => {
+                     self.typed.pop();
+                     None
+                  }
+                  _ => None,
+               };
+               if let (Some(axis), true) = (axis, mode != GizmoMode::Scale) {
+                  self.handle = if modifiers.shift && mode == GizmoMode::Translate { Handle::Plane(axis) } else { Handle::Axis(axis) };
+               }
+            }
+            _ => {}
+         }
+      }
+   }
+
+   /// the node's new local transform for the pointer at `pointer`, none while it can't be worked out
+   pub fn transform(&self, screen: &Screen, mode: GizmoMode, pointer: Vector2<f32>) -> Option<Transform> {
+      let start = self.before.transform;
+      let world = self.parent.then(&start);
+      let origin = world.position;
+      let view_axis = screen.camera.basis().0;
+      let mut transform = start;
+
+      match mode {
+         GizmoMode::Translate => {
+            let position = match self.handle {
+               Handle::Axis(axis) => {
+                  let direction = self.axes[axis];
+                  let distance = match self.typed_value() {
+                     Some(value) => value,
+                     None => {
+                        line_parameter(origin, direction, screen.ray(pointer))?
+                            - line_parameter(origin, direction, screen.ray(self.start_pointer))?
+                     }
+                  };
+                  origin + direction * distance
+               }
+               Handle::Plane(axis) => self.plane_move(screen, origin, self.axes[axis], pointer)?,
+               Handle::View | Handle::Uniform => self.plane_move(screen, origin, view_axis, pointer)?,
+            };
+            transform.position = self.parent.to_local(position);
+         }
+
+         GizmoMode::Rotate => {
+            let axis = match self.handle {
+               Handle::Axis(axis) | Handle::Plane(axis) => self.axes[axis],
+               Handle::View | Handle::Uniform => view_axis,
+            };
+            let angle = match self.typed_value() {
+               Some(degrees) => degrees.to_radians(),
+               None => {
+                  let from = plane_hit(origin, axis, screen.ray(self.start_pointer))? - origin;
+                  let to = plane_hit(origin, axis, screen.ray(pointer))? - origin;
+                  axis.dot(from.cross(to)).atan2(from.dot(to))
+               }
+            };
+            let rotation = Matrix3::from_axis_angle(axis, Rad(angle)) * world.rotation;
+            transform.set_rotation_matrix(self.parent.rotation.transpose() * rotation);
+         }
+
+         GizmoMode::Scale => {
+            let factor = match self.typed_value() {
+               Some(factor) => factor,
+               None => {
+                  let center = screen.project(origin)?;
+                  let start = (self.start_pointer - center).magnitude();
+                  if start < 1.0 {
+                     return None;
+                  }
+                  (pointer - center).magnitude() / start
+               }
+            };
+            transform.scale = (start.scale * factor).max(0.001);
+         }
+      }
+
+      Some(transform)
+   }
+
+   fn plane_move(&self, screen: &Screen, origin: Vector3<f32>, normal: Vector3<f32>, pointer: Vector2<f32>) -> Option<Vector3<f32>> {
+      let from = plane_hit(origin, normal, screen.ray(self.start_pointer))?;
+      let to = plane_hit(origin, normal, screen.ray(pointer))?;
+      Some(origin + to - from)
+   }
+}
+
+
+/// world directions of the gizmo's axes for a node in `world`
+pub fn axes(world: &Frame, space: GizmoSpace) -> [Vector3<f32>; 3] {
+   match space {
+      GizmoSpace::World => [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+      GizmoSpace::Local => [world.rotation.x, world.rotation.y, world.rotation.z].map(|axis| axis.normalize()),
+   }
+}
+
+pub fn paint(painter: &Painter, handles: &[(Handle, HandleShape)], highlighted: Option<Handle>) {
+   for (handle, shape) in handles {
+      let mut color = match handle {
+         Handle::Axis(axis) | Handle::Plane(axis) => AXIS_COLORS[*axis],
+         Handle::View | Handle::Uniform => Color32::from_gray(220),
+      };
+      if Some(*handle) == highlighted {
+         color = Color32::from_rgb(250, 220, 60);
+      }
+      let stroke = Stroke::new(2.5, color);
+      let pos = |v: &Vector2<f32>| Pos2::new(v.x, v.y);
+
+      match shape {
+         HandleShape::Line(a, b) => {
+            painter.line_segment([pos(a), pos(b)], stroke);
+            painter.circle_filled(pos(b), 4.0, color);
+         }
+         HandleShape::Polyline(points) => {
+            painter.add(Shape::line(points.iter().map(pos).collect(), stroke));
+         }
+         HandleShape::Quad(corners) => {
+            painter.add(Shape::convex_polygon(corners.iter().map(pos).collect(), color.gamma_multiply(0.5), stroke));
+         }
+         HandleShape::Disc(center, radius) => {
+            painter.circle(pos(center), *radius, color.gamma_multiply(0.5), stroke);
+         }
+      }
+   }
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::scene::node::NodeKind;
+   use crate::scene::primitives::{Primitive, SphereParams};
+
+   const AXES: [Vector3<f32>; 3] = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+
+   /// off every axis so no handle is seen edge on
+   fn screen() -> Screen {
+      let camera = Camera { position: Point3::new(4.0, 3.0, -5.0), ..Default::default() };
+      Screen { camera, min: Vector2::new(0.0, 0.0), size: Vector2::new(800.0, 600.0) }
+   }
+
+   fn drag(handle: Handle, pointer: Vector2<f32>) -> GizmoDrag {
+      let node = Node::new("Sphere", NodeKind::Primitive(Primitive::Sphere(SphereParams { radius: 1.0 })));
+      GizmoDrag::new(NodeId(1), handle, node, Frame::default(), pointer, AXES)
+   }
+
+   fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+      assert!((a - b).magnitude() < 1e-3, "{a:?} != {b:?}");
+   }
+
+   #[test]
+   fn handle_shape_distance() {
+      let line = HandleShape::Line(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0));
+      assert_eq!(line.distance(Vector2::new(5.0, 3.0)), 3.0);
+      assert_eq!(line.distance(Vector2::new(13.0, 4.0)), 5.0);
+
+      let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].map(|(x, y)| Vector2::new(x, y));
+      let polyline = HandleShape::Polyline(square.to_vec());
+      assert_eq!(polyline.distance(Vector2::new(5.0, 5.0)), 5.0);
+      let quad = HandleShape::Quad(square);
+      assert_eq!(quad.distance(Vector2::new(5.0, 5.0)), 0.0);
+      assert_eq!(quad.distance(Vector2::new(5.0, 12.0)), 2.0);
+
+      let disc = HandleShape::Disc(Vector2::new(0.0, 0.0), 2.0);
+      assert_eq!(disc.distance(Vector2::new(1.0, 1.0)), 0.0);
+      assert_eq!(disc.distance(Vector2::new(0.0, -5.0)), 3.0);
+   }
+
+   #[test]
+   fn hit_test_finds_each_translate_handle() {
+      let screen = screen();
+      let handles = handles(&screen, GizmoMode::Translate, Vector3::new(0.0, 0.0, 0.0), AXES);
+      let length = screen.world_length(Vector3::new(0.0, 0.0, 0.0), GIZMO_PIXELS);
+
+      let center = screen.project(Vector3::new(0.0, 0.0, 0.0)).unwrap();
+      assert_eq!(hit_test(&handles, center), Some(Handle::View));
+      for axis in 0..3 {
+         let along = screen.project(AXES[axis] * length * 0.9).unwrap();
+         assert_eq!(hit_test(&handles, along), Some(Handle::Axis(axis)));
+         let (u, v) = (AXES[(axis + 1) % 3], AXES[(axis + 2) % 3]);
+         let plane = screen.project((u + v) * length * 0.35).unwrap();
+         assert_eq!(hit_test(&handles, plane), Some(Handle::Plane(axis)));
+      }
+      assert_eq!(hit_test(&handles, Vector2::new(5.0, 5.0)), None);
+   }
+
+   #[test]
+   fn hit_test_finds_rotate_rings() {
+      let screen = screen();
+      let handles = handles(&screen, GizmoMode::Rotate, Vector3::new(0.0, 0.0, 0.0), AXES);
+      let length = screen.world_length(Vector3::new(0.0, 0.0, 0.0), GIZMO_PIXELS);
+
+      for axis in 0..3 {
+         let (u, v) = (AXES[(axis + 1) % 3], AXES[(axis + 2) % 3]);
+         let on_ring = screen.project((u + v).normalize() * length * 0.8).unwrap();
+         assert_eq!(hit_test(&handles, on_ring), Some(Handle::Axis(axis)));
+      }
+      let center = screen.project(Vector3::new(0.0, 0.0, 0.0)).unwrap();
+      assert_eq!(hit_test(&handles, center + Vector2::new(GIZMO_PIXELS, 0.0)), Some(Handle::View));
+      assert_eq!(hit_test(&handles, center), None);
+   }
+
+   #[test]
+   fn translate_drag_follows_the_pointer_along_its_axis() {
+      let screen = screen();
+      let start = screen.project(Vector3::new(0.0, 0.0, 0.0)).unwrap();
+      let drag = drag(Handle::Axis(0), start);
+      let pointer = screen.project(Vector3::new(1.5, 0.0, 0.0)).unwrap();
+      let transform = drag.transform(&screen, GizmoMode::Translate, pointer).unwrap();
+      assert_close(transform.position, Vector3::new(1.5, 0.0, 0.0));
+
+      // the pointer off the axis still only moves along it
+      let transform = drag.transform(&screen, GizmoMode::Translate, pointer + Vector2::new(0.0, 40.0)).unwrap();
+      assert_eq!((transform.position.y, transform.position.z), (0.0, 0.0));
+   }
+
+   #[test]
+   fn typed_values_replace_the_pointer() {
+      let screen = screen();
+      let start = Vector2::new(400.0, 300.0);
+      let mut drag = drag(Handle::Axis(2), start);
+      drag.typed = "2.5".to_string();
+      let transform = drag.transform(&screen, GizmoMode::Translate, start).unwrap();
+      assert_close(transform.position, Vector3::new(0.0, 0.0, 2.5));
+
+      drag.typed = "90".to_string();
+      let transform = drag.transform(&screen, GizmoMode::Rotate, start).unwrap();
+      assert_close(transform.rotation_matrix() * Vector3::unit_x(), Vector3::unit_y());
+
+      drag.typed = "2".to_string();
+      let transform = drag.transform(&screen, GizmoMode::Scale, start).unwrap();
+      assert_eq!(transform.scale, 2.0);
+   }
+
+   #[test]
+   fn rotate_drag_turns_by_the_angle_swept() {
+      let screen = screen();
+      let start = screen.project(Vector3::new(1.0, 0.0, 0.0)).unwrap();
+      let pointer = screen.project(Vector3::new(0.0, 1.0, 0.0)).unwrap();
+      let transform = drag(Handle::Axis(2), start).transform(&screen, GizmoMode::Rotate, pointer).unwrap();
+      assert_close(transform.rotation_matrix() * Vector3::unit_x(), Vector3::unit_y());
+   }
+}
@@ -0,0 +1,165 @@
+use egui::{CollapsingHeader, ComboBox, ScrollArea, TextEdit, Ui};
+use crate::scene::document::Document;
+use crate::scene::history::Command;
+use crate::scene::light::{Light, LightKind, MAX_LIGHTS};
+use crate::scene::material::{Material, MaterialId};
+use crate::scene::node::{NodeId, NodeKind};
+
+/// properties of the selected node, the materials, lights and render settings in the right panel
+///
+/// widgets write straight into the scene and the change is recorded afterwards,
+/// so a drag merges into one undo step the same way outliner edits do
+pub struct Inspector {
+   /// index into `Scene::materials` shown in the materials section
+   material: usize,
+}
+impl Default for Inspector {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl Inspector {
+   pub fn new() -> Self {
+      Self {
+         material: 0,
+      }
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui, document: &mut Document, selected: Option<NodeId>) {
+      ui.heading("Inspector");
+      ui.separator();
+
+      ScrollArea::vertical().show(ui, |ui| {
+         match selected {
+            Some(id) => node_ui(ui, document, id),
+            None => {
+               ui.weak("Nothing selected");
+            }
+         }
+         ui.separator();
+
+         self.materials_ui(ui, document);
+         lights_ui(ui, document);
+
+         let Document { scene, history } = document;
+         let before = scene.lighting;
+         if scene.lighting.ui(ui) {
+            history.record(Command::EditLighting { before, after: scene.lighting });
+         }
+
+         let before = scene.settings;
+         if scene.settings.ui(ui) {
+            history.record(Command::EditSettings { before, after: scene.settings });
+         }
+      });
+   }
+
+   fn materials_ui(&mut self, ui: &mut Ui, document: &mut Document) {
+      let Document { scene, history } = document;
+      self.material = self.material.min(scene.materials.len().saturating_sub(1));
+
+      CollapsingHeader::new("Materials")
+          .default_open(true)
+          .show(ui, |ui| {
+             ui.horizontal(|ui| {
+                material_combo(ui, "inspector_material", &scene.materials, &mut self.material);
+                if ui.button("Add").clicked() {
+                   let material = Material::new(&format!("Material {}", scene.materials.len()), [0.8, 0.8, 0.8]);
+                   history.execute(scene, Command::AddMaterial { material });
+                   self.material = scene.materials.len() - 1;
+                }
+             });
+
+             let index = self.material;
+             let Some(material) = scene.materials.get_mut(index) else { return };
+             let before = material.clone();
+
+             if material.fields_ui(ui) {
+                history.record(Command::EditMaterial { index, before, after: material.clone() });
+             }
+          });
+   }
+}
+
+
+fn node_ui(ui: &mut Ui, document: &mut Document, id: NodeId) {
+   let Document { scene, history } = document;
+   let materials = scene.materials.clone();
+   let Some(node) = scene.node_mut(id) else { return };
+   let before = node.clone();
+
+   let mut changed = ui.add(TextEdit::singleline(&mut node.name).desired_width(f32::INFINITY)).changed();
+   ui.weak(node.kind.label());
+
+   CollapsingHeader::new("Transform")
+       .default_open(true)
+       .show(ui, |ui| changed |= node.transform.fields_ui(ui));
+
+   if let NodeKind::Primitive(_) = node.kind {
+      ui.horizontal(|ui| {
+         let mut material = node.material.0;
+         material_combo(ui, ("node_material", id), &materials, &mut material);
+         ui.label("material");
+         if material != node.material.0 {
+            node.material = MaterialId(material);
+            changed = true;
+         }
+      });
+   }
+
+   changed |= match &mut node.kind {
+      NodeKind::Primitive(primitive) => primitive.ui(ui),
+      NodeKind::Operation(operation) => operation.ui(ui),
+      NodeKind::Domain(domain) => domain.ui(ui),
+      NodeKind::Group => false,
+   };
+
+   if changed {
+      history.edit_node(scene, id, before);
+   }
+}
+
+fn lights_ui(ui: &mut Ui, document: &mut Document) {
+   let Document { scene, history } = document;
+
+   let mut remove = None;
+   for (index, light) in scene.lights.iter_mut().enumerate() {
+      ui.push_id(("light", index), |ui| {
+         let before = *light;
+         if light.ui(ui) {
+            history.record(Command::EditLight { index, before, after: *light });
+         }
+         if ui.small_button("Delete light").clicked() {
+            remove = Some(index);
+         }
+      });
+   }
+   if let Some(index) = remove {
+      let light = scene.lights[index];
+      history.execute(scene, Command::RemoveLight { index, light });
+   }
+
+   if scene.lights.len() > MAX_LIGHTS {
+      ui.colored_label(ui.visuals().warn_fg_color, format!("only the first {MAX_LIGHTS} lights are drawn"));
+   }
+   ui.menu_button("Add light", |ui| {
+      for kind in LightKind::ALL {
+         if ui.button(kind.name()).clicked() {
+            let index = scene.lights.len();
+            history.execute(scene, Command::AddLight { index, light: Light::new(kind) });
+            ui.close_menu();
+         }
+      }
+   });
+}
+
+fn material_combo(ui: &mut Ui, id_source: impl std::hash::Hash, materials: &[Material], index: &mut usize) {
+   let selected = materials.get(*index).map_or("", |material| material.name.as_str());
+   ComboBox::from_id_source(id_source)
+       .selected_text(selected)
+       .show_ui(ui, |ui| {
+          for (i, material) in materials.iter().enumerate() {
+             ui.selectable_value(index, i, &material.name);
+          }
+       });
+}
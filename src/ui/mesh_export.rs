@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use egui::{ComboBox, Context, ProgressBar, Slider, Window};
+use crate::meshing::export::MeshFormat;
+use crate::meshing::mesh::{MeshSettings, Mesher, Progress};
+use crate::scene::graph::Scene;
+use crate::utility::widgets::{FieldOptions, ParamField};
+
+/// a mesher running on a background thread, finishes with a message for the window
+struct Job {
+   progress: Arc<Progress>,
+   handle: JoinHandle<Result<String, String>>,
+}
+
+
+/// the File > Export Mesh window
+pub struct MeshExport {
+   pub open: bool,
+   settings: MeshSettings,
+   format: MeshFormat,
+   job: Option<Job>,
+   /// outcome of the last export
+   message: Option<String>,
+}
+impl Default for MeshExport {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl MeshExport {
+   pub fn new() -> Self {
+      Self {
+         open: false,
+         settings: MeshSettings::default(),
+         format: MeshFormat::StlBinary,
+         job: None,
+         message: None,
+      }
+   }
+
+   pub fn ui(&mut self, context: &Context, scene: &Scene) {
+      if self.job.as_ref().is_some_and(|job| job.handle.is_finished()) {
+         if let Some(job) = self.job.take() {
+            self.message = Some(match job.handle.join() {
+               Ok(Ok(message) | Err(message)) => message,
+               Err(_) => "Mesh export panicked".to_string(),
+            });
+         }
+      }
+
+      let mut open = self.open;
+      Window::new("Export Mesh")
+          .open(&mut open)
+          .resizable(false)
+          .show(context, |ui| {
+             ui.add_enabled_ui(self.job.is_none(), |ui| {
+                ComboBox::from_label("mesher")
+                    .selected_text(self.settings.mesher.name())
+                    .show_ui(ui, |ui| {
+                       for mesher in Mesher::ALL {
+                          ui.selectable_value(&mut self.settings.mesher, mesher, mesher.name())
+                              .on_hover_text(mesher.description());
+                       }
+                    });
+
+                let mut options = FieldOptions::new("min");
+                options.step = Some(0.05);
+                self.settings.min.field_ui(ui, &options);
+                options.label = "max";
+                self.settings.max.field_ui(ui, &options);
+
+                ui.add(Slider::new(&mut self.settings.resolution, 8..=512).logarithmic(true).text("resolution"))
+                    .on_hover_text("cells along the longest side of the bounds");
+                let [x, y, z] = self.settings.cells();
+                ui.weak(format!("{x} × {y} × {z} cells, {:.4} per cell", self.settings.cell_size()));
+
+                ui.checkbox(&mut self.settings.close_boundary, "Close at bounds")
+                    .on_hover_text("caps anything cut off by the bounds so the mesh is watertight");
+
+                ComboBox::from_label("format")
+                    .selected_text(self.format.name())
+                    .show_ui(ui, |ui| {
+                       for format in MeshFormat::ALL {
+                          ui.selectable_value(&mut self.format, format, format.name());
+                       }
+                    });
+
+                if ui.button("Export...").clicked() {
+                   self.start(scene);
+                }
+             });
+
+             if let Some(job) = &self.job {
+                ui.horizontal(|ui| {
+                   let progress = &job.progress;
+                   ui.add(ProgressBar::new(progress.fraction()).text(progress.stage()).desired_width(200.0));
+                   if ui.button("Cancel").clicked() {
+                      progress.cancel();
+                   }
+                });
+                context.request_repaint();
+             }
+
+             if let Some(message) = &self.message {
+                ui.label(message);
+             }
+          });
+      self.open = open;
+   }
+
+   /// asks where to save then meshes a copy of the scene in the background
+   #[cfg(not(target_arch = "wasm32"))]
+   fn start(&mut self, scene: &Scene) {
+      use crate::meshing::export;
+
+      let format = self.format;
+      let Some(path) = rfd::FileDialog::new()
+          .add_filter(format.name(), &[format.extension()])
+          .set_file_name(format!("scene.{}", format.extension()))
+          .save_file() else { return };
+
+      let scene = scene.clone();
+      let settings = self.settings;
+      let progress = Arc::new(Progress::default());
+      let job_progress = progress.clone();
+
+      let handle = std::thread::spawn(move || {
+         let mesh = settings.mesher.mesh(&scene, &settings, &job_progress).ok_or("Export cancelled")?;
+         job_progress.start("Writing", 0);
+         export::save(&path, &mesh, format).map_err(|error| format!("{}: {error}", path.display()))?;
+         Ok(format!("Wrote {} triangles to {}", mesh.triangle_count(), path.display()))
+      });
+
+      self.message = None;
+      self.job = Some(Job { progress, handle });
+   }
+
+   #[cfg(target_arch = "wasm32")]
+   fn start(&mut self, _scene: &Scene) {
+      self.message = Some("Mesh export needs threads and a file system, it isn't available on the web".to_string());
+   }
+}
@@ -0,0 +1,309 @@
+use egui::collapsing_header::CollapsingState;
+use egui::{Frame, Key, ScrollArea, Sense, Stroke, TextEdit, Ui};
+use crate::scene::document::Document;
+use crate::scene::domain::DomainOp;
+use crate::scene::graph::Scene;
+use crate::scene::node::{Node, NodeId};
+use crate::scene::operators::{Blend, BooleanOp, Operation};
+use crate::scene::primitives::Primitive;
+
+/// what the tree asked for while drawing, applied once it's done borrowing the scene
+enum Action {
+   Select(NodeId),
+   Rename(NodeId, String),
+   ToggleVisible(NodeId),
+   ToggleSolo(NodeId),
+   Move { id: NodeId, parent: NodeId, index: usize },
+   Add { parent: NodeId, node: Node },
+   Wrap { id: NodeId, wrapper: Node },
+   Delete(NodeId),
+}
+
+
+/// node hierarchy in the left panel, also owns the selection and solo state
+pub struct Outliner {
+   pub selected: Option<NodeId>,
+   /// only this subtree is drawn while set, see `Scene::soloed`
+   pub solo: Option<NodeId>,
+   renaming: Option<(NodeId, String)>,
+}
+impl Default for Outliner {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl Outliner {
+   pub fn new() -> Self {
+      Self {
+         selected: None,
+         solo: None,
+         renaming: None,
+      }
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui, document: &mut Document) {
+      let mut actions = vec![];
+      let scene = &document.scene;
+      let root = scene.root();
+
+      ui.horizontal(|ui| {
+         ui.heading("Outliner");
+         ui.menu_button("+", |ui| {
+            if let Some(node) = new_node_menu(ui, true) {
+               actions.push(Action::Add { parent: root, node });
+               ui.close_menu();
+            }
+         });
+      });
+      ui.separator();
+
+      ScrollArea::vertical().show(ui, |ui| {
+         for child in scene.children(root) {
+            self.node_ui(ui, scene, *child, &mut actions);
+         }
+
+         // dropping below the tree moves the node to the end of the root
+         let (_, dropped) = ui.dnd_drop_zone::<NodeId, _>(Frame::none(), |ui| {
+            ui.allocate_space(ui.available_size().max(egui::vec2(0.0, 24.0)));
+         });
+         if let Some(id) = dropped {
+            actions.push(Action::Move { id: *id, parent: root, index: scene.children(root).len() });
+         }
+      });
+
+      for action in actions {
+         self.apply(document, action);
+      }
+
+      let scene = &document.scene;
+      self.selected = self.selected.filter(|id| scene.contains(*id));
+      self.solo = self.solo.filter(|id| scene.contains(*id));
+   }
+
+   fn node_ui(&mut self, ui: &mut Ui, scene: &Scene, id: NodeId, actions: &mut Vec<Action>) {
+      let Some(node) = scene.node(id) else { return };
+
+      if node.children.is_empty() {
+         ui.horizontal(|ui| {
+            // lines leaves up with the names of nodes that have a collapse arrow
+            ui.add_space(ui.spacing().icon_width + ui.spacing().item_spacing.x);
+            self.row_ui(ui, scene, id, actions);
+         });
+         return;
+      }
+
+      CollapsingState::load_with_default_open(ui.ctx(), ui.make_persistent_id(("outliner", id)), true)
+          .show_header(ui, |ui| self.row_ui(ui, scene, id, actions))
+          .body(|ui| {
+             for child in &node.children {
+                self.node_ui(ui, scene, *child, actions);
+             }
+          });
+   }
+
+   fn row_ui(&mut self, ui: &mut Ui, scene: &Scene, id: NodeId, actions: &mut Vec<Action>) {
+      let Some(node) = scene.node(id) else { return };
+
+      if ui.selectable_label(node.visible, "👁").on_hover_text("Visible").clicked() {
+         actions.push(Action::ToggleVisible(id));
+      }
+      if ui.selectable_label(self.solo == Some(id), "S").on_hover_text("Solo").clicked() {
+         actions.push(Action::ToggleSolo(id));
+      }
+
+      if let Some((renaming, buffer)) = &mut self.renaming {
+         if *renaming == id {
+            let response = ui.add(TextEdit::singleline(buffer).desired_width(120.0));
+            if !response.lost_focus() {
+               response.request_focus();
+            }
+            if ui.input(|input| input.key_pressed(Key::Escape)) {
+               self.renaming = None;
+            } else if response.lost_focus() {
+               actions.push(Action::Rename(id, buffer.clone()));
+               self.renaming = None;
+            }
+            return;
+         }
+      }
+
+      let response = ui.selectable_label(self.selected == Some(id), &node.name)
+          .interact(Sense::drag());
+      ui.weak(node.kind.label());
+
+      if response.clicked() {
+         actions.push(Action::Select(id));
+      }
+      if response.double_clicked() {
+         self.renaming = Some((id, node.name.clone()));
+      }
+
+      response.dnd_set_drag_payload(id);
+      if let Some(dragged) = response.dnd_hover_payload::<NodeId>() {
+         if *dragged != id {
+            let (_, into) = drop_target(scene, id, &response);
+            let rect = response.rect;
+            let stroke = Stroke::new(2.0, ui.visuals().selection.stroke.color);
+            if into {
+               ui.painter().rect_stroke(rect, 2.0, stroke);
+            } else if drops_before(&response) {
+               ui.painter().hline(rect.x_range(), rect.top(), stroke);
+            } else {
+               ui.painter().hline(rect.x_range(), rect.bottom(), stroke);
+            }
+         }
+      }
+      if let Some(dragged) = response.dnd_release_payload::<NodeId>() {
+         if *dragged != id {
+            let ((parent, index), _) = drop_target(scene, id, &response);
+            actions.push(Action::Move { id: *dragged, parent, index });
+         }
+      }
+
+      response.context_menu(|ui| {
+         if node.kind.accepts_children() {
+            ui.menu_button("Add child", |ui| {
+               if let Some(child) = new_node_menu(ui, true) {
+                  actions.push(Action::Add { parent: id, node: child });
+                  ui.close_menu();
+               }
+            });
+         }
+         ui.menu_button("Wrap in", |ui| {
+            if let Some(wrapper) = new_node_menu(ui, false) {
+               actions.push(Action::Wrap { id, wrapper });
+               ui.close_menu();
+            }
+         });
+         ui.separator();
+         if ui.button("Rename").clicked() {
+            self.renaming = Some((id, node.name.clone()));
+            ui.close_menu();
+         }
+         if ui.button("Delete").clicked() {
+            actions.push(Action::Delete(id));
+            ui.close_menu();
+         }
+      });
+   }
+
+   fn apply(&mut self, document: &mut Document, action: Action) {
+      let Document { scene, history } = document;
+
+      match action {
+         Action::Select(id) => self.selected = Some(id),
+
+         Action::Rename(id, name) => {
+            let Some(node) = scene.node_mut(id) else { return };
+            let before = node.clone();
+            node.name = name;
+            history.edit_node(scene, id, before);
+            history.seal();
+         }
+         Action::ToggleVisible(id) => {
+            let Some(node) = scene.node_mut(id) else { return };
+            let before = node.clone();
+            node.visible = !node.visible;
+            history.edit_node(scene, id, before);
+            history.seal();
+         }
+         Action::ToggleSolo(id) => {
+            self.solo = if self.solo == Some(id) { None } else { Some(id) };
+         }
+
+         Action::Move { id, parent, index } => {
+            history.reparent(scene, id, parent, index);
+         }
+         Action::Add { parent, node } => {
+            let index = scene.children(parent).len();
+            if let Some(id) = history.insert(scene, parent, index, node) {
+               self.selected = Some(id);
+            }
+         }
+         Action::Wrap { id, wrapper } => {
+            let Some(parent) = scene.node(id).and_then(|node| node.parent) else { return };
+            let Some(index) = scene.children(parent).iter().position(|child| *child == id) else { return };
+            let name = format!("Wrap in {}", wrapper.name);
+
+            let wrapper_id = history.batch(&name, |history| {
+               let wrapper_id = history.insert(scene, parent, index, wrapper)?;
+               history.reparent(scene, id, wrapper_id, 0);
+               Some(wrapper_id)
+            });
+            if wrapper_id.is_some() {
+               self.selected = wrapper_id;
+            }
+         }
+         Action::Delete(id) => {
+            if self.selected.is_some_and(|selected| selected == id || scene.is_ancestor(id, selected)) {
+               self.selected = None;
+            }
+            history.remove(scene, id);
+         }
+      }
+   }
+}
+
+
+fn drops_before(response: &egui::Response) -> bool {
+   response.hover_pos().is_some_and(|pos| pos.y < response.rect.center().y)
+}
+
+/// (parent, index) to move a dropped node to, and whether it goes inside `target`
+fn drop_target(scene: &Scene, target: NodeId, response: &egui::Response) -> ((NodeId, usize), bool) {
+   let node = scene.node(target);
+   let parent = node.and_then(|node| node.parent).unwrap_or(scene.root());
+   let index = scene.children(parent).iter().position(|child| *child == target).unwrap_or(0);
+
+   if drops_before(response) {
+      ((parent, index), false)
+   } else if node.is_some_and(|node| node.kind.accepts_children()) {
+      ((target, scene.children(target).len()), true)
+   } else {
+      ((parent, index + 1), false)
+   }
+}
+
+/// menu entries for every kind of node, primitives are left out where they can't hold children
+fn new_node_menu(ui: &mut Ui, primitives: bool) -> Option<Node> {
+   let mut chosen = None;
+
+   if primitives {
+      ui.menu_button("Primitive", |ui| {
+         for primitive in Primitive::all() {
+            if ui.button(primitive.name()).clicked() {
+               chosen = Some(Node::primitive(primitive.name(), primitive));
+            }
+         }
+      });
+   }
+
+   ui.menu_button("Operation", |ui| {
+      let operations = [
+         Operation::union(),
+         Operation::subtraction(),
+         Operation::intersection(),
+         Operation::blended(BooleanOp::Union, Blend::Smooth, 0.2),
+      ];
+      for operation in operations {
+         let name = operation.name();
+         if ui.button(&name).clicked() {
+            chosen = Some(Node::operation(&name, operation));
+         }
+      }
+   });
+
+   ui.menu_button("Domain", |ui| {
+      for domain in DomainOp::all() {
+         if ui.button(domain.name()).clicked() {
+            chosen = Some(Node::domain(domain.name(), domain));
+         }
+      }
+   });
+
+   if ui.button("Group").clicked() {
+      chosen = Some(Node::group("Group"));
+   }
+
+   chosen
+}
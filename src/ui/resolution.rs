@@ -0,0 +1,280 @@
+use egui::{DragValue, Rect, Slider, Ui, Vec2};
+use wgpu::FilterMode;
+
+pub const MIN_SCALE: f32 = 0.25;
+pub const MAX_SCALE: f32 = 2.0;
+
+/// automatic mode moves the scale in steps this big, so small wobbles in frame time don't remake the texture
+const AUTO_STEP: f32 = 0.05;
+
+/// frames automatic mode waits after changing the scale before judging the new frame time
+const AUTO_SETTLE: u32 = 30;
+
+/// largest pixel count along a side for a locked resolution
+const MAX_LOCKED: u32 = 8192;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResolutionMode {
+   /// the viewport's size times a fixed scale
+   Scaled,
+   /// the scale follows the frame time towards a target
+   Automatic,
+   /// a fixed pixel size, letterboxed into the viewport
+   Locked,
+}
+impl ResolutionMode {
+   pub const ALL: [ResolutionMode; 3] = [ResolutionMode::Scaled, ResolutionMode::Automatic, ResolutionMode::Locked];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         ResolutionMode::Scaled => "Scaled",
+         ResolutionMode::Automatic => "Automatic",
+         ResolutionMode::Locked => "Locked",
+      }
+   }
+
+   pub fn description(&self) -> &'static str {
+      match self {
+         ResolutionMode::Scaled => "Renders at a fixed fraction or multiple of the screen's pixels",
+         ResolutionMode::Automatic => "Lowers or raises the scale to keep the frame time near a target",
+         ResolutionMode::Locked => "Renders at an exact size whatever the viewport's shape, for final frames",
+      }
+   }
+}
+
+
+/// how the render is stretched to the viewport when the pixel counts differ
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Upscale {
+   /// blocky, shows the pixels as they are
+   Nearest,
+   /// smooth
+   Linear,
+}
+impl Upscale {
+   pub const ALL: [Upscale; 2] = [Upscale::Nearest, Upscale::Linear];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         Upscale::Nearest => "Nearest",
+         Upscale::Linear => "Linear",
+      }
+   }
+
+   pub fn filter(&self) -> FilterMode {
+      match self {
+         Upscale::Nearest => FilterMode::Nearest,
+         Upscale::Linear => FilterMode::Linear,
+      }
+   }
+}
+
+
+/// how many pixels the viewport renders and where they're drawn
+#[derive(Clone, Debug)]
+pub struct Resolution {
+   pub mode: ResolutionMode,
+   /// render pixels per screen pixel, set by hand when scaled and by the frame time when automatic
+   pub scale: f32,
+   /// milliseconds, automatic mode can't go below what vsync allows
+   pub target_frame_time: f32,
+   pub locked: [u32; 2],
+   pub upscale: Upscale,
+
+   /// smoothed milliseconds per frame
+   frame_time: f32,
+   settle: u32,
+}
+impl Default for Resolution {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl Resolution {
+   pub fn new() -> Self {
+      Self {
+         mode: ResolutionMode::Scaled,
+         scale: 1.0,
+         target_frame_time: 33.3,
+         locked: [1920, 1080],
+         upscale: Upscale::Linear,
+         frame_time: 0.0,
+         settle: 0,
+      }
+   }
+
+   /// the pixel size to render and the rect to draw it in, centred in `available` with bars either side when locked,
+   /// neither side goes over `max_side`, the device's texture limit
+   pub fn layout(&self, available: Rect, pixels_per_point: f32, max_side: u32) -> ([u32; 2], Rect) {
+      match self.mode {
+         ResolutionMode::Scaled | ResolutionMode::Automatic => {
+            (fit_within(available.size() * pixels_per_point * self.scale, max_side), available)
+         }
+         ResolutionMode::Locked => {
+            let [width, height] = self.locked_size(max_side);
+            let fit = (available.width() / width as f32).min(available.height() / height as f32);
+            let size = Vec2::new(width as f32, height as f32) * fit;
+            ([width, height], Rect::from_center_size(available.center(), size))
+         }
+      }
+   }
+
+   /// the locked size as it's rendered, shrunk to fit the device with its aspect kept
+   pub fn locked_size(&self, max_side: u32) -> [u32; 2] {
+      let [width, height] = self.locked.map(|side| side.clamp(1, MAX_LOCKED));
+      fit_within(Vec2::new(width as f32, height as f32), max_side)
+   }
+
+   /// feeds the last frame's length in seconds to automatic mode
+   pub fn record_frame(&mut self, seconds: f32) {
+      if self.mode != ResolutionMode::Automatic || seconds <= 0.0 {
+         return;
+      }
+      let milliseconds = seconds * 1000.0;
+      self.frame_time = match self.frame_time {
+         0.0 => milliseconds,
+         smoothed => smoothed + (milliseconds - smoothed) * 0.1,
+      };
+
+      if self.settle > 0 {
+         self.settle -= 1;
+         return;
+      }
+      // a band either side of the target so it settles rather than hunting back and forth
+      let ratio = self.target_frame_time / self.frame_time;
+      if (0.9..=1.3).contains(&ratio) {
+         return;
+      }
+
+      // the cost goes with the pixel count, the square of the scale
+      let wanted = (self.scale * ratio.sqrt()).clamp(self.scale - 0.25, self.scale + 0.25);
+      let scale = ((wanted / AUTO_STEP).round() * AUTO_STEP).clamp(MIN_SCALE, MAX_SCALE);
+      if scale != self.scale {
+         self.scale = scale;
+         self.settle = AUTO_SETTLE;
+      }
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui, rendered: [u32; 2], max_side: u32) {
+      for mode in ResolutionMode::ALL {
+         ui.radio_value(&mut self.mode, mode, mode.name()).on_hover_text(mode.description());
+      }
+      ui.separator();
+
+      match self.mode {
+         ResolutionMode::Scaled => {
+            ui.add(Slider::new(&mut self.scale, MIN_SCALE..=MAX_SCALE).step_by(0.05).text("scale"));
+         }
+         ResolutionMode::Automatic => {
+            ui.add(Slider::new(&mut self.target_frame_time, 4.0..=100.0).logarithmic(true).suffix(" ms").text("target"));
+            ui.label(format!("scale {:.2}, {:.1} ms per frame", self.scale, self.frame_time));
+         }
+         ResolutionMode::Locked => {
+            ui.horizontal(|ui| {
+               ui.add(DragValue::new(&mut self.locked[0]).range(1..=MAX_LOCKED));
+               ui.label("x");
+               ui.add(DragValue::new(&mut self.locked[1]).range(1..=MAX_LOCKED));
+            });
+            let [width, height] = self.locked_size(max_side);
+            if [width, height] != self.locked {
+               ui.label(format!("rendered at {width}x{height}, the most this device allows"));
+            }
+            ui.horizontal(|ui| {
+               for preset in [[1280, 720], [1920, 1080], [3840, 2160]] {
+                  if ui.button(format!("{}p", preset[1])).clicked() {
+                     self.locked = preset;
+                  }
+               }
+               if ui.button("Current").on_hover_text("The size being rendered now").clicked() {
+                  self.locked = rendered;
+               }
+            });
+         }
+      }
+      ui.separator();
+
+      ui.horizontal(|ui| {
+         ui.label("Upscale");
+         for upscale in Upscale::ALL {
+            ui.selectable_value(&mut self.upscale, upscale, upscale.name());
+         }
+      });
+   }
+}
+
+
+/// rounds `pixels` to whole pixels, shrinking both sides evenly if either is over `max_side`
+fn fit_within(pixels: Vec2, max_side: u32) -> [u32; 2] {
+   let pixels = pixels * (max_side as f32 / pixels.max_elem()).min(1.0);
+   let side = |pixels: f32| (pixels.round() as u32).clamp(1, max_side);
+   [side(pixels.x), side(pixels.y)]
+}
+
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use egui::Pos2;
+
+   #[test]
+   fn scaled_sizes_stay_under_the_texture_limit() {
+      let resolution = Resolution { scale: MAX_SCALE, ..Resolution::new() };
+      let available = Rect::from_min_size(Pos2::ZERO, Vec2::new(4000.0, 2000.0));
+      assert_eq!(resolution.layout(available, 1.0, 16384).0, [8000, 4000]);
+      assert_eq!(resolution.layout(available, 1.0, 4096).0, [4096, 2048]);
+   }
+
+   #[test]
+   fn locked_sizes_stay_under_the_texture_limit() {
+      let resolution = Resolution { mode: ResolutionMode::Locked, locked: [6000, 3000], ..Resolution::new() };
+      let available = Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0));
+      assert_eq!(resolution.layout(available, 1.0, 8192).0, [6000, 3000]);
+      assert_eq!(resolution.layout(available, 1.0, 4096).0, [4096, 2048]);
+      assert_eq!(resolution.locked_size(4096), [4096, 2048]);
+   }
+
+   fn assert_scale(resolution: &Resolution, scale: f32) {
+      assert!((resolution.scale - scale).abs() < 1e-4, "scale {} != {scale}", resolution.scale);
+   }
+
+   #[test]
+   fn automatic_scale_steps_settles_and_stays_in_range() {
+      let mut resolution = Resolution { mode: ResolutionMode::Automatic, ..Resolution::new() };
+
+      // three times too slow wants about 0.58, but one change moves at most a quarter
+      resolution.record_frame(0.1);
+      assert_scale(&resolution, 0.75);
+      for _ in 0..AUTO_SETTLE {
+         resolution.record_frame(0.1);
+         assert_scale(&resolution, 0.75);
+      }
+      resolution.record_frame(0.1);
+      assert_scale(&resolution, 0.5);
+
+      for _ in 0..10 * AUTO_SETTLE {
+         resolution.record_frame(0.1);
+         assert!(resolution.scale >= MIN_SCALE);
+      }
+      assert_scale(&resolution, MIN_SCALE);
+
+      for _ in 0..20 * AUTO_SETTLE {
+         resolution.record_frame(0.001);
+         assert!(resolution.scale <= MAX_SCALE);
+      }
+      assert_scale(&resolution, MAX_SCALE);
+   }
+
+   #[test]
+   fn frame_times_near_the_target_or_outside_automatic_leave_the_scale() {
+      let mut resolution = Resolution { mode: ResolutionMode::Automatic, ..Resolution::new() };
+      for _ in 0..100 {
+         resolution.record_frame(resolution.target_frame_time / 1000.0);
+      }
+      assert_scale(&resolution, 1.0);
+
+      let mut resolution = Resolution::new();
+      resolution.record_frame(0.1);
+      assert_scale(&resolution, 1.0);
+   }
+}
@@ -0,0 +1,242 @@
+use cgmath::{Vector2, Vector3};
+use egui::load::SizedTexture;
+use egui::{Align2, Color32, DragValue, FontId, Pos2, Response, Sense, Shape, Stroke, Ui};
+use crate::packages::slice_pipeline::SliceUniform;
+use crate::scene::eval;
+use crate::scene::graph::Scene;
+use crate::scene::node::NodeId;
+use crate::ui::gizmo::{self, HandleShape, Screen};
+use crate::utility::structs::EguiTexturePackage;
+
+/// zoom factor per pixel scrolled
+const ZOOM_SPEED: f32 = 0.002;
+
+/// length of the plane's handle in the 3d view, in pixels
+const HANDLE_PIXELS: f32 = 60.0;
+
+const PLANE_COLOR: Color32 = Color32::from_rgb(240, 200, 60);
+
+
+/// which world axis the slice cuts across
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SliceAxis {
+   X,
+   Y,
+   Z,
+}
+impl SliceAxis {
+   pub const ALL: [SliceAxis; 3] = [SliceAxis::X, SliceAxis::Y, SliceAxis::Z];
+
+   pub fn name(&self) -> &'static str {
+      match self {
+         SliceAxis::X => "X",
+         SliceAxis::Y => "Y",
+         SliceAxis::Z => "Z",
+      }
+   }
+
+   /// the image's right and up and the plane's normal, right cross up is the normal
+   pub fn basis(&self) -> [Vector3<f32>; 3] {
+      match self {
+         SliceAxis::X => [-Vector3::unit_z(), Vector3::unit_y(), Vector3::unit_x()],
+         SliceAxis::Y => [Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()],
+         SliceAxis::Z => [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+      }
+   }
+}
+
+
+/// a second viewport showing the signed distance on a plane through the scene, the plane is drawn
+/// in the 3d view and its handle drags it along its normal
+///
+/// evenly spaced contours mean the field is a true distance, uneven ones that it's only a bound
+pub struct SliceView {
+   pub open: bool,
+   pub axis: SliceAxis,
+   /// how far along its normal the plane is from the origin
+   pub offset: f32,
+   /// position of the image's centre along its right and up
+   pub center: [f32; 2],
+   /// world units from the bottom of the image to the top
+   pub extent: f32,
+   /// distance between contour lines, 0 for none
+   pub contour_spacing: f32,
+   pub zero_highlight: bool,
+
+   /// width over height of the image last frame, for the plane's outline in the 3d view
+   aspect: f32,
+   /// offset when the handle was grabbed and where the pointer was
+   drag: Option<(f32, Vector2<f32>)>,
+}
+impl Default for SliceView {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl SliceView {
+   pub fn new() -> Self {
+      Self {
+         open: false,
+         axis: SliceAxis::Z,
+         offset: 0.0,
+         center: [0.0, 0.0],
+         extent: 8.0,
+         contour_spacing: 0.25,
+         zero_highlight: true,
+         aspect: 1.0,
+         drag: None,
+      }
+   }
+
+   /// world position of the image's centre
+   pub fn origin(&self) -> Vector3<f32> {
+      let [right, up, normal] = self.axis.basis();
+      right * self.center[0] + up * self.center[1] + normal * self.offset
+   }
+
+   /// world point under a pixel, measured from the image's centre with y down
+   fn point(&self, pixel: Vector2<f32>, height: f32) -> Vector3<f32> {
+      let [right, up, _] = self.axis.basis();
+      let scale = self.extent / height.max(1.0);
+      self.origin() + (right * pixel.x - up * pixel.y) * scale
+   }
+
+   pub fn uniform(&self, resolution: (u32, u32)) -> SliceUniform {
+      let [right, up, _] = self.axis.basis();
+      let origin = self.origin();
+      SliceUniform {
+         origin: [origin.x, origin.y, origin.z, self.extent / resolution.1.max(1) as f32],
+         right: [right.x, right.y, right.z, self.contour_spacing.max(0.0)],
+         up: [up.x, up.y, up.z, self.zero_highlight as u32 as f32],
+         resolution: [resolution.0.max(1) as f32, resolution.1.max(1) as f32],
+         range: self.extent * 0.25,
+         _padding: 0.0,
+      }
+   }
+
+   /// the controls and the image, drag to pan and scroll to zoom
+   pub fn ui(&mut self, ui: &mut Ui, texture_package: &mut EguiTexturePackage, scene: &Scene, solo: Option<NodeId>) {
+      ui.horizontal_wrapped(|ui| {
+         for axis in SliceAxis::ALL {
+            ui.selectable_value(&mut self.axis, axis, axis.name())
+                .on_hover_text(format!("Cut across the {} axis", axis.name()));
+         }
+         ui.add(DragValue::new(&mut self.offset).speed(0.01).prefix("at "))
+             .on_hover_text("Distance of the plane from the origin along its axis");
+         ui.add(DragValue::new(&mut self.contour_spacing).range(0.0..=100.0).speed(0.005).prefix("contours "))
+             .on_hover_text("Distance between contour lines, 0 for none");
+         ui.checkbox(&mut self.zero_highlight, "Zero")
+             .on_hover_text("Highlight where the distance is zero, the surface");
+         if ui.button("Reset").clicked() {
+            self.center = [0.0, 0.0];
+            self.extent = Self::new().extent;
+         }
+      });
+
+      ui.set_min_height(1.0);
+      let size = ui.available_size();
+      let pixels = size * ui.ctx().pixels_per_point();
+      texture_package.size = wgpu::Extent3d {
+         width: (pixels.x as u32).max(1),
+         height: (pixels.y as u32).max(1),
+         depth_or_array_layers: 1,
+      };
+      self.aspect = size.x / size.y.max(1.0);
+
+      let texture = SizedTexture::new(texture_package.texture_id, size);
+      let response = ui.add(egui::Image::new(texture).fit_to_exact_size(size).sense(Sense::drag()));
+      let rect = response.rect;
+      let scale = self.extent / rect.height().max(1.0);
+
+      if response.dragged() {
+         let delta = response.drag_delta();
+         self.center[0] -= delta.x * scale;
+         self.center[1] += delta.y * scale;
+      }
+
+      let Some(pointer) = response.hover_pos() else { return };
+      let pixel = Vector2::new(pointer.x - rect.center().x, pointer.y - rect.center().y);
+
+      // zoom about the pointer, keeping the point under it still
+      let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+      if scroll != 0.0 {
+         let zoom = (-scroll * ZOOM_SPEED).exp();
+         self.center[0] += pixel.x * scale * (1.0 - zoom);
+         self.center[1] -= pixel.y * scale * (1.0 - zoom);
+         self.extent = (self.extent * zoom).clamp(1e-3, 1e4);
+      }
+
+      let p = self.point(pixel, rect.height());
+      let distance = eval::eval_drawn(scene, solo, p).dist;
+      ui.painter_at(rect).text(
+         rect.left_bottom() + egui::vec2(6.0, -6.0),
+         Align2::LEFT_BOTTOM,
+         format!("{distance:.4} at ({:.3}, {:.3}, {:.3})", p.x, p.y, p.z),
+         FontId::monospace(12.0),
+         Color32::WHITE,
+      );
+   }
+
+   /// draws the plane in the 3d view and lets its handle be dragged along the normal,
+   /// returns true if the handle took the pointer this frame
+   pub fn plane_ui(&mut self, ui: &Ui, response: &Response, screen: &Screen) -> bool {
+      let [right, up, normal] = self.axis.basis();
+      let origin = self.origin();
+      let to_vector = |pos: Pos2| Vector2::new(pos.x, pos.y);
+      let to_pos = |v: Vector2<f32>| Pos2::new(v.x, v.y);
+
+      if let Some((start, press)) = self.drag {
+         let pointer = ui.input(|input| input.pointer.latest_pos()).map(to_vector);
+         let moved = pointer.and_then(|pointer| {
+            // measured from where the plane was when grabbed, so the handle stays under the pointer
+            let grabbed = origin + normal * (start - self.offset);
+            Some(gizmo::line_parameter(grabbed, normal, screen.ray(pointer))?
+                - gizmo::line_parameter(grabbed, normal, screen.ray(press))?)
+         });
+         if let Some(moved) = moved {
+            self.offset = start + moved;
+         }
+         if !ui.input(|input| input.pointer.primary_down()) {
+            self.drag = None;
+         }
+      }
+
+      let painter = ui.painter_at(response.rect);
+      let (half_width, half_height) = (self.extent * self.aspect * 0.5, self.extent * 0.5);
+      let corners: Option<Vec<Pos2>> = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+          .map(|(x, y)| screen.project(origin + right * (x * half_width) + up * (y * half_height)).map(to_pos))
+          .collect();
+      if let Some(corners) = corners {
+         painter.add(Shape::closed_line(corners, Stroke::new(1.5, PLANE_COLOR)));
+      }
+
+      let Some(center) = screen.project(origin) else { return self.drag.is_some() };
+      let Some(tip) = screen.project(origin + normal * screen.world_length(origin, HANDLE_PIXELS)) else {
+         return self.drag.is_some();
+      };
+      let handle = HandleShape::Line(center, tip);
+      let hovered = response.hover_pos().is_some_and(|pointer| handle.distance(to_vector(pointer)) <= gizmo::HIT_PIXELS);
+
+      if self.drag.is_none() && response.drag_started_by(egui::PointerButton::Primary) {
+         let press = ui.input(|input| input.pointer.press_origin()).map(to_vector);
+         if let Some(press) = press.filter(|press| handle.distance(*press) <= gizmo::HIT_PIXELS) {
+            self.drag = Some((self.offset, press));
+         }
+      }
+
+      let color = if hovered || self.drag.is_some() { Color32::WHITE } else { PLANE_COLOR };
+      painter.line_segment([to_pos(center), to_pos(tip)], Stroke::new(2.5, color));
+      painter.circle_filled(to_pos(tip), 4.0, color);
+      painter.circle_stroke(to_pos(center), 4.0, Stroke::new(1.5, color));
+
+      painter.text(
+         to_pos(tip) + egui::vec2(6.0, 0.0),
+         Align2::LEFT_CENTER,
+         format!("{} {:.3}", self.axis.name(), self.offset),
+         FontId::proportional(12.0),
+         color,
+      );
+
+      self.drag.is_some()
+   }
+}
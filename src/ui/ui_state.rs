@@ -1,46 +1,156 @@
 use catppuccin_egui::Theme;
-use egui::{CentralPanel, Context, menu, SidePanel, TopBottomPanel, Ui, Vec2, Visuals};
-use egui::load::SizedTexture;
+use egui::{CentralPanel, Context, menu, SidePanel, TopBottomPanel, Ui, Visuals};
 use egui::panel::{Side, TopBottomSide};
-use egui_plot::{Line, Plot, PlotPoints};
 use egui_wgpu::ScreenDescriptor;
-use wgpu::{CommandEncoder, Extent3d, TextureView};
+use wgpu::{CommandEncoder, TextureView};
 use crate::inbuilt::gui_state::EguiRenderer;
 use crate::inbuilt::setup::Setup;
+use crate::packages::path_trace_pipeline::PathTraceSettings;
+use crate::packages::post_process_pipeline::PostSettings;
+use crate::packages::scene_render_pipeline::{DebugView, RenderMode};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::scene::file;
+use crate::scene::document::Document;
+use crate::scene::graph::Scene;
+use crate::scene::camera::Camera;
+use crate::scene::node::NodeId;
+use crate::ui::inspector::Inspector;
+use crate::ui::mesh_export::MeshExport;
+use crate::ui::outliner::Outliner;
+use crate::ui::slice_view::SliceView;
+use crate::ui::viewport::{CameraMode, Viewport};
 use crate::utility::structs::EguiTexturePackage;
 
+/// the path tracer's controls, copied to and from `PathTracePipeline` each frame
+#[derive(Clone, Debug, Default)]
+pub struct PathTraceControls {
+   pub settings: PathTraceSettings,
+   /// samples accumulated so far, read back from the path tracer
+   pub samples: u32,
+   /// set by the restart button, cleared once passed on
+   pub restart: bool,
+}
+
+
 pub struct UiState {
    theme: Theme,
+   pub render_mode: RenderMode,
+   pub path_trace: PathTraceControls,
+   pub post_process: PostSettings,
+   post_process_open: bool,
+   pub slice: SliceView,
+   pub outliner: Outliner,
+   inspector: Inspector,
+   viewport: Viewport,
+   mesh_export: MeshExport,
+
+   /// where the open scene was loaded from or last saved to
+   file_path: Option<std::path::PathBuf>,
+   file_error: Option<String>,
 }
+impl Default for UiState {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+
 impl UiState {
    pub fn new() -> Self {
 
       Self {
          theme: catppuccin_egui::FRAPPE,
+         render_mode: RenderMode::Compiled,
+         path_trace: PathTraceControls::default(),
+         post_process: PostSettings::default(),
+         post_process_open: false,
+         slice: SliceView::new(),
+         outliner: Outliner::new(),
+         inspector: Inspector::new(),
+         viewport: Viewport::new(),
+         mesh_export: MeshExport::new(),
+         file_path: None,
+         file_error: None,
       }
    }
 
-   fn ui(&mut self, context: &Context, egui_texture_package: &mut EguiTexturePackage) {
-      catppuccin_egui::set_theme(&context, self.theme);
+   fn ui(&mut self, context: &Context, egui_texture_package: &mut EguiTexturePackage, slice_texture_package: &mut EguiTexturePackage, document: &mut Document) {
+      catppuccin_egui::set_theme(context, self.theme);
+
+      // a drag in progress or a text field being typed in keeps merging into the same undo step
+      if !context.input(|input| input.pointer.any_down()) && !context.wants_keyboard_input() {
+         document.history.seal();
+      }
 
       CentralPanel::default().show(context, |ui| {
          ui.group(|ui| {
             menu::bar(ui, |ui| {
                ui.menu_button("File", |ui| {
-                  if ui.button("Open").clicked() {
-                     // Handle open action
-                  }
-                  if ui.button("Save").clicked() {
-                     // Handle save action
+                  // rfd only has async dialogs on the web
+                  ui.add_enabled_ui(!cfg!(target_arch = "wasm32"), |ui| {
+                     if ui.button("Open").clicked() {
+                        self.open(document);
+                        ui.close_menu();
+                     }
+                     if ui.button("Save").clicked() {
+                        self.save(&document.scene, false);
+                        ui.close_menu();
+                     }
+                     if ui.button("Save As").clicked() {
+                        self.save(&document.scene, true);
+                        ui.close_menu();
+                     }
+                  });
+                  ui.separator();
+                  if ui.button("Export Mesh...").clicked() {
+                     self.mesh_export.open = true;
+                     ui.close_menu();
                   }
                });
 
                ui.menu_button("Edit", |ui| {
-                  if ui.button("Undo").clicked() {
-                     // Handle undo action
+                  let history = &document.history;
+                  let (can_undo, can_redo) = (history.can_undo(), history.can_redo());
+                  let undo = history.undo_name().map_or("Undo".to_string(), |name| format!("Undo {name}"));
+                  let redo = history.redo_name().map_or("Redo".to_string(), |name| format!("Redo {name}"));
+
+                  if ui.add_enabled(can_undo, egui::Button::new(undo).shortcut_text("Ctrl+Z")).clicked() {
+                     document.undo();
+                     ui.close_menu();
                   }
-                  if ui.button("Redo").clicked() {
-                     // Handle redo action
+                  if ui.add_enabled(can_redo, egui::Button::new(redo).shortcut_text("Ctrl+Shift+Z")).clicked() {
+                     document.redo();
+                     ui.close_menu();
+                  }
+               });
+
+               ui.menu_button("View", |ui| {
+                  ui.label("Render mode");
+                  ui.radio_value(&mut self.render_mode, RenderMode::Compiled, "Compiled");
+                  // webgl has no storage buffers in fragment shaders, or compute shaders
+                  if !cfg!(target_arch = "wasm32") {
+                     ui.radio_value(&mut self.render_mode, RenderMode::Interpreted, "Interpreted");
+                     ui.radio_value(&mut self.render_mode, RenderMode::PathTraced, "Path traced")
+                         .on_hover_text("Adds a sample per pixel each frame, restarts whenever the view or scene changes");
+                     ui.radio_value(&mut self.render_mode, RenderMode::Compute, "Compute")
+                         .on_hover_text("The compiled shader as a compute shader, tiles skip the empty space in front of them together");
+                  }
+
+                  ui.checkbox(&mut self.slice.open, "Slice viewer")
+                      .on_hover_text("A cut through the distance field beside the viewport, drag the plane's handle to move it");
+                  if ui.button("Post Processing...").clicked() {
+                     self.post_process_open = true;
+                     ui.close_menu();
+                  }
+
+                  ui.separator();
+                  ui.label("Camera");
+                  ui.radio_value(&mut self.viewport.mode, CameraMode::Orbit, "Orbit")
+                      .on_hover_text("Drag to orbit, right drag or shift drag to pan, scroll to zoom");
+                  ui.radio_value(&mut self.viewport.mode, CameraMode::Fly, "Fly")
+                      .on_hover_text("Drag to look, WASD to move, Q and E for down and up, scroll for speed");
+                  if ui.button("Reset camera").clicked() {
+                     document.scene.camera = Camera::default();
+                     ui.close_menu();
                   }
                });
 
@@ -64,23 +174,98 @@ impl UiState {
             });
          });
 
+         if let Some(error) = &self.file_error {
+            let mut dismissed = false;
+            ui.horizontal(|ui| {
+               ui.colored_label(ui.visuals().error_fg_color, error);
+               dismissed = ui.small_button("x").clicked();
+            });
+            if dismissed {
+               self.file_error = None;
+            }
+         }
 
-         self.panels(ui, egui_texture_package);
+         self.panels(ui, egui_texture_package, slice_texture_package, document);
       });
 
+      self.mesh_export.ui(context, &document.scene);
+      self.post_process.window(context, &mut self.post_process_open);
+
+   }
+
+   /// escape would cancel something of ours rather than quit
+   pub fn escape_in_use(&self) -> bool {
+      self.viewport.dragging()
+   }
+
+   /// what the viewport shows in place of the lit scene
+   pub fn debug_view(&self) -> DebugView {
+      self.viewport.debug_view
    }
 
-   fn panels(&mut self, ui: &mut Ui, egui_texture_package: &mut EguiTexturePackage) {
+   /// the node the viewport should draw on its own, if any
+   pub fn solo(&self) -> Option<NodeId> {
+      self.outliner.solo
+   }
+
+   #[cfg(not(target_arch = "wasm32"))]
+   fn open(&mut self, document: &mut Document) {
+      let Some(path) = rfd::FileDialog::new()
+          .add_filter("Scene", &[file::EXTENSION])
+          .pick_file() else { return };
+
+      match file::load(&path) {
+         Ok(loaded) => {
+            document.replace(loaded);
+            self.outliner = Outliner::new();
+            self.file_path = Some(path);
+            self.file_error = None;
+         }
+         Err(error) => self.file_error = Some(format!("{}: {error}", path.display())),
+      }
+   }
+
+   /// asks for a path the first time, or always when `choose_path` is set
+   #[cfg(not(target_arch = "wasm32"))]
+   fn save(&mut self, scene: &Scene, choose_path: bool) {
+      let path = match (&self.file_path, choose_path) {
+         (Some(path), false) => path.clone(),
+         _ => {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("Scene", &[file::EXTENSION])
+                .set_file_name(format!("scene.{}", file::EXTENSION))
+                .save_file() else { return };
+            path
+         }
+      };
+
+      match file::save(&path, scene) {
+         Ok(()) => {
+            self.file_path = Some(path);
+            self.file_error = None;
+         }
+         Err(error) => self.file_error = Some(format!("{}: {error}", path.display())),
+      }
+   }
+
+   #[cfg(target_arch = "wasm32")]
+   fn open(&mut self, _document: &mut Document) {}
+
+   #[cfg(target_arch = "wasm32")]
+   fn save(&mut self, _scene: &Scene, _choose_path: bool) {}
+
+   fn panels(&mut self, ui: &mut Ui, egui_texture_package: &mut EguiTexturePackage, slice_texture_package: &mut EguiTexturePackage, document: &mut Document) {
       SidePanel::new(Side::Left, "left")
           .resizable(true)
           .show_inside(ui, |ui| {
-             ui.allocate_space(ui.available_size());
+             self.outliner.ui(ui, document);
           });
 
       SidePanel::new(Side::Right, "right")
-          .resizable(false)
-          .max_width(0.0)
-          .show_inside(ui, |_ui| {});
+          .resizable(true)
+          .show_inside(ui, |ui| {
+             self.inspector.ui(ui, document, self.outliner.selected);
+          });
 
       CentralPanel::default() // right panel
           .show_inside(ui, |ui| {
@@ -94,19 +279,42 @@ impl UiState {
 
              CentralPanel::default() // image panel
                  .show_inside(ui, |ui| {
-                    ui.set_min_height(1.0);
-                    let ms = to_extent(ui.available_size());
-                    egui_texture_package.size = ms;
-
-                    let st = SizedTexture::new(
-                       egui_texture_package.texture_id,
-                       to_v2(egui_texture_package.texture.size())
-                    );
-                    ui.add(egui::Image::new(st));
+                    if self.render_mode == RenderMode::PathTraced {
+                       self.path_trace_bar(ui);
+                    }
+                    if self.slice.open {
+                       SidePanel::new(Side::Right, "slice")
+                           .resizable(true)
+                           .default_width(320.0)
+                           .show_inside(ui, |ui| {
+                              self.slice.ui(ui, slice_texture_package, &document.scene, self.outliner.solo);
+                           });
+                    }
+                    let slice = self.slice.open.then_some(&mut self.slice);
+                    self.viewport.ui(ui, egui_texture_package, document, &mut self.outliner, slice);
                  });
           });
    }
 
+   fn path_trace_bar(&mut self, ui: &mut Ui) {
+      let controls = &mut self.path_trace;
+      let settings = &mut controls.settings;
+      ui.horizontal(|ui| {
+         let budget = settings.budget.max(1);
+         ui.add(egui::ProgressBar::new(controls.samples.min(budget) as f32 / budget as f32)
+             .desired_width(160.0)
+             .text(format!("{} / {budget} samples", controls.samples)));
+
+         ui.add(egui::DragValue::new(&mut settings.budget).range(1..=65536).speed(4.0).prefix("budget "))
+             .on_hover_text("Samples per pixel after which tracing stops");
+         ui.add(egui::DragValue::new(&mut settings.max_bounces).range(0..=16).prefix("bounces "))
+             .on_hover_text("Bounces after the first hit, more brightens shadows and adds reflections of reflections");
+         if ui.button("Restart").clicked() {
+            controls.restart = true;
+         }
+      });
+   }
+
    fn bottom_right(&mut self, ui: &mut Ui) {
       self.statistics(ui)
    }
@@ -151,11 +359,14 @@ impl UiState {
 
    }
 
+   #[allow(clippy::too_many_arguments)]
    pub fn render_and_update(&mut self,
     setup: &Setup,
     egui_renderer: &mut EguiRenderer,
     egui_texture_package: &mut EguiTexturePackage,
+    slice_texture_package: &mut EguiTexturePackage,
     view: &TextureView, encoder: &mut CommandEncoder,
+    document: &mut Document,
     // time_package: &TimePackage,
    ) {
       #[allow(unused_assignments)]
@@ -178,30 +389,18 @@ impl UiState {
       }
 
       let run_ui = |context: &Context| {
-         self.ui(&context, egui_texture_package);
+         self.ui(context, egui_texture_package, slice_texture_package, document);
       };
 
       egui_renderer.draw(
          &setup.device,
          &setup.queue,
          encoder,
-         &setup.window,
-         &view,
+         setup.window,
+         view,
          screen_descriptor,
          run_ui,
       );
    }
 }
 
-
-fn to_v2(extent: Extent3d) -> Vec2 {
-   Vec2::new(extent.width as f32, extent.height as f32)
-}
-
-fn to_extent(vec2: Vec2) -> Extent3d {
-   Extent3d {
-      width: vec2.x as u32,
-      height: vec2.y as u32,
-      depth_or_array_layers: 1,
-   }
-}
\ No newline at end of file
@@ -0,0 +1,261 @@
+use cgmath::{Vector2, Vector3};
+use egui::load::SizedTexture;
+use egui::{Color32, Key, PointerButton, Response, Sense, Ui};
+use crate::packages::scene_render_pipeline::DebugView;
+use crate::scene::camera::Camera;
+use crate::scene::document::Document;
+use crate::scene::node::NodeId;
+use crate::scene::picking;
+use crate::ui::gizmo::{self, GizmoDrag, GizmoMode, GizmoSpace, Screen};
+use crate::ui::outliner::Outliner;
+use crate::ui::resolution::Resolution;
+use crate::ui::slice_view::SliceView;
+use crate::utility::functions::get_mouse_position;
+use crate::utility::structs::EguiTexturePackage;
+
+/// radians turned per pixel dragged
+const TURN_SPEED: f32 = 0.008;
+
+/// zoom factor per pixel scrolled
+const ZOOM_SPEED: f32 = 0.002;
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+   /// left drag orbits the target, right or middle drag pans, scroll zooms
+   Orbit,
+   /// drag to look around, wasd to move, q and e for down and up, scroll changes the speed
+   Fly,
+}
+
+
+/// the rendered image, takes camera input only while the pointer is over it,
+/// selects what's clicked on and draws a transform gizmo on the selected node
+///
+/// camera moves aren't recorded in the undo history, gizmo drags are one step each
+pub struct Viewport {
+   pub mode: CameraMode,
+   /// units per second in fly mode, shift doubles it
+   pub fly_speed: f32,
+   pub gizmo_mode: GizmoMode,
+   pub gizmo_space: GizmoSpace,
+   pub resolution: Resolution,
+   pub debug_view: DebugView,
+   drag: Option<GizmoDrag>,
+}
+impl Default for Viewport {
+   fn default() -> Self {
+      Self::new()
+   }
+}
+impl Viewport {
+   pub fn new() -> Self {
+      Self {
+         mode: CameraMode::Orbit,
+         fly_speed: 2.0,
+         gizmo_mode: GizmoMode::Translate,
+         gizmo_space: GizmoSpace::World,
+         resolution: Resolution::new(),
+         debug_view: DebugView::Shaded,
+         drag: None,
+      }
+   }
+
+   /// a gizmo is being dragged, escape puts the node back
+   pub fn dragging(&self) -> bool {
+      self.drag.is_some()
+   }
+
+   pub fn ui(&mut self, ui: &mut Ui, egui_texture_package: &mut EguiTexturePackage, document: &mut Document, outliner: &mut Outliner, slice: Option<&mut SliceView>) {
+      let texture_size = egui_texture_package.texture.size();
+      self.toolbar(ui, [texture_size.width, texture_size.height], egui_texture_package.max_side());
+      ui.set_min_height(1.0);
+      let (available, _) = ui.allocate_exact_size(ui.available_size(), Sense::hover());
+
+      self.resolution.record_frame(ui.input(|input| input.unstable_dt));
+      let ([width, height], rect) = self.resolution.layout(available, ui.ctx().pixels_per_point(), egui_texture_package.max_side());
+      egui_texture_package.size = wgpu::Extent3d { width, height, depth_or_array_layers: 1 };
+      egui_texture_package.filter = self.resolution.upscale.filter();
+
+      if rect != available {
+         ui.painter().rect_filled(available, 0.0, Color32::BLACK);
+      }
+      let texture = SizedTexture::new(egui_texture_package.texture_id, rect.size());
+      let response = ui.put(rect, egui::Image::new(texture).fit_to_exact_size(rect.size()).sense(Sense::click_and_drag()));
+
+      let slice_used = slice.is_some_and(|slice| slice.plane_ui(ui, &response, &screen(document.scene.camera, response.rect)));
+      let gizmo_used = slice_used || self.gizmo_ui(ui, &response, document, outliner.selected);
+
+      if !gizmo_used && response.clicked() {
+         outliner.selected = pick(ui, &response, document, outliner.solo);
+      }
+
+      if !gizmo_used && (response.hovered() || response.dragged()) {
+         let camera = &mut document.scene.camera;
+         match self.mode {
+            CameraMode::Orbit => orbit_input(ui, &response, camera),
+            CameraMode::Fly => self.fly_input(ui, &response, camera),
+         }
+      }
+   }
+
+   fn toolbar(&mut self, ui: &mut Ui, rendered: [u32; 2], max_side: u32) {
+      ui.horizontal(|ui| {
+         ui.menu_button(format!("{}x{}", rendered[0], rendered[1]), |ui| self.resolution.ui(ui, rendered, max_side))
+             .response.on_hover_text("Render resolution");
+         ui.label("View").on_hover_text(self.debug_view.description());
+         self.debug_view.dropdown(ui);
+         ui.separator();
+         ui.selectable_value(&mut self.gizmo_mode, GizmoMode::Translate, "Move");
+         ui.selectable_value(&mut self.gizmo_mode, GizmoMode::Rotate, "Rotate");
+         ui.selectable_value(&mut self.gizmo_mode, GizmoMode::Scale, "Scale");
+         ui.separator();
+         ui.selectable_value(&mut self.gizmo_space, GizmoSpace::World, "World");
+         ui.selectable_value(&mut self.gizmo_space, GizmoSpace::Local, "Local");
+
+         if let Some(drag) = &self.drag {
+            ui.separator();
+            let typed = if drag.typed.is_empty() { "type a value, x y z to constrain" } else { drag.typed.as_str() };
+            ui.label(format!("{}: {typed}", drag.handle.label()));
+         }
+      });
+   }
+
+   /// returns true if the gizmo took the pointer this frame
+   fn gizmo_ui(&mut self, ui: &Ui, response: &Response, document: &mut Document, selected: Option<NodeId>) -> bool {
+      let Document { scene, history } = document;
+      let screen = screen(scene.camera, response.rect);
+      let to_vector = |pos: egui::Pos2| Vector2::new(pos.x, pos.y);
+
+      let selected = selected.filter(|id| scene.contains(*id));
+      if self.drag.as_ref().is_some_and(|drag| Some(drag.id) != selected) {
+         self.drag = None;
+      }
+      let Some(id) = selected else { return false };
+
+      let world = scene.world_frame(id);
+      let axes = gizmo::axes(&world, self.gizmo_space);
+
+      if let Some(drag) = &mut self.drag {
+         let (cancel, confirm) = ui.input(|input| {
+            drag.handle_keys(input, self.gizmo_mode);
+            (input.key_pressed(Key::Escape), input.key_pressed(Key::Enter))
+         });
+
+         if cancel {
+            if let Some(node) = scene.node_mut(id) {
+               node.transform = drag.before.transform;
+            }
+            self.drag = None;
+            return true;
+         }
+
+         // the axes stay as they were when the drag started
+         let pointer = ui.input(|input| input.pointer.latest_pos()).map(to_vector);
+         if let Some(transform) = pointer.and_then(|pointer| drag.transform(&screen, self.gizmo_mode, pointer)) {
+            if let Some(node) = scene.node_mut(id) {
+               node.transform = transform;
+            }
+         }
+
+         if confirm || response.drag_stopped() || !ui.input(|input| input.pointer.primary_down()) {
+            if let Some(drag) = self.drag.take() {
+               history.edit_node(scene, id, drag.before);
+               history.seal();
+            }
+         }
+      }
+
+      let handles = gizmo::handles(&screen, self.gizmo_mode, world.position, axes);
+      let hovered = response.hover_pos().map(to_vector).and_then(|pointer| gizmo::hit_test(&handles, pointer));
+
+      let mut used = self.drag.is_some();
+      if self.drag.is_none() && response.drag_started_by(PointerButton::Primary) {
+         // drags start a few pixels after the press, the handle is the one that was pressed
+         let press = ui.input(|input| input.pointer.press_origin()).map(to_vector);
+         let pressed = press.and_then(|press| Some((press, gizmo::hit_test(&handles, press)?)));
+         if let (Some((press, handle)), Some(node)) = (pressed, scene.node(id)) {
+            self.drag = Some(GizmoDrag::new(id, handle, node.clone(), scene.parent_frame(id), press, axes));
+            used = true;
+         }
+      }
+
+      let highlighted = self.drag.as_ref().map(|drag| drag.handle).or(hovered);
+      gizmo::paint(&ui.painter_at(response.rect), &handles, highlighted);
+
+      used
+   }
+
+   fn fly_input(&mut self, ui: &Ui, response: &Response, camera: &mut Camera) {
+      if response.dragged() {
+         let delta = response.drag_delta();
+         camera.look(delta.x * TURN_SPEED, -delta.y * TURN_SPEED);
+      }
+
+      let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+      self.fly_speed = (self.fly_speed * (scroll * ZOOM_SPEED).exp()).clamp(0.01, 1000.0);
+
+      // a focused text field elsewhere keeps its keys
+      if ui.ctx().wants_keyboard_input() {
+         return;
+      }
+
+      let (movement, dt, fast) = ui.input(|input| {
+         let axis = |negative: Key, positive: Key| {
+            input.key_down(positive) as i32 as f32 - input.key_down(negative) as i32 as f32
+         };
+         let movement = Vector3::new(axis(Key::A, Key::D), axis(Key::Q, Key::E), axis(Key::S, Key::W));
+         (movement, input.stable_dt.min(0.1), input.modifiers.shift)
+      });
+
+      if movement != Vector3::new(0.0, 0.0, 0.0) {
+         let speed = if fast { self.fly_speed * 2.0 } else { self.fly_speed };
+         camera.fly(movement * speed * dt);
+         ui.ctx().request_repaint();
+      }
+   }
+}
+
+
+fn screen(camera: Camera, rect: egui::Rect) -> Screen {
+   Screen {
+      camera,
+      min: Vector2::new(rect.min.x, rect.min.y),
+      size: Vector2::new(rect.width(), rect.height()),
+   }
+}
+
+/// the node under the mouse, traced on the cpu so there's no gpu readback
+fn pick(ui: &Ui, response: &Response, document: &Document, solo: Option<NodeId>) -> Option<NodeId> {
+   let mouse = get_mouse_position(ui.ctx());
+   if !response.rect.contains(mouse) {
+      return None;
+   }
+
+   // only what's drawn can be picked
+   let soloed = solo.map(|id| document.scene.soloed(id));
+   let scene = soloed.as_ref().unwrap_or(&document.scene);
+
+   let (origin, direction) = screen(scene.camera, response.rect).ray(Vector2::new(mouse.x, mouse.y));
+   picking::pick(scene, origin, direction).map(|hit| hit.id)
+}
+
+fn orbit_input(ui: &Ui, response: &Response, camera: &mut Camera) {
+   let delta = response.drag_delta();
+   let panning = response.dragged_by(PointerButton::Secondary)
+       || response.dragged_by(PointerButton::Middle)
+       || (response.dragged_by(PointerButton::Primary) && ui.input(|input| input.modifiers.shift));
+
+   if panning {
+      // the point under the cursor at the target's depth stays under it
+      let pixel = camera.pixel_size(response.rect.height());
+      camera.pan(-delta.x * pixel, delta.y * pixel);
+   } else if response.dragged_by(PointerButton::Primary) {
+      camera.orbit(delta.x * TURN_SPEED, delta.y * TURN_SPEED);
+   }
+
+   let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+   if scroll != 0.0 {
+      camera.zoom((-scroll * ZOOM_SPEED).exp());
+   }
+}
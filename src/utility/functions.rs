@@ -13,12 +13,19 @@ pub fn wait(ms: u64) {
 }
 
 
-#[allow(dead_code)]
 pub fn get_mouse_position(ctx: &Context) -> Pos2 {
    ctx.input(|i| i.pointer.hover_pos().unwrap_or(Pos2::new(0.0, 0.0)))
 }
 
 
+/// same curve the `Rgba8UnormSrgb` targets apply on write
+pub fn linear_to_srgb8(linear: f32) -> u8 {
+   let linear = linear.clamp(0.0, 1.0);
+   let srgb = if linear <= 0.0031308 { linear * 12.92 } else { 1.055 * linear.powf(1.0 / 2.4) - 0.055 };
+   (srgb * 255.0).round() as u8
+}
+
+
 
 
 
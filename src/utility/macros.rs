@@ -1,47 +1,71 @@
+/// fields take an optional `=> range` and attributes for the widget:
+/// doc comments become tooltips, `#[logarithmic]`, `#[step(0.1)]`, `#[colour]` for `[f32; 3]` and `#[drag]`
+///
+/// `Name, fields...` makes a `Pod` struct with a collapsible `ui`. `struct Name, fields...` makes a plain
+/// serde struct for fields that aren't numbers, its derives and docs go before `struct` and it only gets
+/// `fields_ui`, for a hand written panel to wrap. `#[visible(|s: &Name| ...)]` hides a field while it returns false
 #[macro_export]
 macro_rules! defaults_and_sliders_gui {
-    ($name:ident, $($field_name:ident: $field_type:ty = $default:expr => $range:expr),*) => {
-        #[repr(C)]
-        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
+    ($(#[$meta:meta])* struct $name:ident, $($(#[$($attr:tt)*])* $field_name:ident: $field_type:ty = $default:expr $(=> $range:expr)?),* $(,)?) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(default)]
         pub struct $name {
             $(pub $field_name: $field_type,)*
         }
 
-        impl Default for $name {
-            fn default() -> Self {
-                Self {
-                    $($field_name: $default,)*
-                }
-            }
+        $crate::params_impl!(false, $name, $($(#[$($attr)*])* $field_name: $field_type = $default $(=> $range)?),*);
+    };
+    ($name:ident, $($(#[$($attr:tt)*])* $field_name:ident: $field_type:ty = $default:expr $(=> $range:expr)?),* $(,)?) => {
+        $crate::params_struct!(false, $name, $($(#[$($attr)*])* $field_name: $field_type = $default $(=> $range)?),*);
+    };
+}
+
+
+/// same as `defaults_and_sliders_gui!` but every number is a drag value
+#[macro_export]
+macro_rules! defaults_and_drag_value_gui {
+    ($name:ident, $($(#[$($attr:tt)*])* $field_name:ident: $field_type:ty = $default:expr $(=> $range:expr)?),* $(,)?) => {
+        $crate::params_struct!(true, $name, $($(#[$($attr)*])* $field_name: $field_type = $default $(=> $range)?),*);
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! params_struct {
+    ($drag:literal, $name:ident, $($(#[$($attr:tt)*])* $field_name:ident: $field_type:ty = $default:expr $(=> $range:expr)?),*) => {
+        #[repr(C)]
+        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(default)]
+        pub struct $name {
+            $(pub $field_name: $field_type,)*
         }
 
+        $crate::params_impl!($drag, $name, $($(#[$($attr)*])* $field_name: $field_type = $default $(=> $range)?),*);
+
         impl $name {
-            pub fn ui(&mut self, ui: &mut egui::Ui) {
+            /// returns true if any field changed
+            pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+                let mut changed = false;
                 egui::CollapsingHeader::new(stringify!($name))
                     .default_open(true)
                     .show(ui, |ui| {
-                        if ui.add(egui::Button::new("Reset")).clicked() { *self = Self::default() }
-
-                        $(ui.add(
-                            egui::Slider::new(&mut self.$field_name, $range)
-                            .text(stringify!($field_name))
-                        );)*
+                        if ui.add(egui::Button::new("Reset")).clicked() {
+                            *self = Self::default();
+                            changed = true;
+                        }
+                        changed |= self.fields_ui(ui);
                     });
+                changed
             }
         }
     };
 }
 
-
 #[macro_export]
-macro_rules! defaults_and_drag_value_gui {
-    ($name:ident, $($field_name:ident: $field_type:ty = $default:expr => $range:expr),*) => {
-        #[repr(C)]
-        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
-        pub struct $name {
-            $(pub $field_name: $field_type,)*
-        }
-
+#[doc(hidden)]
+macro_rules! params_impl {
+    ($drag:literal, $name:ident, $($(#[$($attr:tt)*])* $field_name:ident: $field_type:ty = $default:expr $(=> $range:expr)?),*) => {
         impl Default for $name {
             fn default() -> Self {
                 Self {
@@ -51,28 +75,49 @@ macro_rules! defaults_and_drag_value_gui {
         }
 
         impl $name {
-            pub fn ui(&mut self, ui: &mut egui::Ui) {
-                egui::CollapsingHeader::new(stringify!($name))
-                    .default_open(true)
-                    .show(ui, |ui| {
-                        if ui.add(egui::Button::new("Reset")).clicked() { *self = Self::default() }
-
-                        $(ui.add(
-                            egui::DragValue::new(&mut self.$field_name)
-                            .clamp_range($range)
-                            .prefix(stringify!($field_name))
-                        );)*
-                    });
+            /// just the field widgets, for drawing inside a bigger panel, returns true if any field changed
+            pub fn fields_ui(&mut self, ui: &mut egui::Ui) -> bool {
+                let mut changed = false;
+                $(
+                    if true $(&& $crate::field_visible!(self, $($attr)*))* {
+                        #[allow(unused_mut)]
+                        let mut options = $crate::utility::widgets::FieldOptions::new(stringify!($field_name));
+                        options.drag = $drag;
+                        $(options = options.with_range($range);)?
+                        $($crate::field_option!(options, $($attr)*);)*
+                        changed |= $crate::utility::widgets::ParamField::field_ui(&mut self.$field_name, ui, &options);
+                    }
+                )*
+                changed
             }
         }
     };
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! field_option {
+    ($options:ident, doc = $text:literal) => { $options.add_tooltip_line($text); };
+    ($options:ident, logarithmic) => { $options.logarithmic = true; };
+    ($options:ident, step($step:expr)) => { $options.step = Some($step as f64); };
+    ($options:ident, colour) => { $options.colour = true; };
+    ($options:ident, drag) => { $options.drag = true; };
+    ($options:ident, visible($visible:expr)) => {};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! field_visible {
+    ($target:expr, visible($visible:expr)) => { ($visible)(&*$target) };
+    ($target:expr, $($attr:tt)*) => { true };
+}
+
 #[macro_export]
 macro_rules! defaults_only_gui {
     ($name:ident, $($field_name:ident: $field_type:ty = $default:expr),*) => {
         #[repr(C)]
-        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
+        #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable, PartialEq, serde::Serialize, serde::Deserialize)]
+        #[serde(default)]
         pub struct $name {
             $(pub $field_name: $field_type,)*
         }
@@ -114,7 +159,7 @@ macro_rules! if_is_type {
 #[macro_export]
 macro_rules! enum_egui_dropdown {
     ($name: ident, $($option:tt),* ) => {
-        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
         pub enum $name {
             $($option,)*
         }
@@ -1,49 +1,49 @@
 use egui::TextureId;
-use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, BufferUsages, Extent3d, Queue, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
+use wgpu::{BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, BufferUsages, Extent3d, FilterMode, Queue, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use crate::inbuilt::gui_state::EguiRenderer;
 use crate::inbuilt::setup::Setup;
 
 /// to Ping Or Pong
-enum POP {
+enum Pop {
    First,
    Second,
 }
 pub struct PingPongData<T> {
    first: T,
    second: T,
-   current: POP,
+   current: Pop,
 }
 impl<T> PingPongData<T> {
    pub fn new(first: T, second: T) -> Self {
       Self {
          first,
          second,
-         current: POP::First,
+         current: Pop::First,
       }
    }
 
    pub fn pull_current(&self) -> &T {
       // send first
       match self.current {
-         POP::First => { &self.first }
-         POP::Second => { &self.second }
+         Pop::First => { &self.first }
+         Pop::Second => { &self.second }
       }
    }
 
    pub fn pull_other(&self) -> &T {
       // send not first
       match self.current {
-         POP::First => { &self.second }
-         POP::Second => { & self.first }
+         Pop::First => { &self.second }
+         Pop::Second => { & self.first }
       }
    }
 
    pub fn ping_pong(&mut self) {
       // swap
       self.current = match self.current {
-         POP::First => { POP::Second }
-         POP::Second => { POP::First }
+         Pop::First => { Pop::Second }
+         Pop::Second => { Pop::First }
       }
    }
 }
@@ -62,7 +62,7 @@ impl<T: bytemuck::Pod> UniformPackageSingles<T> {
 
       let buffer = setup.device.create_buffer_init(&BufferInitDescriptor {
          label: Some("UniformPackageSingles"),
-         contents: &*Vec::from(bytemuck::bytes_of(&data)),
+         contents: &Vec::from(bytemuck::bytes_of(&data)),
          usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
       });
 
@@ -112,6 +112,78 @@ impl<T: bytemuck::Pod> UniformPackageSingles<T> {
 
 
 
+/// read only storage buffer holding a list of `T`, grows its buffer when the data outgrows it
+pub struct StorageBufferPackage<T> {
+   pub bind_group: BindGroup,
+   pub layout: BindGroupLayout,
+   pub buffer: Buffer,
+   pub data: Vec<T>,
+}
+impl<T: bytemuck::Pod> StorageBufferPackage<T> {
+   pub fn create(setup: &Setup, shader_stages: ShaderStages, data: Vec<T>) -> StorageBufferPackage<T> {
+      let layout = setup.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+         label: Some("StorageBufferPackage"),
+         entries: &[
+            wgpu::BindGroupLayoutEntry {
+               binding: 0,
+               visibility: shader_stages,
+               ty: wgpu::BindingType::Buffer {
+                  ty: wgpu::BufferBindingType::Storage { read_only: true },
+                  has_dynamic_offset: false,
+                  min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+               },
+               count: None,
+            },
+         ],
+      });
+
+      let (buffer, bind_group) = Self::create_buffer(setup, &layout, &data);
+
+      StorageBufferPackage {
+         bind_group,
+         layout,
+         buffer,
+         data,
+      }
+   }
+
+   fn create_buffer(setup: &Setup, layout: &BindGroupLayout, data: &[T]) -> (Buffer, BindGroup) {
+      // empty storage bindings aren't allowed, keep at least one zeroed element around
+      let zeroed = [T::zeroed()];
+      let contents = if data.is_empty() { &zeroed[..] } else { data };
+
+      let buffer = setup.device.create_buffer_init(&BufferInitDescriptor {
+         label: Some("StorageBufferPackage"),
+         contents: bytemuck::cast_slice(contents),
+         usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+      });
+
+      let bind_group = setup.device.create_bind_group(&BindGroupDescriptor {
+         label: None,
+         layout,
+         entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding()
+         }],
+      });
+
+      (buffer, bind_group)
+   }
+
+   /// writes `data` in place when the length is unchanged, otherwise rebuilds the buffer and bind group
+   pub fn update_with_data(&mut self, setup: &Setup) {
+      let bytes = (self.data.len().max(1) * std::mem::size_of::<T>()) as u64;
+      if bytes == self.buffer.size() && !self.data.is_empty() {
+         setup.queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&self.data));
+      } else {
+         let (buffer, bind_group) = Self::create_buffer(setup, &self.layout, &self.data);
+         self.buffer = buffer;
+         self.bind_group = bind_group;
+      }
+   }
+}
+
+
 pub struct StorageTexturePackage {
    pub size: Extent3d,
    pub texture: Texture,
@@ -191,47 +263,76 @@ pub struct EguiTexturePackage {
    pub texture: Texture,
    pub view: TextureView,
    pub texture_id: TextureId,
+   /// the size wanted, the texture is remade to match on the next `update`
    pub size: Extent3d,
+   /// how egui samples the texture when it's drawn at a different size to its pixels
+   pub filter: FilterMode,
+   registered_filter: FilterMode,
+   max_side: u32,
 }
 impl EguiTexturePackage {
+   pub const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
    pub fn new(setup: &Setup, egui_renderer: &mut EguiRenderer, in_size: Extent3d) -> Self {
+      let (texture, view) = Self::create_texture(setup, in_size);
+      let filter = FilterMode::Linear;
+      let texture_id = egui_renderer.renderer.register_native_texture(
+         &setup.device,
+         &view,
+         filter
+      );
+
+      Self {
+         size: texture.size(),
+         texture,
+         view,
+         texture_id,
+         filter,
+         registered_filter: filter,
+         max_side: setup.device.limits().max_texture_dimension_2d,
+      }
+   }
+
+   /// the largest width or height the device can make a texture with
+   pub fn max_side(&self) -> u32 {
+      self.max_side
+   }
+
+   fn create_texture(setup: &Setup, in_size: Extent3d) -> (Texture, TextureView) {
+      let max_side = setup.device.limits().max_texture_dimension_2d;
       let size = Extent3d {
-         width: if in_size.width > 0 { in_size.width } else { 1 },
-         height: if in_size.height > 0 { in_size.height } else { 1 },
+         width: in_size.width.clamp(1, max_side),
+         height: in_size.height.clamp(1, max_side),
          depth_or_array_layers: 1,
       };
 
       let texture = setup.device.create_texture(&TextureDescriptor {
          label: Some("Egui Texture"),
-         size: size.clone(),
+         size,
          mip_level_count: 1,
          sample_count: 1,
          dimension: TextureDimension::D2,
-         format: TextureFormat::Rgba8UnormSrgb,
+         format: Self::FORMAT,
          usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
          view_formats: &[],
       });
 
       let view = texture.create_view(&TextureViewDescriptor::default());
-
-      let texture_id = egui_renderer.renderer.register_native_texture(
-         &setup.device,
-         &view,
-         wgpu::FilterMode::Linear
-      );
-
-      Self {
-         texture,
-         view,
-         texture_id,
-         size,
-      }
+      (texture, view)
    }
 
+   /// remakes the texture if the size changed, keeping the same id so egui carries on drawing it
    pub fn update(&mut self, setup: &Setup, egui_renderer: &mut EguiRenderer) {
-      if self.texture.size() != self.size {
-         let size = self.size;
-         *self = Self::new(&setup, egui_renderer, size)
+      self.size.width = self.size.width.clamp(1, self.max_side);
+      self.size.height = self.size.height.clamp(1, self.max_side);
+      let resized = self.texture.size() != self.size;
+      if resized {
+         (self.texture, self.view) = Self::create_texture(setup, self.size);
+      }
+
+      if resized || self.filter != self.registered_filter {
+         egui_renderer.renderer.update_egui_texture_from_wgpu_texture(&setup.device, &self.view, self.filter, self.texture_id);
+         self.registered_filter = self.filter;
       }
    }
 }
\ No newline at end of file
@@ -0,0 +1,148 @@
+use std::ops::RangeInclusive;
+use egui::{DragValue, Slider, Ui};
+
+/// per field settings gathered from the attributes in the `defaults_and_*_gui!` macros
+#[derive(Clone, Debug)]
+pub struct FieldOptions {
+   pub label: &'static str,
+   pub range: Option<RangeInclusive<f64>>,
+   pub tooltip: Option<String>,
+   pub logarithmic: bool,
+   pub step: Option<f64>,
+   /// drag values instead of sliders
+   pub drag: bool,
+   /// show a `[f32; 3]` as a colour picker
+   pub colour: bool,
+}
+impl FieldOptions {
+   pub fn new(label: &'static str) -> Self {
+      Self {
+         label,
+         range: None,
+         tooltip: None,
+         logarithmic: false,
+         step: None,
+         drag: false,
+         colour: false,
+      }
+   }
+
+   pub fn with_range<T: Copy + Into<f64>>(mut self, range: RangeInclusive<T>) -> Self {
+      self.range = Some((*range.start()).into()..=(*range.end()).into());
+      self
+   }
+
+   /// doc comments arrive one line at a time
+   pub fn add_tooltip_line(&mut self, line: &str) {
+      let tooltip = self.tooltip.get_or_insert_with(String::new);
+      if !tooltip.is_empty() {
+         tooltip.push(' ');
+      }
+      tooltip.push_str(line.trim());
+   }
+
+   fn drag_speed(&self) -> f64 {
+      self.step.unwrap_or(0.01)
+   }
+}
+
+
+/// a value the parameter macros know how to draw, returns true when it was changed
+pub trait ParamField {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool;
+}
+
+fn with_tooltip(response: egui::Response, options: &FieldOptions) -> egui::Response {
+   match &options.tooltip {
+      Some(tooltip) => response.on_hover_text(tooltip),
+      None => response,
+   }
+}
+
+fn number_ui<T: egui::emath::Numeric>(value: &mut T, ui: &mut Ui, options: &FieldOptions) -> bool {
+   let response = match (&options.range, options.drag) {
+      (Some(range), false) => {
+         let mut slider = Slider::new(value, T::from_f64(*range.start())..=T::from_f64(*range.end()))
+             .text(options.label)
+             .logarithmic(options.logarithmic);
+         if let Some(step) = options.step {
+            slider = slider.step_by(step);
+         }
+         ui.add(slider)
+      }
+      (range, _) => ui.horizontal(|ui| {
+         let mut drag = DragValue::new(value).speed(options.drag_speed());
+         if let Some(range) = range {
+            drag = drag.range(range.clone());
+         }
+         let response = ui.add(drag);
+         ui.label(options.label);
+         response
+      }).inner,
+   };
+   with_tooltip(response, options).changed()
+}
+
+impl ParamField for f32 {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      number_ui(self, ui, options)
+   }
+}
+
+impl ParamField for u32 {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      number_ui(self, ui, options)
+   }
+}
+
+impl ParamField for [f32; 3] {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      let response = ui.horizontal(|ui| {
+         if options.colour {
+            let response = ui.color_edit_button_rgb(self);
+            ui.label(options.label);
+            return response.changed();
+         }
+
+         let mut changed = false;
+         for value in self.iter_mut() {
+            let mut drag = DragValue::new(value).speed(options.drag_speed());
+            if let Some(range) = &options.range {
+               drag = drag.range(range.clone());
+            }
+            changed |= ui.add(drag).changed();
+         }
+         ui.label(options.label);
+         changed
+      });
+      let changed = response.inner;
+      with_tooltip(response.response, options);
+      changed
+   }
+}
+
+impl ParamField for cgmath::Vector3<f32> {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      let mut values: [f32; 3] = (*self).into();
+      let changed = values.field_ui(ui, options);
+      *self = values.into();
+      changed
+   }
+}
+
+impl ParamField for bool {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      with_tooltip(ui.checkbox(self, options.label), options).changed()
+   }
+}
+
+impl ParamField for String {
+   fn field_ui(&mut self, ui: &mut Ui, options: &FieldOptions) -> bool {
+      let response = ui.horizontal(|ui| {
+         let response = ui.add(egui::TextEdit::singleline(self).desired_width(120.0));
+         ui.label(options.label);
+         response
+      }).inner;
+      with_tooltip(response, options).changed()
+   }
+}